@@ -0,0 +1,367 @@
+use crate::error::{is_error_status, Error, Result};
+use crate::request::{self, *};
+use crate::response::{Event, GameData, GameStart, Joined, ServerHello, Session, Spectating};
+use crate::tls;
+use net_utils::encoding::{Encoding, PROTOCOL_VERSION};
+use net_utils::map::{GameDataType, Point};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+
+// any byte stream connected to the server (plain tcp, tls...)
+pub trait Stream: AsyncRead + AsyncWrite + Send + 'static {}
+impl<T: AsyncRead + AsyncWrite + Send + 'static> Stream for T {}
+
+// statuses the server sends on its own, never as the response of a request
+const UNSOLICITED_STATUSES: [u64; 2] = [SERV_SHUTDOWN, GM_KICKED];
+
+// request waiting for its response, told apart from the broadcasts of the game by
+// is_response (status, and pseudo or player number when the status isn't enough)
+struct Pending {
+    is_response: Box<dyn Fn(&Value) -> bool + Send>,
+    reply: oneshot::Sender<Value>,
+}
+
+type PendingSlot = Arc<Mutex<Option<Pending>>>;
+
+// packets received without request, in order
+pub struct Events(mpsc::UnboundedReceiver<Event>);
+impl Events {
+    // None once the connection is closed
+    pub async fn next(&mut self) -> Option<Event> {
+        self.0.recv().await
+    }
+
+    // next event if one was already received
+    pub fn try_next(&mut self) -> Option<Event> {
+        self.0.try_recv().ok()
+    }
+}
+
+pub struct GameClient {
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    // encoding of the requests, the reader task switches on its own once the reply is read
+    encoding: Encoding,
+    pending: PendingSlot,
+    pseudo: String,
+    player_token: String,
+    game_token: String,
+    player_num: String,
+}
+
+impl GameClient {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<(Self, Events)> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Ok(Self::from_stream(stream))
+    }
+
+    // the server certificate is verified against the ca certificate(s) in ca_path
+    pub async fn connect_tls(
+        addr: impl ToSocketAddrs,
+        server_name: &str,
+        ca_path: &Path,
+    ) -> Result<(Self, Events)> {
+        let stream = tls::connect(addr, server_name, ca_path).await?;
+        Ok(Self::from_stream(stream))
+    }
+
+    pub fn from_stream<S: Stream>(stream: S) -> (Self, Events) {
+        let (reader, writer) = tokio::io::split(stream);
+        let pending: PendingSlot = Arc::default();
+        let (events, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(read_packets(reader, pending.clone(), events));
+
+        let client = Self {
+            writer: Box::new(writer),
+            encoding: Encoding::Json,
+            pending,
+            pseudo: String::new(),
+            player_token: String::new(),
+            game_token: String::new(),
+            player_num: String::new(),
+        };
+        (client, Events(receiver))
+    }
+
+    pub fn pseudo(&self) -> &str {
+        &self.pseudo
+    }
+
+    // player token or session token, empty before create_player/register/login
+    pub fn player_token(&self) -> &str {
+        &self.player_token
+    }
+
+    // game created, joined or spectated last
+    pub fn game_token(&self) -> &str {
+        &self.game_token
+    }
+
+    // number of the player in the current game, empty when spectating
+    pub fn player_num(&self) -> &str {
+        &self.player_num
+    }
+
+    // send the request and wait for its response, error statuses are turned into errors (the
+    // unsolicited ones are events)
+    async fn request(
+        &mut self,
+        json: serde_json::Result<String>,
+        is_response: impl Fn(&Value) -> bool + Send + 'static,
+    ) -> Result<Value> {
+        let json = json?;
+        let (reply, response) = oneshot::channel();
+        *self.pending.lock().unwrap() = Some(Pending {
+            is_response: Box::new(move |r| {
+                r["status"].as_u64().is_some_and(is_error_response) || is_response(r)
+            }),
+            reply,
+        });
+        write_packet(&mut self.writer, self.encoding, &json).await?;
+
+        let response = response.await.map_err(|_| Error::Disconnected)?;
+        match response["status"].as_u64() {
+            Some(status) if is_error_status(status) => Err(Error::Status(status)),
+            _ => Ok(response),
+        }
+    }
+
+    // handshake, client is the name (and version) of the program, the packets following the
+    // response use the encoding; the server closes the connection if the protocol doesn't fit
+    pub async fn hello(&mut self, client: &str, encoding: Encoding) -> Result<ServerHello> {
+        let json = request::Hello::json_string(client, PROTOCOL_VERSION, encoding.name());
+        let response = self.request(json, status_is(OK_HELLO)).await?;
+        self.encoding = encoding;
+        Ok(ServerHello::deserialize(response)?)
+    }
+
+    pub async fn create_player(&mut self, pseudo: &str) -> Result<String> {
+        let json = PlayerCreation::json_string(pseudo);
+        let response = self.request(json, status_is(OK_PL_CREAT)).await?;
+        self.player_token = token(&response, "player_token")?;
+        self.pseudo = pseudo.into();
+
+        Ok(self.player_token.clone())
+    }
+
+    pub async fn register(&mut self, pseudo: &str, password: &str) -> Result<Session> {
+        let json = Credentials::json_string(REGISTER, pseudo, password);
+        let response = self.request(json, status_is(OK_REGISTER)).await?;
+        self.open_session(pseudo, response)
+    }
+
+    pub async fn login(&mut self, pseudo: &str, password: &str) -> Result<Session> {
+        let json = Credentials::json_string(LOGIN, pseudo, password);
+        let response = self.request(json, status_is(OK_LOGIN)).await?;
+        self.open_session(pseudo, response)
+    }
+
+    fn open_session(&mut self, pseudo: &str, response: Value) -> Result<Session> {
+        let session = Session::deserialize(response)?;
+        self.player_token = session.player_token.clone();
+        self.pseudo = pseudo.into();
+
+        Ok(session)
+    }
+
+    pub async fn create_game(&mut self) -> Result<String> {
+        let json = GameCreation::json_string(&self.player_token);
+        let response = self.request(json, status_is(OK_GM_CREAT)).await?;
+        self.game_token = token(&response, "game_token")?;
+        // the host is always the first player
+        self.player_num = "1".into();
+
+        Ok(self.game_token.clone())
+    }
+
+    pub async fn join_game(&mut self, game_token: &str) -> Result<Joined> {
+        let json = GameJoining::json_string(&self.player_token, game_token);
+        let pseudo = self.pseudo.clone();
+        let response = self
+            .request(json, move |r| {
+                r["status"] == OK_GM_JOIN && r["pseudo"] == pseudo.as_str()
+            })
+            .await?;
+        let joined = Joined::deserialize(response)?;
+        self.player_num = match joined.players.iter().find(|p| p.pseudo == self.pseudo) {
+            Some(player) => player.player_num.clone(),
+            None => return Err(Error::Protocol("player missing from the game".into())),
+        };
+        self.game_token = game_token.into();
+
+        Ok(joined)
+    }
+
+    // the events of the game are received without taking part in it
+    pub async fn spectate(&mut self, game_token: &str) -> Result<Spectating> {
+        let json = GameSpectating::json_string(&self.player_token, game_token);
+        let response = self.request(json, status_is(OK_GM_SPECTATE)).await?;
+        self.game_token = game_token.into();
+        self.player_num.clear();
+
+        Ok(Spectating::deserialize(response)?)
+    }
+
+    // host only, difficulty is easy, normal or hard
+    pub async fn add_bot(&mut self, difficulty: &str) -> Result<Joined> {
+        let json = BotAdding::json_string(&self.player_token, &self.game_token, difficulty);
+        let response = self.request(json, status_is(OK_GM_JOIN)).await?;
+        Ok(Joined::deserialize(response)?)
+    }
+
+    pub async fn choose_character(&mut self, character: &str) -> Result<()> {
+        let json = ChooseCharacter::json_string(&self.player_token, &self.game_token, character);
+        let pseudo = self.pseudo.clone();
+        self.request(json, move |r| {
+            r["status"] == OK_CHAR_CHOOSING && r["pseudo"] == pseudo.as_str()
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    // host only, the other players get the game start as an event
+    pub async fn start_game(&mut self) -> Result<GameStart> {
+        let json = GameStarting::json_string(&self.player_token, &self.game_token);
+        let response = self.request(json, status_is(OK_GM_START)).await?;
+        Ok(GameStart::deserialize(response)?)
+    }
+
+    // current map (as seen by the player) and state version, after a gap in the game data
+    pub async fn snapshot(&mut self) -> Result<GameStart> {
+        let json = GameSnapshot::json_string(&self.player_token, &self.game_token);
+        let response = self.request(json, status_is(OK_GM_SNAPSHOT)).await?;
+        Ok(GameStart::deserialize(response)?)
+    }
+
+    pub async fn send_game_data(&mut self, game_data: GameDataType) -> Result<GameData> {
+        let (gm_code, target) = match game_data {
+            GameDataType::Movement(pos) => (GM_DATA_MOV, pos),
+            GameDataType::Attack(pos) => (GM_DATA_ATK, pos),
+            GameDataType::Skip => (GM_DATA_SKIP, Point(-1, -1)),
+        };
+        let json =
+            request::GameData::json_string(gm_code, &self.player_token, &self.game_token, target);
+        let player_num = self.player_num.clone();
+        let response = self
+            .request(json, move |r| {
+                r["status"] == OK_GM_DATA && r["player_num"] == player_num.as_str()
+            })
+            .await?;
+
+        Ok(GameData::deserialize(response)?)
+    }
+
+    pub async fn terminate(mut self) -> Result<()> {
+        let json = TerminateConnection::json_string();
+        self.request(json, status_is(OK_TERM_CON)).await?;
+        Ok(())
+    }
+}
+
+fn is_error_response(status: u64) -> bool {
+    is_error_status(status) && !UNSOLICITED_STATUSES.contains(&status)
+}
+
+fn status_is(status: u64) -> impl Fn(&Value) -> bool + Send + 'static {
+    move |r| r["status"] == status
+}
+
+fn token(response: &Value, field: &str) -> Result<String> {
+    match response[field].as_str() {
+        // uuid v4 length
+        Some(token) if token.len() == 36 => Ok(token.into()),
+        _ => Err(Error::Protocol(format!("invalid {field}"))),
+    }
+}
+
+// InvalidData if the length is over the maximum of the encoding, the buffer is allocated
+// from it
+async fn read_packet<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    encoding: Encoding,
+) -> io::Result<Vec<u8>> {
+    let mut header = [0; 4];
+    let header = &mut header[..encoding.header_size()];
+    stream.read_exact(header).await?;
+    let size = encoding.packet_size(header);
+    if size > encoding.max_packet_size() {
+        let message = format!("packet of {size} bytes refused");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    let mut packet = vec![0; size];
+    stream.read_exact(&mut packet).await?;
+
+    Ok(packet)
+}
+
+async fn write_packet<S: AsyncWrite + Unpin + ?Sized>(
+    stream: &mut S,
+    encoding: Encoding,
+    json: &str,
+) -> Result<()> {
+    let payload = match encoding {
+        Encoding::Json => json.as_bytes().to_vec(),
+        encoding => encoding.encode(&serde_json::from_str(json)?),
+    };
+    let packet = encoding
+        .frame(&payload)
+        .ok_or_else(|| Error::Protocol("request too large".into()))?;
+    stream.write_all(&packet).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+// hand the responses to the pending request and everything else to the events, until the
+// connection is closed
+async fn read_packets<S: Stream>(
+    mut reader: ReadHalf<S>,
+    pending: PendingSlot,
+    events: mpsc::UnboundedSender<Event>,
+) {
+    let mut encoding = Encoding::Json;
+    while let Ok(packet) = read_packet(&mut reader, encoding).await {
+        let json = match encoding.decode(&packet) {
+            Some(json) => json,
+            None => continue,
+        };
+        // the packets after the hello reply are in the new encoding
+        if json["status"] == OK_HELLO {
+            if let Some(e) = json["encoding"].as_str().and_then(Encoding::from_name) {
+                encoding = e;
+            }
+        }
+
+        let response = {
+            let mut pending = pending.lock().unwrap();
+            match pending.take() {
+                Some(p) if (p.is_response)(&json) => Some(p.reply),
+                p => {
+                    *pending = p;
+                    None
+                }
+            }
+        };
+        match response {
+            Some(reply) => {
+                let _ = reply.send(json);
+            }
+            None => {
+                let _ = events.send(Event::from_json(json));
+            }
+        }
+    }
+
+    // the pending request fails with Error::Disconnected
+    pending.lock().unwrap().take();
+}
@@ -0,0 +1,94 @@
+use net_utils::packet::status_codes::*;
+use std::fmt;
+use std::io;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    // invalid ca certificate or failed handshake
+    Tls(String),
+    // the server answered with an error status
+    Status(u64),
+    // response not following the protocol
+    Protocol(String),
+    // connection closed before the response
+    Disconnected,
+}
+
+impl Error {
+    // status of the server error, if the server answered
+    pub fn status(&self) -> Option<u64> {
+        match self {
+            Self::Status(status) => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+// error statuses are 30 to 49 and 70 to 79, every other status is a success
+pub fn is_error_status(status: u64) -> bool {
+    is_error(status)
+}
+
+fn status_description(status: u64) -> &'static str {
+    match status {
+        ERR_INTERNAL_SERV => "internal server error",
+        ERR_MAL_REQ => "malformed request",
+        ERR_INV_PSEUD => "invalid pseudo",
+        ERR_INV_PL_TOK => "invalid player token",
+        ERR_INV_GM_TOK => "invalid game token",
+        ERR_GM_AL_START => "game already started",
+        ERR_GM_FULL => "game full",
+        ERR_GM_NOT_JOIN => "game not joined",
+        ERR_GM_NOT_FULL => "game not full",
+        ERR_GM_NOT_START => "game not started",
+        ERR_SERV_FULL => "server full",
+        ERR_THROTTLED => "too many requests",
+        ERR_PL_LIMIT => "too many players created on the connection",
+        SERV_SHUTDOWN => "server shutting down",
+        ERR_NOT_TURN => "not your turn",
+        ERR_PSEUD_TAKEN => "pseudo already registered",
+        ERR_INV_LOGIN => "unknown pseudo or wrong password",
+        ERR_INV_PASSWD => "invalid password",
+        ERR_NO_STATS => "no statistics for this player",
+        ERR_UNSUP_PROTO => "protocol not supported by the server",
+        ERR_WRONG_STATE => "request not allowed now",
+        GM_KICKED => "kicked from the game",
+        _ => "unknown error",
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "connection error: {e}"),
+            Self::Tls(e) => write!(f, "tls error: {e}"),
+            Self::Status(status) => write!(f, "{} ({status})", status_description(*status)),
+            Self::Protocol(e) => write!(f, "unexpected response: {e}"),
+            Self::Disconnected => write!(f, "disconnected from the server"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Protocol(e.to_string())
+    }
+}
@@ -0,0 +1,12 @@
+// async client for the game protocol, shared by the cli client and the other tools
+mod client;
+mod error;
+mod map_state;
+mod request;
+mod response;
+mod tls;
+
+pub use client::{Events, GameClient, Stream};
+pub use error::{is_error_status, Error, Result};
+pub use map_state::{MapState, Update};
+pub use response::*;
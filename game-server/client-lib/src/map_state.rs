@@ -0,0 +1,54 @@
+use crate::error::{Error, Result};
+use crate::response::{GameData, GameStart};
+use net_utils::map::GameMap;
+
+// map of a started game kept up to date with the changes of the game data packets, each
+// packet is one version ahead of the previous one
+#[derive(Clone, Debug)]
+pub struct MapState {
+    map: GameMap,
+    version: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Update {
+    Applied,
+    // older than the map (received while a snapshot was requested), ignored
+    Outdated,
+    // packets were missed: the map must be replaced by a snapshot
+    Gap,
+}
+
+impl MapState {
+    // from the game start or a snapshot
+    pub fn new(start: &GameStart) -> Result<Self> {
+        let map = start
+            .map
+            .parse()
+            .map_err(|e| Error::Protocol(format!("invalid map: {e}")))?;
+        Ok(Self {
+            map,
+            version: start.version,
+        })
+    }
+
+    pub fn map(&self) -> &GameMap {
+        &self.map
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn apply(&mut self, data: &GameData) -> Update {
+        if data.version <= self.version {
+            return Update::Outdated;
+        }
+        if data.version > self.version + 1 {
+            return Update::Gap;
+        }
+        self.map.apply(&data.changes);
+        self.version = data.version;
+        Update::Applied
+    }
+}
@@ -1,4 +1,3 @@
-use net_utils::character::CharacterClass;
 use net_utils::map::Point;
 use net_utils::packet::request_codes::*;
 use serde::{Deserialize, Serialize};
@@ -17,6 +16,27 @@ impl<'a> PlayerCreation<'a> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct Credentials<'a> {
+    request_type: u64,
+    pseudo: &'a str,
+    password: &'a str,
+}
+impl<'a> Credentials<'a> {
+    // request_type is REGISTER or LOGIN
+    pub fn json_string(
+        request_type: u64,
+        pseudo: &'a str,
+        password: &'a str,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type,
+            pseudo,
+            password,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GameCreation<'a> {
     request_type: u64,
@@ -47,6 +67,44 @@ impl<'a> GameJoining<'a> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GameSpectating<'a> {
+    request_type: u64,
+    player_token: &'a str,
+    game_token: &'a str,
+}
+impl<'a> GameSpectating<'a> {
+    pub fn json_string(player_token: &'a str, game_token: &'a str) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type: GM_SPECTATE,
+            player_token,
+            game_token,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BotAdding<'a> {
+    request_type: u64,
+    player_token: &'a str,
+    game_token: &'a str,
+    difficulty: &'a str,
+}
+impl<'a> BotAdding<'a> {
+    pub fn json_string(
+        player_token: &'a str,
+        game_token: &'a str,
+        difficulty: &'a str,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type: ADD_BOT,
+            player_token,
+            game_token,
+            difficulty,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GameStarting<'a> {
     request_type: u64,
@@ -63,6 +121,22 @@ impl<'a> GameStarting<'a> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct GameSnapshot<'a> {
+    request_type: u64,
+    player_token: &'a str,
+    game_token: &'a str,
+}
+impl<'a> GameSnapshot<'a> {
+    pub fn json_string(player_token: &'a str, game_token: &'a str) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type: GM_SNAPSHOT,
+            player_token,
+            game_token,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ChooseCharacter<'a> {
     request_type: u64,
@@ -109,3 +183,37 @@ impl<'a> GameData<'a> {
         })
     }
 }
+
+#[derive(Serialize, Deserialize)]
+pub struct TerminateConnection {
+    request_type: u64,
+}
+impl TerminateConnection {
+    pub fn json_string() -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type: TERM_CON,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Hello<'a> {
+    request_type: u64,
+    client: &'a str,
+    version: u64,
+    encoding: &'a str,
+}
+impl<'a> Hello<'a> {
+    pub fn json_string(
+        client: &'a str,
+        version: u64,
+        encoding: &'a str,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            request_type: HELLO,
+            client,
+            version,
+            encoding,
+        })
+    }
+}
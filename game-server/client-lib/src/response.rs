@@ -0,0 +1,158 @@
+use net_utils::item::Item;
+use net_utils::map::TileChange;
+use net_utils::packet::status_codes::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+// player of a game, from the [player_number, pseudo, character, is_host, team] entries of
+// the server
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(from = "[String; 5]")]
+pub struct Player {
+    pub player_num: String,
+    pub pseudo: String,
+    // empty until the character is chosen
+    pub character: String,
+    pub host: bool,
+    // "1" or "2", empty if the game has no teams
+    pub team: String,
+}
+impl From<[String; 5]> for Player {
+    fn from([player_num, pseudo, character, host, team]: [String; 5]) -> Self {
+        Self {
+            player_num,
+            pseudo,
+            character,
+            host: host == "1",
+            team,
+        }
+    }
+}
+
+// handshake reply, what the server supports
+#[derive(Deserialize, Clone, Debug)]
+pub struct ServerHello {
+    // name and version of the server
+    pub server: String,
+    // protocol version and encoding of the connection
+    pub version: u64,
+    pub encoding: String,
+    pub versions: Vec<u64>,
+    pub encodings: Vec<String>,
+    pub capabilities: Vec<String>,
+}
+impl ServerHello {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Session {
+    pub player_token: String,
+    // unix time (seconds) after which the session token is refused
+    pub expires: u64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Joined {
+    // pseudo of the player who joined (or left)
+    pub pseudo: String,
+    #[serde(rename = "player_vec")]
+    pub players: Vec<Player>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Spectating {
+    #[serde(rename = "player_vec")]
+    pub players: Vec<Player>,
+    pub started: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CharacterChoice {
+    pub pseudo: String,
+    pub character: String,
+}
+
+// also the response to a snapshot request
+#[derive(Deserialize, Clone, Debug)]
+pub struct GameStart {
+    pub player_turn: String,
+    pub map: String,
+    // state version of the map
+    pub version: u32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GameData {
+    // GM_DATA_MOV, GM_DATA_ATK or GM_DATA_SKIP
+    pub data_type: u64,
+    // player who played
+    pub player_num: String,
+    pub player_turn: String,
+    // state version after the action, see MapState
+    pub version: u32,
+    // tiles of the map that changed
+    pub changes: Vec<TileChange>,
+    // (enemy_number, enemy_remaining_hp), only set by attacks
+    pub enemy: (String, u8),
+    // item picked up by the player who played
+    pub item: Option<Item>,
+    // atk, hp, ms, rng of the player who played, items included
+    pub stats: (u8, u8, u8, u8),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GameOver {
+    // number of the last player alive (numbers of the players of the winning team in team
+    // games), empty when the game was ended by the server operators
+    pub winner: String,
+}
+
+// packet sent by the server without request from this client
+#[derive(Clone, Debug)]
+pub enum Event {
+    PlayerJoined(Joined),
+    // removed from the game before its start by the server operators, with the players left
+    PlayerLeft(Joined),
+    CharacterChosen(CharacterChoice),
+    GameStarted(GameStart),
+    // action of another player, or a turn skipped by the server
+    GameData(GameData),
+    GameOver(GameOver),
+    // the connection is closed right after, started games can be joined again later
+    ServerShutdown,
+    // message of the server operators
+    Notice(String),
+    // removed from the game by the server operators, back in the lobby
+    Kicked,
+    // error status received without a pending request
+    Error(u64),
+    // anything this version of the library doesn't know about
+    Other(Value),
+}
+
+impl Event {
+    pub fn from_json(json: Value) -> Self {
+        let event = match json["status"].as_u64() {
+            Some(OK_GM_JOIN) => Joined::deserialize(&json).map(Self::PlayerJoined),
+            Some(GM_PL_LEFT) => Joined::deserialize(&json).map(Self::PlayerLeft),
+            Some(OK_CHAR_CHOOSING) => {
+                CharacterChoice::deserialize(&json).map(Self::CharacterChosen)
+            }
+            Some(OK_GM_START) => GameStart::deserialize(&json).map(Self::GameStarted),
+            Some(OK_GM_DATA) => GameData::deserialize(&json).map(Self::GameData),
+            Some(OK_GM_OVER) => GameOver::deserialize(&json).map(Self::GameOver),
+            Some(SERV_SHUTDOWN) => Ok(Self::ServerShutdown),
+            Some(SERV_NOTICE) => match json["message"].as_str() {
+                Some(message) => Ok(Self::Notice(message.into())),
+                None => return Self::Other(json),
+            },
+            Some(GM_KICKED) => Ok(Self::Kicked),
+            Some(status) if crate::error::is_error_status(status) => Ok(Self::Error(status)),
+            _ => return Self::Other(json),
+        };
+        event.unwrap_or(Self::Other(json))
+    }
+}
@@ -0,0 +1,40 @@
+use crate::error::{Error, Result};
+use rustls_pemfile::certs;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+// connect to the server and verify its certificate against the ca certificate(s) in ca_path
+pub async fn connect(
+    addr: impl ToSocketAddrs,
+    server_name: &str,
+    ca_path: &Path,
+) -> Result<TlsStream<TcpStream>> {
+    let tls_error = |e: &dyn std::fmt::Display| Error::Tls(format!("{}: {e}", ca_path.display()));
+    let mut reader = BufReader::new(File::open(ca_path).map_err(|e| tls_error(&e))?);
+    let mut roots = RootCertStore::empty();
+    for cert in certs(&mut reader) {
+        let cert = cert.map_err(|e| tls_error(&e))?;
+        roots.add(cert).map_err(|e| tls_error(&e))?;
+    }
+    if roots.is_empty() {
+        return Err(tls_error(&"no ca certificate found"));
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(server_name.to_owned())
+        .map_err(|e| Error::Tls(format!("invalid server name {server_name}: {e}")))?;
+    let tcp = TcpStream::connect(addr).await?;
+    TlsConnector::from(Arc::new(config))
+        .connect(server_name, tcp)
+        .await
+        .map_err(|e| Error::Tls(e.to_string()))
+}
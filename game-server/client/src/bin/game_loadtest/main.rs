@@ -0,0 +1,87 @@
+// load test: pairs of simulated players playing random legal moves against a server, one
+// game after the other, then a report of the throughput, latencies and errors
+// meant for a local server with the memory backend and the request limits raised (every
+// simulated player connects from the same address, throttled requests are reported), e.g.:
+// game_server --storage memory --requests-per-second 1000 --request-burst 1000 \
+//     --ip-requests-per-second 100000 --ip-request-burst 100000 --max-games 100000
+mod pair;
+mod report;
+
+use clap::Parser;
+use net_utils::encoding::Encoding;
+use pair::{run_pair, Settings};
+use report::Report;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{interval, Instant};
+
+#[derive(Parser, Debug)]
+#[command(about = "load test of the game server")]
+struct Args {
+    #[arg(long, env = "GAME_SERVER_ADDR", default_value = "127.0.0.1:8000")]
+    addr: String,
+    // games played at the same time, two connections each
+    #[arg(long, default_value_t = 10)]
+    pairs: usize,
+    // actions per second in each game (the two players play in turn)
+    #[arg(long, default_value_t = 4.0)]
+    rate: f64,
+    // seconds, the games in progress are abandoned after it
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+    // json or msgpack
+    #[arg(long, default_value = "json")]
+    encoding: String,
+}
+
+// seconds between two progress lines
+const PROGRESS_INTERVAL: u64 = 5;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let encoding = match Encoding::from_name(&args.encoding) {
+        Some(e) => e,
+        None => anyhow::bail!("unknown encoding {}, json or msgpack", args.encoding),
+    };
+    if !(args.rate > 0.0 && args.rate.is_finite()) {
+        anyhow::bail!("rate must be a positive number of actions per second");
+    }
+
+    let start = Instant::now();
+    let settings = Arc::new(Settings {
+        addr: args.addr,
+        encoding,
+        action_interval: Duration::from_secs_f64(1.0 / args.rate),
+        deadline: start + Duration::from_secs(args.duration),
+    });
+    let report = Arc::new(Mutex::new(Report::default()));
+    println!(
+        "{} pairs against {} for {}s, {} actions/s per game",
+        args.pairs, settings.addr, args.duration, args.rate
+    );
+
+    let mut pairs = JoinSet::new();
+    for id in 0..args.pairs {
+        pairs.spawn(run_pair(id, Arc::clone(&settings), Arc::clone(&report)));
+    }
+
+    let mut progress = interval(Duration::from_secs(PROGRESS_INTERVAL));
+    progress.tick().await;
+    loop {
+        tokio::select! {
+            _ = progress.tick() => {
+                let elapsed = start.elapsed().as_secs();
+                println!("[{elapsed}s] {}", report.lock().unwrap().progress());
+            },
+            pair = pairs.join_next() => match pair {
+                Some(_) => continue,
+                None => break,
+            },
+        }
+    }
+
+    report.lock().unwrap().print(start.elapsed(), args.pairs);
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use crate::report::Report;
+use anyhow::bail;
+use game_client_lib::{Error, Event, Events, GameClient, GameData, MapState, Update};
+use net_utils::character::CharacterClass;
+use net_utils::encoding::Encoding;
+use net_utils::map::{GameDataType, GameMap};
+use net_utils::packet::game_data_code::GM_DATA_ATK;
+use net_utils::packet::status_codes::{ERR_NOT_TURN, ERR_THROTTLED};
+use net_utils::rules::{attackable_tiles, chebyshev_distance, reachable_tiles};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{sleep, timeout, Instant};
+
+// an event expected from the server that doesn't come means the pair is stuck
+const EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+// games longer than this are given up (the players may be walled off from each other)
+const MAX_ACTIONS: u32 = 300;
+
+enum Outcome {
+    Over,
+    // the players are still in the game, new connections are needed for the next one
+    Abandoned,
+}
+
+pub struct Settings {
+    pub addr: String,
+    pub encoding: Encoding,
+    // time waited by a player before each action
+    pub action_interval: Duration,
+    // the games in progress are abandoned
+    pub deadline: Instant,
+}
+
+struct Simulated {
+    client: GameClient,
+    events: Events,
+    // atk, hp, ms, rng of the character of the current game
+    stats: (u8, u8, u8, u8),
+}
+
+// plays games until the deadline, the error stopping the pair is reported
+pub async fn run_pair(id: usize, settings: Arc<Settings>, report: Arc<Mutex<Report>>) {
+    if let Err(e) = play_games(id, &settings, &report).await {
+        report.lock().unwrap().pair_stopped(e.to_string());
+    }
+}
+
+async fn play_games(id: usize, settings: &Settings, report: &Mutex<Report>) -> anyhow::Result<()> {
+    let mut rng = StdRng::from_entropy();
+    let mut players = [
+        connect(settings, &format!("host{id}")).await?,
+        connect(settings, &format!("guest{id}")).await?,
+    ];
+    loop {
+        let outcome = play_game(&mut players, settings, report, &mut rng).await?;
+        let over = matches!(outcome, Outcome::Over);
+        report.lock().unwrap().game_ended(over);
+        if Instant::now() >= settings.deadline {
+            break;
+        }
+        if !over {
+            players = [
+                connect(settings, &format!("host{id}")).await?,
+                connect(settings, &format!("guest{id}")).await?,
+            ];
+        }
+    }
+    // the connection closes in a game too
+    for player in players {
+        player.client.terminate().await?;
+    }
+    Ok(())
+}
+
+async fn connect(settings: &Settings, pseudo: &str) -> anyhow::Result<Simulated> {
+    let (mut client, events) = GameClient::connect(&settings.addr).await?;
+    let name = concat!("game-loadtest/", env!("CARGO_PKG_VERSION"));
+    client.hello(name, settings.encoding).await?;
+    client.create_player(pseudo).await?;
+    Ok(Simulated {
+        client,
+        events,
+        stats: (0, 0, 0, 0),
+    })
+}
+
+// first event matching, the others (characters chosen...) are skipped
+async fn wait_event<T>(
+    events: &mut Events,
+    matching: impl Fn(Event) -> Option<T>,
+) -> anyhow::Result<T> {
+    let wait = async {
+        while let Some(event) = events.next().await {
+            if let Some(found) = matching(event) {
+                return Ok(found);
+            }
+        }
+        Err(Error::Disconnected.into())
+    };
+    match timeout(EVENT_TIMEOUT, wait).await {
+        Ok(result) => result,
+        Err(_) => bail!("no response from the server for {EVENT_TIMEOUT:?}"),
+    }
+}
+
+async fn game_data(events: &mut Events) -> anyhow::Result<GameData> {
+    wait_event(events, |event| match event {
+        Event::GameData(data) => Some(data),
+        _ => None,
+    })
+    .await
+}
+
+async fn game_over(events: &mut Events) -> anyhow::Result<()> {
+    wait_event(events, |event| match event {
+        Event::GameOver(_) => Some(()),
+        _ => None,
+    })
+    .await
+}
+
+async fn play_game(
+    players: &mut [Simulated; 2],
+    settings: &Settings,
+    report: &Mutex<Report>,
+    rng: &mut StdRng,
+) -> anyhow::Result<Outcome> {
+    let [host, guest] = players;
+    let game_token = host.client.create_game().await?;
+    guest.client.join_game(&game_token).await?;
+    for player in [&mut *host, &mut *guest] {
+        let character = *["mag", "bar", "bow"].choose(rng).unwrap();
+        player.stats = CharacterClass::new(character).unwrap().get_stats();
+        player.client.choose_character(character).await?;
+    }
+    let start = host.client.start_game().await?;
+    wait_event(&mut guest.events, |event| match event {
+        Event::GameStarted(_) => Some(()),
+        _ => None,
+    })
+    .await?;
+
+    let mut map = MapState::new(&start)?;
+    let mut turn = start.player_turn;
+    for _ in 0..MAX_ACTIONS {
+        sleep(settings.action_interval).await;
+        if Instant::now() >= settings.deadline {
+            break;
+        }
+        let (player, other) = match &*turn {
+            "1" => (&mut *host, &mut *guest),
+            _ => (&mut *guest, &mut *host),
+        };
+        let action = random_action(map.map(), &turn, player.stats, rng);
+
+        let sent = Instant::now();
+        let data = match player.client.send_game_data(action).await {
+            Ok(data) => data,
+            // the action is tried again
+            Err(e @ Error::Status(ERR_THROTTLED)) => {
+                report.lock().unwrap().error(e.to_string());
+                continue;
+            }
+            // turn skipped by the server (turn timer), back in sync with a snapshot
+            Err(e @ Error::Status(ERR_NOT_TURN)) => {
+                report.lock().unwrap().error(e.to_string());
+                let snapshot = player.client.snapshot().await?;
+                map = MapState::new(&snapshot)?;
+                turn = snapshot.player_turn;
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        report.lock().unwrap().action(sent.elapsed());
+        game_data(&mut other.events).await?;
+
+        if map.apply(&data) != Update::Applied {
+            map = MapState::new(&player.client.snapshot().await?)?;
+        }
+        // two players: the first one dead ends the game
+        if data.data_type == GM_DATA_ATK && data.enemy.1 == 0 {
+            game_over(&mut player.events).await?;
+            game_over(&mut other.events).await?;
+            return Ok(Outcome::Over);
+        }
+        turn = data.player_turn;
+    }
+    Ok(Outcome::Abandoned)
+}
+
+// attack when an enemy is in range, otherwise move (half of the time as close to the enemy as
+// possible, to end the games), skip when the player can't move
+fn random_action(
+    map: &GameMap,
+    player_num: &str,
+    (_, _, ms, range): (u8, u8, u8, u8),
+    rng: &mut StdRng,
+) -> GameDataType {
+    let (player, enemy) = match player_num {
+        "1" => ('1', '2'),
+        _ => ('2', '1'),
+    };
+    if let Some(target) = attackable_tiles(map, player, range).choose(rng) {
+        return GameDataType::Attack(*target);
+    }
+
+    let tiles = reachable_tiles(map, player, ms);
+    let dest = match map.find(enemy) {
+        Some(enemy) if rng.gen_bool(0.5) => tiles
+            .iter()
+            .min_by_key(|tile| chebyshev_distance(**tile, enemy)),
+        _ => tiles.choose(rng),
+    };
+    dest.map_or(GameDataType::Skip, |dest| GameDataType::Movement(*dest))
+}
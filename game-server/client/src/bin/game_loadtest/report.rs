@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+// results shared by every pair
+#[derive(Default)]
+pub struct Report {
+    // game data request sent -> response received
+    latencies: Vec<Duration>,
+    games: u64,
+    // stopped by the deadline or too long
+    games_abandoned: u64,
+    // errors (status code included) -> times seen
+    errors: BTreeMap<String, u64>,
+    // pairs whose connections failed before the end
+    pairs_stopped: usize,
+}
+
+// value under which the fraction of the sorted durations are
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    match sorted.len() {
+        0 => Duration::ZERO,
+        len => sorted[((len - 1) as f64 * fraction).round() as usize],
+    }
+}
+
+impl Report {
+    pub fn action(&mut self, latency: Duration) {
+        self.latencies.push(latency);
+    }
+
+    pub fn game_ended(&mut self, over: bool) {
+        match over {
+            true => self.games += 1,
+            false => self.games_abandoned += 1,
+        }
+    }
+
+    pub fn error(&mut self, error: String) {
+        *self.errors.entry(error).or_default() += 1;
+    }
+
+    pub fn pair_stopped(&mut self, error: String) {
+        self.pairs_stopped += 1;
+        self.error(error);
+    }
+
+    pub fn progress(&self) -> String {
+        format!(
+            "games {}, actions {}, errors {}",
+            self.games,
+            self.latencies.len(),
+            self.errors.values().sum::<u64>()
+        )
+    }
+
+    pub fn print(&mut self, elapsed: Duration, pairs: usize) {
+        let secs = elapsed.as_secs_f64();
+        let actions = self.latencies.len();
+        println!("\n{pairs} pairs, {:.1}s", secs);
+        println!(
+            "games finished: {} ({:.2}/s)",
+            self.games,
+            self.games as f64 / secs
+        );
+        println!("games abandoned: {}", self.games_abandoned);
+        println!("actions: {actions} ({:.1}/s)", actions as f64 / secs);
+
+        self.latencies.sort();
+        let latencies = &self.latencies;
+        println!(
+            "action latency: p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+            percentile(latencies, 0.5),
+            percentile(latencies, 0.9),
+            percentile(latencies, 0.99),
+            latencies.last().copied().unwrap_or_default()
+        );
+
+        if self.pairs_stopped > 0 {
+            println!("pairs stopped by an error: {}", self.pairs_stopped);
+        }
+        if self.errors.is_empty() {
+            println!("no errors");
+        } else {
+            println!("errors:");
+            for (error, count) in &self.errors {
+                println!("  {error}: {count}");
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use game_client_lib::{Event, GameData, GameStart, Joined, MapState, Player, Update};
+use net_utils::character::Character;
+use net_utils::map::{GameDataType, GameMap, Point, HIDDEN_TILE};
+use net_utils::packet::game_data_code::*;
+use net_utils::rules::{attack_target, attackable_tiles, can_move, reachable_tiles};
+
+// lines kept in the event log
+const LOG_SIZE: usize = 200;
+
+pub struct PlayerState {
+    pub player_num: String,
+    pub pseudo: String,
+    pub character: String,
+    pub hp: u8,
+    pub max_hp: u8,
+    pub host: bool,
+    // empty if the game has no teams
+    pub team: String,
+    // atk, hp, ms, rng sent with the last action of the player, items included
+    pub stats: Option<(u8, u8, u8, u8)>,
+}
+
+impl PlayerState {
+    fn new(player: Player) -> Self {
+        let max_hp = player.character.parse::<Character>().map_or(0, |c| c.hp);
+        Self {
+            player_num: player.player_num,
+            pseudo: player.pseudo,
+            character: player.character,
+            hp: max_hp,
+            max_hp,
+            host: player.host,
+            team: player.team,
+            stats: None,
+        }
+    }
+
+    fn set_character(&mut self, character: String) {
+        self.max_hp = character.parse::<Character>().map_or(0, |c| c.hp);
+        self.hp = self.max_hp;
+        self.character = character;
+    }
+}
+
+#[derive(PartialEq, Eq)]
+pub enum Phase {
+    Waiting,
+    Playing,
+    // number of the winner (numbers of the winning team)
+    Over(String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Move,
+    Attack,
+}
+
+// request to send to the server after a key press
+pub enum Action {
+    Start,
+    AddBot,
+    Play(GameDataType),
+    // whole map, after a missed game data packet
+    Snapshot,
+}
+
+pub struct App {
+    pub player_num: String,
+    pub host: bool,
+    pub game_token: String,
+    pub players: Vec<PlayerState>,
+    pub phase: Phase,
+    map: Option<MapState>,
+    // set when a game data packet was missed, the map is out of date
+    pub snapshot_needed: bool,
+    pub turn: String,
+    pub cursor: Point,
+    pub mode: Mode,
+    pub log: Vec<String>,
+    pub connected: bool,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(player_num: String, game_token: String, players: Vec<Player>) -> Self {
+        let mut app = Self {
+            host: player_num == "1",
+            player_num,
+            game_token,
+            players: vec![],
+            phase: Phase::Waiting,
+            map: None,
+            snapshot_needed: false,
+            turn: String::new(),
+            cursor: Point(0, 0),
+            mode: Mode::Move,
+            log: vec![],
+            connected: true,
+            quit: false,
+        };
+        app.set_players(players);
+        app
+    }
+
+    pub fn log(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        if self.log.len() > LOG_SIZE {
+            self.log.remove(0);
+        }
+    }
+
+    pub fn my_turn(&self) -> bool {
+        self.phase == Phase::Playing && self.turn == self.player_num
+    }
+
+    fn me(&self) -> Option<&PlayerState> {
+        self.player(&self.player_num)
+    }
+
+    fn player(&self, player_num: &str) -> Option<&PlayerState> {
+        self.players.iter().find(|p| p.player_num == player_num)
+    }
+
+    // "pseudo (number)" of a player for the log
+    fn name(&self, player_num: &str) -> String {
+        match self.player(player_num) {
+            Some(p) => format!("{} ({player_num})", p.pseudo),
+            None => format!("player {player_num}"),
+        }
+    }
+
+    fn teammate(&self, player_num: &str) -> bool {
+        match (self.me(), self.player(player_num)) {
+            (Some(me), Some(p)) => !me.team.is_empty() && me.team == p.team,
+            _ => false,
+        }
+    }
+
+    fn set_players(&mut self, players: Vec<Player>) {
+        for player in players {
+            match self
+                .players
+                .iter_mut()
+                .find(|p| p.player_num == player.player_num)
+            {
+                Some(p) if p.character != player.character => p.set_character(player.character),
+                Some(_) => (),
+                None => self.players.push(PlayerState::new(player)),
+            }
+        }
+    }
+
+    pub fn set_character(&mut self, character: String) {
+        let player_num = self.player_num.clone();
+        if let Some(p) = self.players.iter_mut().find(|p| p.player_num == player_num) {
+            p.set_character(character);
+        }
+    }
+
+    pub fn joined(&mut self, joined: Joined) {
+        self.log(format!("{} joined the game", joined.pseudo));
+        self.set_players(joined.players);
+    }
+
+    pub fn started(&mut self, start: GameStart) {
+        self.phase = Phase::Playing;
+        self.set_map(&start);
+        self.turn = start.player_turn;
+        if let Some(position) = self.map().and_then(|m| m.find(self.my_tile())) {
+            self.cursor = position;
+        }
+        let first = self.name(&self.turn);
+        self.log(format!("game started, {first} plays first"));
+    }
+
+    pub fn game_data(&mut self, gm_data: GameData) {
+        let name = self.name(&gm_data.player_num);
+        match gm_data.data_type {
+            GM_DATA_MOV => self.log(format!("{name} moved")),
+            GM_DATA_ATK => {
+                let (enemy, hp) = gm_data.enemy.clone();
+                let enemy_name = self.name(&enemy);
+                if let Some(p) = self.players.iter_mut().find(|p| p.player_num == enemy) {
+                    p.hp = hp;
+                }
+                match hp {
+                    0 => self.log(format!("{name} killed {enemy_name}")),
+                    _ => self.log(format!("{name} hit {enemy_name}, {hp} hp left")),
+                }
+            }
+            _ => self.log(format!("{name} skipped his turn")),
+        }
+        if let Some(p) = self
+            .players
+            .iter_mut()
+            .find(|p| p.player_num == gm_data.player_num)
+        {
+            p.stats = Some(gm_data.stats);
+            p.hp = gm_data.stats.1;
+        }
+        if let Some(item) = gm_data.item {
+            self.log(format!("{name} picked up {}", item.name()));
+        }
+        if let Some(map) = &mut self.map {
+            if map.apply(&gm_data) == Update::Gap {
+                self.snapshot_needed = true;
+            }
+        }
+        self.turn = gm_data.player_turn;
+    }
+
+    pub fn snapshot(&mut self, snapshot: GameStart) {
+        self.set_map(&snapshot);
+        self.turn = snapshot.player_turn;
+    }
+
+    pub fn event(&mut self, event: Event) {
+        match event {
+            Event::PlayerJoined(joined) => self.joined(joined),
+            Event::PlayerLeft(left) => {
+                self.log(format!("{} left the game", left.pseudo));
+                self.players
+                    .retain(|p| left.players.iter().any(|l| l.player_num == p.player_num));
+            }
+            Event::CharacterChosen(choice) => {
+                self.log(format!("{} picked {}", choice.pseudo, choice.character));
+                if let Some(p) = self.players.iter_mut().find(|p| p.pseudo == choice.pseudo) {
+                    p.set_character(choice.character);
+                }
+            }
+            Event::GameStarted(start) => self.started(start),
+            Event::GameData(gm_data) => self.game_data(gm_data),
+            Event::GameOver(game_over) => {
+                match &*game_over.winner {
+                    "" => self.log("game ended by the server"),
+                    winner => {
+                        let names: Vec<String> = winner
+                            .chars()
+                            .map(|num| self.name(&num.to_string()))
+                            .collect();
+                        self.log(format!("game over, {} won", names.join(" and ")));
+                    }
+                }
+                self.phase = Phase::Over(game_over.winner);
+            }
+            Event::Kicked => {
+                self.log("kicked from the game");
+                self.phase = Phase::Over(String::new());
+            }
+            Event::Notice(message) => self.log(format!("server notice: {message}")),
+            Event::ServerShutdown => {
+                self.log("server shutting down, join the game again once it is back")
+            }
+            Event::Error(status) => self.log(format!("server error {status}")),
+            Event::Other(_) => (),
+        }
+    }
+
+    pub fn map(&self) -> Option<&GameMap> {
+        self.map.as_ref().map(MapState::map)
+    }
+
+    fn set_map(&mut self, start: &GameStart) {
+        match MapState::new(start) {
+            Ok(map) => self.map = Some(map),
+            Err(e) => self.log(e.to_string()),
+        }
+    }
+
+    fn my_tile(&self) -> char {
+        self.player_num.chars().next().unwrap_or(' ')
+    }
+
+    // map and character of the player while he is alive in a started game
+    fn playing_character(&self) -> Option<(&GameMap, Character)> {
+        let (map, me) = match (self.map(), self.me()) {
+            (Some(map), Some(me)) if self.phase == Phase::Playing && me.hp > 0 => (map, me),
+            _ => return None,
+        };
+        let mut character: Character = me.character.parse().ok()?;
+        // the items picked up change the stats
+        if let Some((atk, _, ms, rng)) = me.stats {
+            (character.atk, character.ms, character.rng) = (atk, ms, rng);
+        }
+        Some((map, character))
+    }
+
+    // tiles to highlight for the current mode
+    pub fn highlighted(&self) -> Vec<Point> {
+        let (map, character) = match self.playing_character() {
+            Some(playing) => playing,
+            None => return vec![],
+        };
+
+        match self.mode {
+            Mode::Move => reachable_tiles(map, self.my_tile(), character.ms),
+            Mode::Attack => attackable_tiles(map, self.my_tile(), character.rng)
+                .into_iter()
+                .filter(|tile| {
+                    let enemy = map.tile(*tile).unwrap_or_default();
+                    !self.teammate(&enemy.to_string())
+                })
+                .collect(),
+        }
+    }
+
+    // why the server would refuse the action, checked before sending it. Hidden tiles
+    // of fog of war games may hide a path, the server decides for those
+    fn illegal(&self, gm_type: &GameDataType) -> Option<&'static str> {
+        let (map, character) = self.playing_character()?;
+        match gm_type {
+            GameDataType::Movement(dest) if map.find(HIDDEN_TILE).is_none() => {
+                (!can_move(map, self.my_tile(), *dest, character.ms)).then_some("out of reach")
+            }
+            GameDataType::Attack(target) => {
+                match attack_target(map, self.my_tile(), *target, character.rng) {
+                    None => Some("no enemy in range there"),
+                    // no friendly fire
+                    Some(enemy) if self.teammate(&enemy.to_string()) => Some("teammate"),
+                    Some(_) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn move_cursor(&mut self, dx: i16, dy: i16) {
+        if let Some(map) = self.map() {
+            let next = Point(self.cursor.0 + dx, self.cursor.1 + dy);
+            if map.contains(next) {
+                self.cursor = next;
+            }
+        }
+    }
+
+    pub fn key(&mut self, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(-1, 0),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(0, 1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(0, -1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(1, 0),
+            KeyCode::Char('m') => self.mode = Mode::Move,
+            KeyCode::Char('a') => self.mode = Mode::Attack,
+            KeyCode::Char('s') if self.host && self.phase == Phase::Waiting => {
+                return Some(Action::Start)
+            }
+            KeyCode::Char('b') if self.host && self.phase == Phase::Waiting => {
+                return Some(Action::AddBot)
+            }
+            KeyCode::Enter | KeyCode::Char('p') if self.phase == Phase::Playing => {
+                if !self.my_turn() {
+                    self.log("not your turn");
+                    return None;
+                }
+                let gm_type = match (key.code, self.mode) {
+                    (KeyCode::Char('p'), _) => GameDataType::Skip,
+                    (_, Mode::Move) => GameDataType::Movement(self.cursor),
+                    (_, Mode::Attack) => GameDataType::Attack(self.cursor),
+                };
+                if let Some(reason) = self.illegal(&gm_type) {
+                    self.log(reason);
+                    return None;
+                }
+                return Some(Action::Play(gm_type));
+            }
+            _ => (),
+        }
+        None
+    }
+}
@@ -0,0 +1,121 @@
+mod app;
+mod ui;
+
+use anyhow::bail;
+use app::{Action, App};
+use crossterm::event::{self, Event as TermEvent, KeyEventKind};
+use game_client_lib::{GameClient, Player};
+use net_utils::encoding::Encoding;
+use std::env;
+use std::path::Path;
+use tokio::sync::mpsc;
+
+// difficulty of the bots added by the host
+const BOT_DIFFICULTY: &str = "normal";
+
+async fn run_action(client: &mut GameClient, app: &mut App, action: Action) {
+    let result = match action {
+        Action::Start => client.start_game().await.map(|start| app.started(start)),
+        Action::AddBot => client.add_bot(BOT_DIFFICULTY).await.map(|j| app.joined(j)),
+        Action::Play(gm_type) => client
+            .send_game_data(gm_type)
+            .await
+            .map(|gm_data| app.game_data(gm_data)),
+        Action::Snapshot => client.snapshot().await.map(|s| app.snapshot(s)),
+    };
+    if let Err(e) = result {
+        app.log(e.to_string());
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // usage: game_tui pseudo character [game token], without game token a game is created
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (pseudo, character, game_token) = match &args[..] {
+        [pseudo, character] => (pseudo, character, None),
+        [pseudo, character, game_token] => (pseudo, character, Some(game_token)),
+        _ => bail!("usage: game_tui <pseudo> <mag|bar|bow> [game token]"),
+    };
+    let server_addr = env::var("GAME_SERVER_ADDR").unwrap_or_else(|_| "127.0.0.1:8000".into());
+    // the server certificate is verified against TLS_CA_PATH when it is set
+    let (mut client, mut events) = match env::var("TLS_CA_PATH") {
+        Ok(ca_path) => {
+            let server_name = env::var("TLS_SERVER_NAME").unwrap_or_else(|_| "localhost".into());
+            GameClient::connect_tls(&server_addr, &server_name, Path::new(&ca_path)).await?
+        }
+        Err(_) => GameClient::connect(&server_addr).await?,
+    };
+    client
+        .hello(
+            concat!("game_tui/", env!("CARGO_PKG_VERSION")),
+            Encoding::Json,
+        )
+        .await?;
+
+    client.create_player(pseudo).await?;
+    let players = match game_token {
+        Some(game_token) => client.join_game(game_token).await?.players,
+        None => {
+            client.create_game().await?;
+            vec![Player {
+                player_num: "1".into(),
+                pseudo: pseudo.clone(),
+                character: String::new(),
+                host: true,
+                // the game is created without teams
+                team: String::new(),
+            }]
+        }
+    };
+    client.choose_character(character).await?;
+    let mut app = App::new(
+        client.player_num().into(),
+        client.game_token().into(),
+        players,
+    );
+    app.set_character(character.clone());
+
+    // terminal events are read on their own thread, crossterm reads block
+    let (keys_sender, mut keys) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if keys_sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    while !app.quit {
+        terminal.draw(|frame| ui::draw(frame, &app))?;
+        tokio::select! {
+            Some(event) = keys.recv() => {
+                if let TermEvent::Key(key) = event {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    if let Some(action) = app.key(key) {
+                        run_action(&mut client, &mut app, action).await;
+                    }
+                }
+            }
+            event = events.next(), if app.connected => match event {
+                Some(event) => app.event(event),
+                None => {
+                    app.connected = false;
+                    app.log("connection to server lost");
+                }
+            },
+        }
+        if std::mem::take(&mut app.snapshot_needed) {
+            run_action(&mut client, &mut app, Action::Snapshot).await;
+        }
+    }
+    ratatui::restore();
+
+    if app.connected {
+        client.terminate().await?;
+    }
+    Ok(())
+}
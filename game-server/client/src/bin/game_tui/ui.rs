@@ -0,0 +1,194 @@
+use crate::app::{App, Mode, Phase};
+use net_utils::map::{Point, EMPTY_TILE, HIDDEN_TILE};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Wrap};
+use ratatui::Frame;
+
+// width of the hp bars, in cells
+const HP_BAR_WIDTH: u16 = 10;
+
+fn player_color(player_num: &str) -> Color {
+    match player_num {
+        "1" => Color::LightRed,
+        "2" => Color::LightBlue,
+        "3" => Color::LightYellow,
+        _ => Color::LightMagenta,
+    }
+}
+
+// symbol and style of a tile, each tile takes 3 cells
+fn tile_span(tile: char, player_num: &str) -> Span<'static> {
+    let (symbol, style) = match tile {
+        EMPTY_TILE => (" · ".into(), Style::new().fg(Color::DarkGray)),
+        HIDDEN_TILE => (" ░ ".into(), Style::new().fg(Color::DarkGray)),
+        'R' => (" ▲ ".into(), Style::new().fg(Color::Gray)),
+        'W' => (" ≈ ".into(), Style::new().fg(Color::Blue)),
+        'T' => (" ♣ ".into(), Style::new().fg(Color::Green)),
+        // health potion, attack buff, movement boots
+        'H' => (" + ".into(), Style::new().fg(Color::LightRed).bold()),
+        'A' => (" ! ".into(), Style::new().fg(Color::LightYellow).bold()),
+        'B' => (" » ".into(), Style::new().fg(Color::LightCyan).bold()),
+        num if num.is_ascii_digit() => {
+            let mut style = Style::new().fg(player_color(&num.to_string())).bold();
+            if num.to_string() == player_num {
+                style = style.add_modifier(Modifier::UNDERLINED);
+            }
+            (format!(" {num} "), style)
+        }
+        other => (format!(" {other} "), Style::new()),
+    };
+    Span::styled(symbol, style)
+}
+
+fn draw_map(frame: &mut Frame, app: &App, area: Rect) {
+    let title = match &app.phase {
+        Phase::Waiting => " waiting for players ".to_string(),
+        Phase::Playing if app.my_turn() => match app.mode {
+            Mode::Move => " your turn: move ".into(),
+            Mode::Attack => " your turn: attack ".into(),
+        },
+        Phase::Playing => format!(" player {} is playing ", app.turn),
+        Phase::Over(winner) if winner.is_empty() => " game over ".into(),
+        Phase::Over(winner) if winner.len() > 1 => format!(" game over, players {winner} won "),
+        Phase::Over(winner) => format!(" game over, player {winner} won "),
+    };
+    let block = Block::bordered().title(title);
+    let map = match app.map() {
+        Some(map) => map,
+        None => {
+            let text = format!("game token:\n{}", app.game_token);
+            let waiting = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+            frame.render_widget(waiting, area);
+            return;
+        }
+    };
+
+    let highlighted = app.highlighted();
+    let highlight = match app.mode {
+        Mode::Move => Color::Rgb(20, 60, 20),
+        Mode::Attack => Color::Rgb(90, 20, 20),
+    };
+    let lines: Vec<Line> = (0..map.height() as i16)
+        .map(|y| {
+            let spans: Vec<Span> = (0..map.width() as i16)
+                .map(|x| {
+                    let point = Point(x, y);
+                    let mut span = tile_span(map.tile(point).unwrap(), &app.player_num);
+                    if highlighted.contains(&point) {
+                        span = span.bg(highlight);
+                    }
+                    if point == app.cursor && app.phase == Phase::Playing {
+                        span = span.bg(Color::White).fg(Color::Black);
+                    }
+                    span
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn hp_bar(hp: u8, max_hp: u8) -> String {
+    let full = match max_hp {
+        0 => 0,
+        _ => (hp as u16 * HP_BAR_WIDTH).div_ceil(max_hp as u16),
+    };
+    format!(
+        "{}{}",
+        "█".repeat(full as usize),
+        "░".repeat((HP_BAR_WIDTH - full) as usize)
+    )
+}
+
+fn draw_players(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .players
+        .iter()
+        .map(|p| {
+            let turn = match app.phase == Phase::Playing && app.turn == p.player_num {
+                true => "▶ ",
+                false => "  ",
+            };
+            let host = if p.host { " (host)" } else { "" };
+            let team = match p.team.as_str() {
+                "" => String::new(),
+                team => format!(" team {team}"),
+            };
+            let character = match p.character.as_str() {
+                "" => "choosing...",
+                c => c,
+            };
+            let color = player_color(&p.player_num);
+            let hp_color = match p.hp {
+                0 => Color::DarkGray,
+                hp if (hp as u16) * 3 < p.max_hp as u16 => Color::Red,
+                _ => Color::Green,
+            };
+            ListItem::new(vec![
+                Line::from(vec![
+                    Span::raw(turn),
+                    Span::styled(format!("{} {}", p.player_num, p.pseudo), color).bold(),
+                    Span::raw(format!("{host}{team} {character}")),
+                ]),
+                Line::from(vec![
+                    Span::raw("  "),
+                    Span::styled(hp_bar(p.hp, p.max_hp), hp_color),
+                    Span::raw(format!(" {}/{}", p.hp, p.max_hp)),
+                ]),
+            ])
+        })
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" players ")),
+        area,
+    );
+}
+
+fn draw_log(frame: &mut Frame, app: &App, area: Rect) {
+    // latest lines at the bottom
+    let height = area.height.saturating_sub(2) as usize;
+    let start = app.log.len().saturating_sub(height);
+    let items: Vec<ListItem> = app.log[start..]
+        .iter()
+        .map(|line| ListItem::new(line.as_str()))
+        .collect();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title(" events ")),
+        area,
+    );
+}
+
+fn help(app: &App) -> &'static str {
+    match app.phase {
+        Phase::Waiting if app.host => "s start  b add a bot  q quit",
+        Phase::Waiting => "waiting for the host to start  q quit",
+        Phase::Playing => {
+            "arrows/hjkl cursor  m move mode  a attack mode  enter play  p skip turn  q quit"
+        }
+        Phase::Over(_) => "q quit",
+    }
+}
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, log, help_area] = Layout::vertical([
+        Constraint::Min(8),
+        Constraint::Length(8),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let map_width = app.map().map_or(40, |m| m.width() as u16 * 3 + 2);
+    let [map, players] =
+        Layout::horizontal([Constraint::Length(map_width.max(40)), Constraint::Min(30)])
+            .areas(main);
+
+    draw_map(frame, app, map);
+    draw_players(frame, app, players);
+    draw_log(frame, app, log);
+    let help = Paragraph::new(help(app))
+        .style(Style::new().fg(Color::DarkGray))
+        .wrap(Wrap { trim: true });
+    frame.render_widget(help, help_area);
+}
@@ -1,433 +1,297 @@
-mod request;
-mod test;
-
-use crate::test::test_clients;
-use net_utils::character::*;
-use net_utils::map::{GameDataType, Point};
+use game_client_lib::{Event, Events, GameClient, GameData, GameStart, MapState, Update};
+use net_utils::encoding::Encoding;
+use net_utils::map::{GameDataType, GameMap, Point};
 use net_utils::packet::game_data_code::*;
-use net_utils::packet::{request_codes::*, status_codes::*};
-use request::*;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::io::{stdin, Read, Write};
-use std::net::TcpStream;
-use std::sync::Arc;
-use std::thread;
-use std::thread::sleep;
-use std::time::Duration;
-
-fn read_packet(stream: &mut TcpStream) -> Vec<u8> {
-    let mut packet_size = [0; 2];
-    stream.read_exact(&mut packet_size).unwrap();
-    let size = u16::from_be_bytes(packet_size);
-
-    let mut packet_vec = (0..size).map(|_| 0).collect::<Vec<_>>();
-    stream.read_exact(&mut packet_vec).unwrap();
-
-    packet_vec
-}
-
-fn write_packet_from_json(stream: &mut TcpStream, json: String) {
-    let bytes = json.as_bytes();
-    let packet_size = &bytes.len().to_be_bytes()[6..];
-    stream.write(packet_size).unwrap();
-    stream.write(bytes).unwrap();
-}
-
-fn create_player(stream: &mut TcpStream, username: &str) -> String {
-    let mut player_token = String::new();
-    loop {
-        let json = PlayerCreation::json_string(username).unwrap();
-        write_packet_from_json(stream, json);
-        let response_packet = read_packet(stream);
-
-        let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-        match json_response["status"].as_u64() {
-            Some(OK_PL_CREAT) => {
-                player_token = match json_response["player_token"].as_str() {
-                    Some(token) => {
-                        // uuid v4 length
-                        if token.len() != 36 {
-                            continue;
-                        }
-                        token.to_owned()
-                    }
-                    None => continue,
-                };
-            }
-            Some(_) | None => continue,
+use std::env;
+use std::path::Path;
+use tokio::io::{stdin, AsyncBufReadExt, BufReader, Lines, Stdin};
+
+struct Input(Lines<BufReader<Stdin>>);
+impl Input {
+    async fn line(&mut self, prompt: &str) -> anyhow::Result<String> {
+        println!("{prompt}");
+        match self.0.next_line().await? {
+            Some(line) => Ok(line.trim().to_owned()),
+            None => anyhow::bail!("stdin closed"),
         }
-        break;
     }
 
-    player_token
-}
-
-fn create_game(stream: &mut TcpStream, player_token: &str) -> String {
-    let mut game_token = String::new();
-    let json = GameCreation::json_string(player_token).unwrap();
-    write_packet_from_json(stream, json);
-    let response_packet = read_packet(stream);
-
-    let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-    match json_response["status"].as_u64() {
-        Some(OK_GM_CREAT) => {
-            game_token = match json_response["game_token"].as_str() {
-                Some(token) => {
-                    // uuid v4 length
-                    if token.len() != 36 {
-                        panic!();
-                    }
-                    token.to_owned()
-                }
-                None => panic!(),
-            };
-        }
-        _ => panic!(),
-    }
-
-    game_token
-}
-
-fn join_game(stream: &mut TcpStream, p_infos: &PlayerInfos) -> Option<String> {
-    let json = GameJoining::json_string(&p_infos.player_token, &p_infos.game_token).unwrap();
-    write_packet_from_json(stream, json);
-
-    let response_packet = read_packet(stream);
-    let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-    if json_response["status"].as_u64() == Some(OK_GM_JOIN) {
-        if let Some(p_vec) = json_response["player_vec"].as_array() {
-            for player in p_vec {
-                if let Some(username) = player[1].as_str() {
-                    if username == p_infos.username {
-                        if let Some(p_num) = player[0].as_str() {
-                            return Some(p_num.into());
-                        }
-                        break;
-                    }
-                } else {
-                    break;
-                }
+    async fn point(&mut self, prompt: &str) -> anyhow::Result<Point> {
+        loop {
+            let line = self.line(prompt).await?;
+            let mut s = line.split(',').map(|c| c.trim().parse::<i16>());
+            if let (Some(Ok(x)), Some(Ok(y)), None) = (s.next(), s.next(), s.next()) {
+                return Ok(Point(x, y));
             }
         }
     }
 
-    None
-}
-
-fn choose_character(stream: &mut TcpStream, p_infos: &PlayerInfos, character_str: &str) -> bool {
-    let json =
-        ChooseCharacter::json_string(&p_infos.player_token, &p_infos.game_token, character_str)
-            .unwrap();
-    write_packet_from_json(stream, json);
-
-    let response_packet = read_packet(stream);
-    let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-    if json_response["status"].as_u64() == Some(OK_CHAR_CHOOSING) {
-        return true;
+    // None when the player quits
+    async fn action(&mut self) -> anyhow::Result<Option<GameDataType>> {
+        loop {
+            let action = self
+                .line("what do you want to do? [mov], [atk], [skip] or [quit]:")
+                .await?;
+            return Ok(Some(match &*action.to_lowercase() {
+                "mov" => GameDataType::Movement(self.point("enter coordinate [x,y]:").await?),
+                "atk" => GameDataType::Attack(self.point("enter target coordinate [x,y]:").await?),
+                "skip" => GameDataType::Skip,
+                "quit" => return Ok(None),
+                _ => continue,
+            }));
+        }
     }
 
-    false
-}
-
-fn start_game(stream: &mut TcpStream, host_infos: &PlayerInfos) -> Option<(String, String)> {
-    let json = GameStarting::json_string(&host_infos.player_token, &host_infos.game_token).unwrap();
-    write_packet_from_json(stream, json);
-
-    let response_packet = read_packet(stream);
-    let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-    //println!("start game packet from host {:?}", json_response);
-    if json_response["status"].as_u64() == Some(OK_GM_START) {
-        if let Some(turn) = json_response["player_turn"].as_str() {
-            if let Some(map) = json_response["map"].as_str() {
-                return Some((turn.into(), map.into()));
+    async fn character(&mut self) -> anyhow::Result<String> {
+        loop {
+            let character = self
+                .line("pick your character [mag], [bar] or [bow]:")
+                .await?
+                .to_lowercase();
+            if let "mag" | "bar" | "bow" = &*character {
+                return Ok(character);
             }
-        };
+        }
     }
-
-    None
-}
-
-#[derive(Serialize, Deserialize)]
-struct GameDataResponse {
-    status: u64,
-    data_type: u64,
-    player_num: String,
-    player_turn: String,
-    map: String,
-    // (enemy_number, enemy_remaining_hp)
-    enemy: (String, u8),
 }
 
-#[derive(Debug)]
-struct GameDataFields {
-    data_type: u64,
-    player_num: String,
-    player_turn: String,
-    map: String,
-    enemy: (String, u8),
-}
-
-fn send_game_data(
-    stream: &mut TcpStream,
-    p_infos: &PlayerInfos,
-    game_data: GameDataType,
-) -> Option<GameDataFields> {
-    let json = match game_data {
-        GameDataType::Attack(pos) => {
-            GameData::json_string(GM_DATA_ATK, &p_infos.player_token, &p_infos.game_token, pos)
-        }
-        GameDataType::Movement(pos) => {
-            GameData::json_string(GM_DATA_MOV, &p_infos.player_token, &p_infos.game_token, pos)
-        }
-        GameDataType::Skip => GameData::json_string(
-            GM_DATA_SKIP,
-            &p_infos.player_token,
-            &p_infos.game_token,
-            Point(-1, -1),
+fn print_game_data(gm_data: &GameData, map: &GameMap) {
+    print!("player {} ", gm_data.player_num);
+    match gm_data.data_type {
+        GM_DATA_MOV => println!("moved\nmap:\n{map}"),
+        GM_DATA_ATK => println!(
+            "attacked\nmap:\n{map}\nplayer {} remaining hp: {}",
+            gm_data.enemy.0, gm_data.enemy.1
         ),
+        _ => println!("skipped his turn"),
     }
-    .unwrap();
-    write_packet_from_json(stream, json);
-
-    let response_packet = read_packet(stream);
-    let gm_data: GameDataResponse = serde_json::from_slice(&*response_packet).unwrap();
-    if gm_data.status == OK_GM_DATA {
-        return Some(GameDataFields {
-            data_type: gm_data.data_type,
-            player_num: gm_data.player_num,
-            player_turn: gm_data.player_turn,
-            map: gm_data.map,
-            enemy: gm_data.enemy,
-        });
+    if let Some(item) = gm_data.item {
+        let (atk, hp, ms, _) = gm_data.stats;
+        println!("picked up {}: atk {atk}, hp {hp}, ms {ms}", item.name());
     }
-
-    None
 }
 
-fn terminate_connection(stream: &mut TcpStream) -> bool {
-    let json = json!({ "request_type": TERM_CON }).to_string();
-    write_packet_from_json(stream, json);
-
-    let response_packet = read_packet(stream);
-    let json_response: Value = serde_json::from_slice(&*response_packet).unwrap();
-    println!(
-        "term con packet: {:?} | stream: {:?}",
-        json_response, stream
-    );
-    if json_response["status"].as_u64() == Some(OK_TERM_CON) {
-        return true;
+// the packets only carry the changed tiles, the whole map is asked for after a missed packet
+async fn update_map(
+    client: &mut GameClient,
+    map: &mut MapState,
+    gm_data: &GameData,
+) -> anyhow::Result<()> {
+    if map.apply(gm_data) == Update::Gap {
+        *map = MapState::new(&client.snapshot().await?)?;
     }
-
-    false
+    Ok(())
 }
 
-struct PlayerInfos {
-    game_token: String,
-    player_token: String,
-    username: String,
-}
-impl PlayerInfos {
-    fn new(game_token: String, player_token: String, username: String) -> Self {
-        Self {
-            game_token,
-            player_token,
-            username,
-        }
+// wait for the next event of the game, None when the connection is closed
+async fn next_event(events: &mut Events) -> Option<Event> {
+    let event = events.next().await;
+    if event.is_none() {
+        println!("connection to server lost");
     }
+    event
 }
 
-fn handle_cli_game_action(stream: &mut TcpStream, p_infos: PlayerInfos) {
-    let mut input = String::new();
+// play until the game is over
+async fn play(
+    client: &mut GameClient,
+    events: &mut Events,
+    input: &mut Input,
+    start: GameStart,
+) -> anyhow::Result<()> {
+    let mut map = MapState::new(&start)?;
+    let mut turn = start.player_turn;
     loop {
-        println!("what do you want to do? [mov], [atk], [skip] or [quit]:");
-        input.clear();
-        stdin().read_line(&mut input).unwrap();
-        let gm_type = match &*input.trim().to_lowercase() {
-            "mov" => {
-                println!("enter coordinate [x,y]:");
-                input.clear();
-                stdin().read_line(&mut input).unwrap();
-                let mut s = input.trim().split(",");
-                let x = s.next().unwrap().parse::<i16>().unwrap();
-                let y = s.next().unwrap().parse::<i16>().unwrap();
-                let point = Point(x, y);
-                GameDataType::Movement(point)
-            }
-            "atk" => {
-                println!("enter target coordinate [x,y]:");
-                input.clear();
-                stdin().read_line(&mut input).unwrap();
-                let mut s = input.trim().split(",");
-                let x = s.next().unwrap().parse::<i16>().unwrap();
-                let y = s.next().unwrap().parse::<i16>().unwrap();
-                let point = Point(x, y);
-                GameDataType::Attack(point)
+        // the game can end while the player is choosing his action
+        let event = if turn == client.player_num() {
+            tokio::select! {
+                action = input.action() => {
+                    let gm_type = match action? {
+                        Some(gm_type) => gm_type,
+                        None => return Ok(()),
+                    };
+                    match client.send_game_data(gm_type.clone()).await {
+                        Ok(gm_data) => {
+                            update_map(client, &mut map, &gm_data).await?;
+                            let map = map.map();
+                            match gm_type {
+                                GameDataType::Movement(_) => println!("{map}"),
+                                GameDataType::Attack(_) => println!(
+                                    "map:\n{map}\nenemy number {} has {} hp remaining",
+                                    gm_data.enemy.0, gm_data.enemy.1
+                                ),
+                                GameDataType::Skip => println!("{map}\nturn skipped"),
+                            }
+                            turn = gm_data.player_turn;
+                        }
+                        // invalid action, the player tries again
+                        Err(e) if e.status().is_some() => println!("{e}"),
+                        Err(e) => return Err(e.into()),
+                    }
+                    continue;
+                }
+                event = next_event(events) => event,
             }
-            "skip" => GameDataType::Skip,
-            "quit" => return,
-            _ => continue,
+        } else {
+            next_event(events).await
         };
 
-        let gm_data = send_game_data(stream, &p_infos, gm_type.clone()).unwrap();
-        match gm_type {
-            GameDataType::Movement(_) => println!("{}", gm_data.map),
-            GameDataType::Attack(_) => println!(
-                "map:\n{}\nenemy number {} has {} hp remaining",
-                gm_data.map, gm_data.enemy.0, gm_data.enemy.1
-            ),
-            GameDataType::Skip => println!("{}\nturn skipped", gm_data.map),
+        match event {
+            Some(Event::GameData(gm_data)) => {
+                update_map(client, &mut map, &gm_data).await?;
+                print_game_data(&gm_data, map.map());
+                turn = gm_data.player_turn;
+            }
+            Some(Event::GameOver(game_over)) if game_over.winner.is_empty() => {
+                println!("game ended by the server");
+                return Ok(());
+            }
+            Some(Event::GameOver(game_over)) if game_over.winner.len() > 1 => {
+                println!("game over, team of players {} won", game_over.winner);
+                return Ok(());
+            }
+            Some(Event::GameOver(game_over)) => {
+                println!("game over, player {} won", game_over.winner);
+                return Ok(());
+            }
+            Some(Event::Kicked) => {
+                println!("kicked from the game");
+                return Ok(());
+            }
+            Some(Event::Notice(message)) => println!("server notice: {message}"),
+            Some(Event::ServerShutdown) => {
+                println!("server shutting down, join the game again once it is back");
+                return Ok(());
+            }
+            Some(_) => continue,
+            None => return Ok(()),
         }
-
-        handle_cli_game_action_reading(stream);
     }
 }
 
-fn handle_cli_game_action_reading(stream: &mut TcpStream) {
-    let packet = read_packet(stream);
-    let gm_data: GameDataResponse = serde_json::from_slice(&*packet).unwrap();
-    print!("player {} ", gm_data.player_num);
-    match gm_data.data_type {
-        GM_DATA_MOV => println!("moved\nmap:\n{}", gm_data.map),
-        GM_DATA_ATK => println!("attacked\nmap:\n{}\nplayer {} remaining hp: {}", gm_data.map, gm_data.enemy.0, gm_data.enemy.1),
-        GM_DATA_SKIP => println!("skipped his turn"),
-        _ => panic!(),
-    }
-}
-
-fn main() -> anyhow::Result<()> {
-    //test_clients();
-    let mut stream = TcpStream::connect("127.0.0.1:8000").unwrap();
-    loop {
-        println!("choose [host] or [player]:");
-        let mut input = String::new();
-        stdin().read_line(&mut input).unwrap();
-        match &*input.trim().to_lowercase() {
-            "host" => {
-                println!("host player chosen\nchoose username:");
-                input.clear();
-                stdin().read_line(&mut input).unwrap();
-                let username = input.trim();
-                let host_player_token = create_player(&mut stream, username);
-                println!("player {username} created\nplayer token: {host_player_token}");
-
-                let game_token = create_game(&mut stream, &host_player_token);
-                println!("game created\ngame token: {game_token}");
-                let p_infos = PlayerInfos::new(game_token, host_player_token, username.into());
-
-                let mut character;
-                loop {
-                    println!("pick your character [mag], [bar] or [bow]:");
-                    input.clear();
-                    stdin().read_line(&mut input).unwrap();
-                    character = input.trim().to_lowercase();
-                    match &*character {
-                        "mag" | "bar" | "bow" => break,
-                        _ => continue,
-                    }
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // usage: game_client [server address]
+    let server_addr = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "127.0.0.1:8000".into());
+    // the server certificate is verified against TLS_CA_PATH when it is set
+    let (mut client, mut events) = match env::var("TLS_CA_PATH") {
+        Ok(ca_path) => {
+            let server_name = env::var("TLS_SERVER_NAME").unwrap_or_else(|_| "localhost".into());
+            GameClient::connect_tls(&server_addr, &server_name, Path::new(&ca_path)).await?
+        }
+        Err(_) => GameClient::connect(&server_addr).await?,
+    };
+    client
+        .hello(
+            concat!("game_client/", env!("CARGO_PKG_VERSION")),
+            Encoding::Json,
+        )
+        .await?;
+    let mut input = Input(BufReader::new(stdin()).lines());
+
+    let host = loop {
+        match &*input
+            .line("choose [host] or [player]:")
+            .await?
+            .to_lowercase()
+        {
+            "host" => break true,
+            "player" => break false,
+            _ => continue,
+        }
+    };
+
+    let username = loop {
+        let username = input.line("choose username:").await?;
+        match client.create_player(&username).await {
+            Ok(player_token) => {
+                println!("player {username} created\nplayer token: {player_token}");
+                break username;
+            }
+            Err(e) => println!("{e}"),
+        }
+    };
+
+    let start = if host {
+        let game_token = client.create_game().await?;
+        println!("game created\ngame token: {game_token}");
+        let character = input.character().await?;
+        client.choose_character(&character).await?;
+        println!("character {character} successfully picked\nwaiting for players to join...");
+
+        // the game can be started once every player picked a character
+        loop {
+            match next_event(&mut events).await {
+                Some(Event::PlayerJoined(joined)) => {
+                    println!("{} joined, waiting for a character pick...", joined.pseudo)
                 }
-
-                if choose_character(&mut stream, &p_infos, &character) {
-                    println!(
-                        "character {character} successfully picked\nwaiting for players to join..."
-                    );
-                } else {
-                    panic!()
+                Some(Event::PlayerLeft(left)) => println!("{} left the game", left.pseudo),
+                Some(Event::Kicked) => {
+                    println!("kicked from the game");
+                    return Ok(());
                 }
-
-                let packet: Value =
-                    serde_json::from_str(std::str::from_utf8(&read_packet(&mut stream)).unwrap())
-                        .unwrap();
-
-                let p_username = packet["pseudo"].as_str().unwrap();
-                println!("player 2 ({p_username}) joined\nwaiting for player 2 to pick a character...");
-
-                //todo: character choosing
-                let packet = read_packet(&mut stream);
-                println!("player 2 picked his character");
-
-                loop {
-                    println!("start game? [y/n]:");
-                    input.clear();
-                    stdin().read_line(&mut input).unwrap();
-                    match &*input.trim().to_lowercase() {
-                        "y" | "yes" => break,
-                        _ => continue,
+                Some(Event::CharacterChosen(choice)) => {
+                    println!("{} picked {}", choice.pseudo, choice.character);
+                    let answer = input.line("start game? [y/n]:").await?.to_lowercase();
+                    if answer != "y" && answer != "yes" {
+                        continue;
+                    }
+                    match client.start_game().await {
+                        Ok(start) => {
+                            println!("game started\nmap:\n{}", start.map);
+                            break start;
+                        }
+                        Err(e) => println!("{e}"),
                     }
                 }
-
-                let (mut turn, map) = start_game(&mut stream, &p_infos).unwrap();
-                println!("game started");
-                if turn == "1" {
-                    println!("you play first\nmap:\n{map}");
-                } else {
-                    println!("map:\n{map}\nplayer {turn} ({p_username}) is the first to play");
-                    handle_cli_game_action_reading(&mut stream);
-                }
-
-                handle_cli_game_action(&mut stream, p_infos);
-                terminate_connection(&mut stream);
-                println!("connection to server closed");
-                break;
+                Some(_) => continue,
+                None => return Ok(()),
             }
-            "player" => {
-                println!("normal player chosen\nchoose username:");
-                input.clear();
-                stdin().read_line(&mut input).unwrap();
-                let username = input.trim().to_owned();
-                let player_token = create_player(&mut stream, &username);
-                println!("player {username} created\nplayer token: {player_token}\nenter game token to join it:");
-                input.clear();
-                stdin().read_line(&mut input).unwrap();
-                let game_token = input.trim();
-                let p_infos = PlayerInfos::new(game_token.into(), player_token, username);
-
-                //todo: players_vec return from join_game
-                let player_number = join_game(&mut stream, &p_infos).unwrap();
-                println!("game joined\nplayer number: {player_number}");
-                let mut character;
-                loop {
-                    println!("pick your character [mag], [bar] or [bow]:");
-                    input.clear();
-                    stdin().read_line(&mut input).unwrap();
-                    character = input.trim().to_lowercase();
-                    match &*character {
-                        "mag" | "bar" | "bow" => break,
-                        _ => continue,
-                    }
+        }
+    } else {
+        loop {
+            let game_token = input.line("enter game token to join it:").await?;
+            match client.join_game(&game_token).await {
+                Ok(_) => break,
+                Err(e) => println!("{e}"),
+            }
+        }
+        println!("game joined\nplayer number: {}", client.player_num());
+        let character = input.character().await?;
+        client.choose_character(&character).await?;
+        println!("character {character} successfully picked\nwaiting for game to start...");
+
+        loop {
+            match next_event(&mut events).await {
+                Some(Event::GameStarted(start)) => {
+                    println!("game started\nmap:\n{}", start.map);
+                    break start;
                 }
-
-                if choose_character(&mut stream, &p_infos, &character) {
-                    println!(
-                        "character {character} successfully picked\nwaiting for game to start..."
-                    );
-                } else {
-                    panic!()
+                Some(Event::Kicked) => {
+                    println!("kicked from the game");
+                    return Ok(());
                 }
-
-                let packet = read_packet(&mut stream);
-                let json: Value = serde_json::from_slice(&*packet).unwrap();
-                let turn = json["player_turn"].as_str().unwrap();
-                let map = json["map"].as_str().unwrap();
-                println!("game started");
-
-                if turn == player_number {
-                    println!("you play first\nmap:\n{map}");
-                } else {
-                    println!("map:\n{map}\nplayer {turn} is the first to play");
-                    handle_cli_game_action_reading(&mut stream);
+                Some(Event::GameOver(_)) => {
+                    println!("game ended by the server");
+                    return Ok(());
                 }
-
-                handle_cli_game_action(&mut stream, p_infos);
-                terminate_connection(&mut stream);
-                println!("connection to server closed");
-                break;
+                Some(_) => continue,
+                None => return Ok(()),
             }
-            _ => continue,
         }
+    };
+
+    if start.player_turn == client.player_num() {
+        println!("you play first");
+    } else {
+        println!("player {} is the first to play", start.player_turn);
     }
+    play(&mut client, &mut events, &mut input, start).await?;
+
+    client.terminate().await?;
+    println!("connection to server closed ({username})");
 
     Ok(())
 }
-
-//todo: remove loops to avoid spamming server
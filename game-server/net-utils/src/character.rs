@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 pub enum CharacterClass {
     Bowman,
     Barbarian,
@@ -47,13 +49,14 @@ impl Character {
             class,
         }
     }
+}
 
-    pub fn from_str(character_str: &str) -> Option<Character> {
-        Some(match character_str {
-            "bar" => Character::new(CharacterClass::Barbarian),
-            "bow" => Character::new(CharacterClass::Bowman),
-            "mag" => Character::new(CharacterClass::Magician),
-            _ => return None,
-        })
+impl FromStr for Character {
+    type Err = ();
+
+    fn from_str(character_str: &str) -> Result<Self, Self::Err> {
+        CharacterClass::new(character_str)
+            .map(Character::new)
+            .ok_or(())
     }
 }
@@ -0,0 +1,87 @@
+// wire encodings of the packets, chosen by the client with HELLO
+// json: u16 big endian length + json text (the default, every packet before the negotiation)
+// msgpack: u32 big endian length + messagepack, for packets too large for a u16 length
+use serde_json::Value;
+
+// version of the protocol, bumped on incompatible changes
+pub const PROTOCOL_VERSION: u64 = 1;
+// versions the server still speaks
+pub const SUPPORTED_VERSIONS: [u64; 1] = [1];
+// every encoding, json first
+pub const ENCODINGS: [Encoding; 2] = [Encoding::Json, Encoding::MessagePack];
+// largest msgpack packet accepted, a bigger length closes the connection
+pub const MAX_BINARY_PACKET_SIZE: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::MessagePack),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+        }
+    }
+
+    // bytes of the length written before each packet
+    pub fn header_size(self) -> usize {
+        match self {
+            Self::Json => 2,
+            Self::MessagePack => 4,
+        }
+    }
+
+    pub fn max_packet_size(self) -> usize {
+        match self {
+            Self::Json => u16::MAX as usize,
+            Self::MessagePack => MAX_BINARY_PACKET_SIZE,
+        }
+    }
+
+    // packet length read from the header_size bytes of the header
+    pub fn packet_size(self, header: &[u8]) -> usize {
+        header
+            .iter()
+            .take(self.header_size())
+            .fold(0, |size, byte| size << 8 | *byte as usize)
+    }
+
+    // length header followed by the payload, None if the payload is too large
+    pub fn frame(self, payload: &[u8]) -> Option<Vec<u8>> {
+        if payload.len() > self.max_packet_size() {
+            return None;
+        }
+        let header_size = self.header_size();
+        let mut packet = Vec::with_capacity(header_size + payload.len());
+        packet.extend_from_slice(&(payload.len() as u64).to_be_bytes()[8 - header_size..]);
+        packet.extend_from_slice(payload);
+        Some(packet)
+    }
+
+    pub fn encode(self, json: &Value) -> Vec<u8> {
+        match self {
+            Self::Json => json.to_string().into_bytes(),
+            // maps keep their field names
+            Self::MessagePack => rmp_serde::to_vec_named(json).unwrap(),
+        }
+    }
+
+    pub fn decode(self, payload: &[u8]) -> Option<Value> {
+        match self {
+            Self::Json => serde_json::from_slice(payload).ok(),
+            Self::MessagePack => rmp_serde::from_slice(payload).ok(),
+        }
+    }
+}
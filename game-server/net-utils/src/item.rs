@@ -0,0 +1,46 @@
+// items lying on the map, picked up by moving onto their tile
+use serde::{Deserialize, Serialize};
+
+// hp given back by a health potion, up to the hp of the character
+pub const POTION_HP: u8 = 30;
+pub const ATTACK_BONUS: u8 = 5;
+pub const BOOTS_BONUS: u8 = 2;
+// turns of the player the attack buff and the boots last, from his next one
+pub const EFFECT_TURNS: u8 = 3;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Item {
+    HealthPotion,
+    AttackBuff,
+    Boots,
+}
+
+impl Item {
+    pub const ALL: [Item; 3] = [Self::HealthPotion, Self::AttackBuff, Self::Boots];
+
+    pub fn from_tile(tile: char) -> Option<Self> {
+        Some(match tile {
+            'H' => Self::HealthPotion,
+            'A' => Self::AttackBuff,
+            'B' => Self::Boots,
+            _ => return None,
+        })
+    }
+
+    pub fn tile(self) -> char {
+        match self {
+            Self::HealthPotion => 'H',
+            Self::AttackBuff => 'A',
+            Self::Boots => 'B',
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::HealthPotion => "health potion",
+            Self::AttackBuff => "attack buff",
+            Self::Boots => "movement boots",
+        }
+    }
+}
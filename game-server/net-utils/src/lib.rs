@@ -1,3 +1,6 @@
 pub mod character;
+pub mod encoding;
+pub mod item;
 pub mod map;
 pub mod packet;
+pub mod rules;
@@ -1,10 +1,156 @@
+use crate::item::Item;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Clone)]
+// nothing on the tile, players can walk on it
+pub const EMPTY_TILE: char = '0';
+// out of sight (fog of war), could be anything
+pub const HIDDEN_TILE: char = '?';
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Point(pub i16, pub i16);
+// tile whose content changed, [x, y, tile] on the wire
+pub type TileChange = (i16, i16, char);
+
 #[derive(Clone)]
 pub enum GameDataType {
     Attack(Point),
     Movement(Point),
     Skip,
 }
+
+// players can walk on the empty tiles and on the items
+pub fn walkable(tile: char) -> bool {
+    tile == EMPTY_TILE || Item::from_tile(tile).is_some()
+}
+
+// grid of tiles: '0' empty, 'R' rock, 'W' water, 'T' tree, '1' to '4' players, '?' hidden,
+// 'H', 'A' and 'B' items
+// written as rows separated by '\n' on the wire, Point(x, y) with (0, 0) the top left tile
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameMap {
+    tiles: Vec<Vec<char>>,
+}
+
+impl GameMap {
+    pub fn height(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn width(&self) -> usize {
+        self.tiles[0].len()
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point.0 >= 0
+            && point.1 >= 0
+            && (point.0 as usize) < self.width()
+            && (point.1 as usize) < self.height()
+    }
+
+    pub fn tile(&self, point: Point) -> Option<char> {
+        if !self.contains(point) {
+            return None;
+        }
+        Some(self.tiles[point.1 as usize][point.0 as usize])
+    }
+
+    pub fn set_tile(&mut self, point: Point, tile: char) {
+        if self.contains(point) {
+            self.tiles[point.1 as usize][point.0 as usize] = tile;
+        }
+    }
+
+    // the tiles further than radius from center (in both directions) are hidden
+    pub fn view(&self, center: Point, radius: u8) -> GameMap {
+        let radius = radius as i16;
+        let tiles = self
+            .tiles
+            .iter()
+            .enumerate()
+            .map(|(y, line)| {
+                line.iter()
+                    .enumerate()
+                    .map(|(x, tile)| {
+                        let visible = (x as i16 - center.0).abs() <= radius
+                            && (y as i16 - center.1).abs() <= radius;
+                        if visible {
+                            *tile
+                        } else {
+                            HIDDEN_TILE
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        GameMap { tiles }
+    }
+
+    // tiles of other that are different in this map, both maps have the same size
+    pub fn changes(&self, other: &GameMap) -> Vec<TileChange> {
+        let mut changes = vec![];
+        for (y, (line, other_line)) in self.tiles.iter().zip(&other.tiles).enumerate() {
+            for (x, (tile, other_tile)) in line.iter().zip(other_line).enumerate() {
+                if tile != other_tile {
+                    changes.push((x as i16, y as i16, *other_tile));
+                }
+            }
+        }
+        changes
+    }
+
+    pub fn apply(&mut self, changes: &[TileChange]) {
+        for (x, y, tile) in changes {
+            self.set_tile(Point(*x, *y), *tile);
+        }
+    }
+
+    // positions of every tile matching
+    pub fn find_all(&self, matching: impl Fn(char) -> bool) -> Vec<Point> {
+        let mut points = vec![];
+        for (y, line) in self.tiles.iter().enumerate() {
+            for (x, tile) in line.iter().enumerate() {
+                if matching(*tile) {
+                    points.push(Point(x as i16, y as i16));
+                }
+            }
+        }
+        points
+    }
+
+    // position of the first tile matching
+    pub fn find(&self, tile: char) -> Option<Point> {
+        self.tiles.iter().enumerate().find_map(|(y, line)| {
+            line.iter()
+                .position(|t| *t == tile)
+                .map(|x| Point(x as i16, y as i16))
+        })
+    }
+}
+
+impl FromStr for GameMap {
+    type Err = String;
+
+    fn from_str(map: &str) -> Result<Self, Self::Err> {
+        let tiles: Vec<Vec<char>> = map.lines().map(|line| line.chars().collect()).collect();
+        if tiles.is_empty() || tiles[0].is_empty() {
+            return Err("empty map".into());
+        }
+        if tiles.iter().any(|line| line.len() != tiles[0].len()) {
+            return Err("map rows have different widths".into());
+        }
+        Ok(Self { tiles })
+    }
+}
+
+impl fmt::Display for GameMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<String> = self
+            .tiles
+            .iter()
+            .map(|line| line.iter().collect())
+            .collect();
+        write!(f, "{}", rows.join("\n"))
+    }
+}
@@ -6,6 +6,27 @@ pub mod request_codes {
     pub const CHAR_CHOOSING: u64 = 14;
     pub const GM_START: u64 = 15;
     pub const GM_DATA: u64 = 16;
+    // create an account (pseudo and password), logs the player in
+    pub const REGISTER: u64 = 17;
+    // session token of a registered player, used as player token
+    pub const LOGIN: u64 = 18;
+    // best rated registered players
+    pub const LEADERBOARD: u64 = 19;
+    // statistics of a registered player
+    pub const PL_STATS: u64 = 20;
+    // the host adds a bot (easy, normal or hard) to his game before it starts
+    pub const ADD_BOT: u64 = 21;
+    // watch a game without playing (full map, even in fog of war games), not for its players
+    pub const GM_SPECTATE: u64 = 22;
+    // deprecated, refused with ERR_UNSUP_PROTO: the protocol version and encoding are chosen
+    // with HELLO
+    pub const PROTO_NEG: u64 = 23;
+    // full map (the view of the player) and state version of the game, to recover from a
+    // missed game data packet
+    pub const GM_SNAPSHOT: u64 = 24;
+    // handshake: client name and protocol version (and encoding), the server replies with
+    // what it supports, can be sent at any time
+    pub const HELLO: u64 = 25;
 }
 
 pub mod status_codes {
@@ -23,6 +44,13 @@ pub mod status_codes {
     pub const OK_GM_START: u64 = 25;
     // game data
     pub const OK_GM_DATA: u64 = 26;
+    // game over, sent to every player after the last player standing is known (the players
+    // of the last team standing in team games, no winner when the game is ended by an operator)
+    pub const OK_GM_OVER: u64 = 27;
+    // account created, with a session token
+    pub const OK_REGISTER: u64 = 28;
+    // logged in, with a session token
+    pub const OK_LOGIN: u64 = 29;
 
     pub const ERR_INTERNAL_SERV: u64 = 30;
     // malformed request
@@ -43,6 +71,75 @@ pub mod status_codes {
     pub const ERR_GM_NOT_FULL: u64 = 38;
     // game not started (can't send game data)
     pub const ERR_GM_NOT_START: u64 = 39;
+    // server full (too many games)
+    pub const ERR_SERV_FULL: u64 = 40;
+    // too many requests, try again later
+    pub const ERR_THROTTLED: u64 = 41;
+    // too many players created on the connection
+    pub const ERR_PL_LIMIT: u64 = 42;
+    // server shutting down, sent without request before the connection is closed
+    // (a started game can be resumed by joining it again once the server is back)
+    pub const SERV_SHUTDOWN: u64 = 43;
+    // not the player turn (can't send game data)
+    pub const ERR_NOT_TURN: u64 = 44;
+    // pseudo already registered (by someone else)
+    pub const ERR_PSEUD_TAKEN: u64 = 45;
+    // unknown pseudo or wrong password
+    pub const ERR_INV_LOGIN: u64 = 46;
+    // password too short or too long
+    pub const ERR_INV_PASSWD: u64 = 47;
+    // no finished game recorded for this pseudo
+    pub const ERR_NO_STATS: u64 = 48;
+    // protocol version or encoding not supported by the server (or PROTO_NEG request)
+    pub const ERR_UNSUP_PROTO: u64 = 49;
+
+    // ok statuses that didn't fit in 20..30
+    // leaderboard
+    pub const OK_LEADERBOARD: u64 = 60;
+    // player statistics
+    pub const OK_PL_STATS: u64 = 61;
+    // spectating a game
+    pub const OK_GM_SPECTATE: u64 = 62;
+    // deprecated with PROTO_NEG, not sent anymore (OK_HELLO replaced it)
+    pub const OK_PROTO_NEG: u64 = 63;
+    // map and state version of the game
+    pub const OK_GM_SNAPSHOT: u64 = 64;
+    // handshake accepted, with the versions, encodings and capabilities of the server
+    pub const OK_HELLO: u64 = 65;
+    // message of the server operators, sent without request
+    pub const SERV_NOTICE: u64 = 66;
+    // a player was removed from the game before its start (by an operator), sent without
+    // request with the players left
+    pub const GM_PL_LEFT: u64 = 67;
+
+    // errors that didn't fit in 30..50
+    // request not allowed at this point of the connection (game request before joining a
+    // game, lobby request during a game, spectating a game the player is in)
+    pub const ERR_WRONG_STATE: u64 = 70;
+    // removed from the game by an operator, sent without request, back in the lobby
+    pub const GM_KICKED: u64 = 71;
+
+    pub fn is_error(code: u64) -> bool {
+        (ERR_INTERNAL_SERV..50).contains(&code) || (ERR_WRONG_STATE..80).contains(&code)
+    }
+}
+
+// features advertised in the HELLO reply
+pub mod capabilities {
+    // REGISTER, LOGIN, LEADERBOARD and PL_STATS
+    pub const ACCOUNTS: &str = "accounts";
+    // ADD_BOT
+    pub const BOTS: &str = "bots";
+    // GM_SPECTATE
+    pub const SPECTATE: &str = "spectate";
+    // fog_of_war game option
+    pub const FOG_OF_WAR: &str = "fog_of_war";
+    // tile changes in game data packets, GM_SNAPSHOT
+    pub const MAP_DELTAS: &str = "map_deltas";
+    // teams game option (2v2)
+    pub const TEAMS: &str = "teams";
+    // item tiles on the map, item and stats in game data packets
+    pub const ITEMS: &str = "items";
 }
 
 pub mod game_data_code {
@@ -0,0 +1,107 @@
+// movement and attack rules, checked by the server before applying an action and by the
+// clients to preview the legal ones (on a fog of war view, hidden tiles can't be walked on)
+use crate::map::{walkable, GameMap, Point, EMPTY_TILE};
+use std::cmp::max;
+use std::collections::VecDeque;
+
+// number of moves (up, down, left, right on walkable tiles) to go from start to every
+// tile, indexed [y][x], None for the tiles that can't be reached
+pub fn distances(map: &GameMap, start: Point) -> Vec<Vec<Option<u16>>> {
+    let mut distances = vec![vec![None; map.width()]; map.height()];
+    distances[start.1 as usize][start.0 as usize] = Some(0);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(point) = queue.pop_front() {
+        let distance = distances[point.1 as usize][point.0 as usize].unwrap();
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let next = Point(point.0 + dx, point.1 + dy);
+            if map.tile(next).is_some_and(walkable)
+                && distances[next.1 as usize][next.0 as usize].is_none()
+            {
+                distances[next.1 as usize][next.0 as usize] = Some(distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+// attacks reach every tile of the square around the player
+pub fn chebyshev_distance(a: Point, b: Point) -> i16 {
+    max((a.0 - b.0).abs(), (a.1 - b.1).abs())
+}
+
+// dest is an empty tile (or an item) the player can reach with his movement speed
+pub fn can_move(map: &GameMap, player_num: char, dest: Point, character_ms: u8) -> bool {
+    if !map.tile(dest).is_some_and(walkable) {
+        return false;
+    }
+    let start = match map.find(player_num) {
+        Some(p) => p,
+        None => return false,
+    };
+
+    distances(map, start)[dest.1 as usize][dest.0 as usize]
+        .is_some_and(|distance| distance <= character_ms as u16)
+}
+
+// every tile the player can move to
+pub fn reachable_tiles(map: &GameMap, player_num: char, character_ms: u8) -> Vec<Point> {
+    let start = match map.find(player_num) {
+        Some(p) => p,
+        None => return vec![],
+    };
+
+    let mut reachable = vec![];
+    for (y, line) in distances(map, start).iter().enumerate() {
+        for (x, distance) in line.iter().enumerate() {
+            if distance.is_some_and(|d| d > 0 && d <= character_ms as u16) {
+                reachable.push(Point(x as i16, y as i16));
+            }
+        }
+    }
+
+    reachable
+}
+
+// number of the enemy standing on target if he is in the player attack range
+pub fn attack_target(
+    map: &GameMap,
+    player_num: char,
+    target: Point,
+    character_rng: u8,
+) -> Option<char> {
+    let enemy = map.tile(target)?;
+    if !enemy.is_ascii_digit() || enemy == EMPTY_TILE || enemy == player_num {
+        return None;
+    }
+
+    let position = map.find(player_num)?;
+    if chebyshev_distance(position, target) > character_rng as i16 {
+        return None;
+    }
+
+    Some(enemy)
+}
+
+// tiles of the enemies the player can attack
+pub fn attackable_tiles(map: &GameMap, player_num: char, character_rng: u8) -> Vec<Point> {
+    let position = match map.find(player_num) {
+        Some(p) => p,
+        None => return vec![],
+    };
+
+    let rng = character_rng as i16;
+    let mut attackable = vec![];
+    for y in position.1 - rng..=position.1 + rng {
+        for x in position.0 - rng..=position.0 + rng {
+            let target = Point(x, y);
+            if attack_target(map, player_num, target, character_rng).is_some() {
+                attackable.push(target);
+            }
+        }
+    }
+
+    attackable
+}
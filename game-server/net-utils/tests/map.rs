@@ -0,0 +1,27 @@
+use net_utils::map::{GameMap, Point, HIDDEN_TILE};
+
+#[test]
+fn changes_rebuild_the_new_map() {
+    let before: GameMap = "1R00\n0000\n0002".parse().unwrap();
+    let after: GameMap = "0R00\n0100\n0000".parse().unwrap();
+    let changes = before.changes(&after);
+    // from, to and cleared tiles
+    assert_eq!(changes, [(0, 0, '0'), (1, 1, '1'), (3, 2, '0')]);
+
+    let mut map = before.clone();
+    map.apply(&changes);
+    assert_eq!(map, after);
+    assert!(after.changes(&after).is_empty());
+}
+
+#[test]
+fn moving_changes_the_visible_tiles() {
+    let map: GameMap = "10000\n00000\n0000R".parse().unwrap();
+    let before = map.view(Point(0, 0), 1);
+    let after = map.view(Point(1, 0), 1);
+    let mut view = before.clone();
+    view.apply(&before.changes(&after));
+    assert_eq!(view, after);
+    assert_eq!(view.tile(Point(2, 1)), Some('0'));
+    assert_eq!(view.tile(Point(4, 2)), Some(HIDDEN_TILE));
+}
@@ -0,0 +1,45 @@
+use net_utils::map::{GameMap, Point};
+use net_utils::rules::*;
+
+#[test]
+fn movement_goes_around_obstacles() {
+    let map: GameMap = "1R00\n0R00\n0002".parse().unwrap();
+    // (0, 0) -> (2, 0) takes 6 moves around the rocks
+    assert!(!can_move(&map, '1', Point(2, 0), 4));
+    assert!(can_move(&map, '1', Point(2, 0), 6));
+    // occupied, blocked or outside the map
+    assert!(!can_move(&map, '1', Point(3, 2), 10));
+    assert!(!can_move(&map, '1', Point(1, 0), 10));
+    assert!(!can_move(&map, '1', Point(4, 0), 10));
+
+    let mut reachable = reachable_tiles(&map, '1', 2);
+    reachable.sort_by_key(|p| (p.1, p.0));
+    assert_eq!(reachable, [Point(0, 1), Point(0, 2)]);
+    assert!(reachable_tiles(&map, '3', 2).is_empty());
+}
+
+#[test]
+fn attacks_reach_the_square_around_the_player() {
+    let map: GameMap = "1000\n0000\n0230\n0004".parse().unwrap();
+    assert_eq!(attack_target(&map, '1', Point(1, 2), 2), Some('2'));
+    assert_eq!(attack_target(&map, '1', Point(3, 3), 2), None);
+    // empty tile and the player himself
+    assert_eq!(attack_target(&map, '1', Point(1, 1), 2), None);
+    assert_eq!(attack_target(&map, '1', Point(0, 0), 2), None);
+
+    let mut attackable = attackable_tiles(&map, '1', 2);
+    attackable.sort_by_key(|p| (p.1, p.0));
+    assert_eq!(attackable, [Point(1, 2), Point(2, 2)]);
+    assert_eq!(chebyshev_distance(Point(0, 0), Point(3, 3)), 3);
+}
+
+#[test]
+fn items_can_be_walked_on() {
+    let map: GameMap = "1HR\nRA0\nBR2".parse().unwrap();
+    assert!(can_move(&map, '1', Point(1, 0), 1));
+    // through the attack buff
+    assert!(can_move(&map, '1', Point(2, 1), 3));
+    // boots walled in by rocks
+    assert!(!can_move(&map, '1', Point(0, 2), 10));
+    assert_eq!(attack_target(&map, '1', Point(1, 1), 1), None);
+}
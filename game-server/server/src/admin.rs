@@ -0,0 +1,254 @@
+// admin console: line based text commands on a local address, for the server operators
+// the first line must be "auth <token>", each command is answered with its output lines
+// followed by "ok" or by a single "error: <reason>" line
+use crate::game::{GameHandle, GameSummary, PlayerSummary};
+use crate::response::Notice;
+use crate::server::State;
+use std::fmt::Write;
+use std::io;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, info_span, warn, Instrument};
+
+const HELP: &str = "\
+games                       list the games
+players                     list the players of the games
+game <game token>           show the map, turn and players of a game
+kick <game token> <player>  remove a player (number or pseudo) from a game, the game ends with
+                            its host if it isn't started
+end <game token>            end a game without winner
+notice <message>            send a message to every connection
+quit                        close the console";
+
+// longest line read (newline included), checked before the authentication too
+const MAX_LINE_LEN: usize = 4096;
+
+pub async fn serve_admin(listener: TcpListener, state: Arc<State>, token: String) {
+    let token: Arc<str> = token.into();
+    while let Ok((stream, addr)) = listener.accept().await {
+        let (state, token) = (Arc::clone(&state), Arc::clone(&token));
+        let span = info_span!("admin", %addr);
+        tokio::spawn(
+            async move {
+                if let Err(e) = admin_session(stream, &state, &token).await {
+                    debug!("admin connection failed: {e}");
+                }
+            }
+            .instrument(span),
+        );
+    }
+    warn!("admin listener stopped");
+}
+
+// compares every byte, the time taken doesn't tell how much of the token is right
+fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// next line without its newline, None at the end of the stream or after a line longer than
+// MAX_LINE_LEN (the client is told before the connection is closed)
+async fn next_line(
+    reader: &mut BufReader<OwnedReadHalf>,
+    writer: &mut OwnedWriteHalf,
+) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader
+        .take(MAX_LINE_LEN as u64)
+        .read_line(&mut line)
+        .await?
+        == 0
+    {
+        return Ok(None);
+    }
+    if line.len() == MAX_LINE_LEN && !line.ends_with('\n') {
+        warn!("admin line too long");
+        writer.write_all(b"error: line too long\n").await?;
+        return Ok(None);
+    }
+    let len = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(len);
+    Ok(Some(line))
+}
+
+async fn admin_session(stream: TcpStream, state: &State, token: &str) -> anyhow::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    let authenticated = match next_line(&mut reader, &mut writer).await? {
+        Some(line) => line
+            .strip_prefix("auth ")
+            .is_some_and(|given| token_matches(given.trim(), token)),
+        None => return Ok(()),
+    };
+    if !authenticated {
+        warn!("admin authentication failed");
+        writer.write_all(b"error: invalid token\n").await?;
+        return Ok(());
+    }
+    info!("admin connected");
+    writer.write_all(b"ok\n").await?;
+
+    while let Some(line) = next_line(&mut reader, &mut writer).await? {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "quit" {
+            writer.write_all(b"ok\n").await?;
+            break;
+        }
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let response = match command_output(state, command, args.trim()).await {
+            Ok(output) => format!("{output}ok\n"),
+            Err(e) => format!("error: {e}\n"),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    info!("admin disconnected");
+    Ok(())
+}
+
+// output lines of the command (each one ending with a newline)
+async fn command_output(state: &State, command: &str, args: &str) -> Result<String, String> {
+    match (command, args) {
+        ("help", _) => Ok(format!("{HELP}\n")),
+        ("games", "") => {
+            let mut output = String::new();
+            for (game_token, summary) in summaries(state).await {
+                let status = if summary.started {
+                    "started"
+                } else {
+                    "waiting"
+                };
+                writeln!(
+                    output,
+                    "{game_token} {status} players={}/{} turn={} turns={} spectators={}",
+                    summary.players.len(),
+                    summary.max_players,
+                    summary.turn.get(..1).unwrap_or("-"),
+                    summary.turn_count,
+                    summary.spectators
+                )
+                .unwrap();
+            }
+            Ok(output)
+        }
+        ("players", "") => {
+            let mut output = String::new();
+            for (game_token, summary) in summaries(state).await {
+                for player in &summary.players {
+                    writeln!(output, "{game_token} {}", player_line(player)).unwrap();
+                }
+            }
+            Ok(output)
+        }
+        ("game", game_token) if !game_token.is_empty() => {
+            let summary = game(state, game_token)
+                .await?
+                .inspect()
+                .await
+                .ok_or("game over")?;
+            Ok(game_output(&summary))
+        }
+        ("kick", args) => {
+            let (game_token, player) = args
+                .split_once(' ')
+                .ok_or("usage: kick <game token> <player>")?;
+            game(state, game_token)
+                .await?
+                .kick(player.trim())
+                .await
+                .ok_or("game over")??;
+            info!(game = game_token, player = player.trim(), "player kicked");
+            Ok(String::new())
+        }
+        ("end", game_token) if !game_token.is_empty() => {
+            game(state, game_token).await?.end().await;
+            info!(game = game_token, "game ended");
+            Ok(String::new())
+        }
+        ("notice", message) if !message.is_empty() => {
+            // no connection is an error for the broadcast channel only
+            let receivers = state
+                .notices
+                .send(Notice::json_string(message).unwrap())
+                .unwrap_or(0);
+            info!(receivers, "notice sent");
+            Ok(format!("sent to {receivers} connections\n"))
+        }
+        _ => Err(format!("unknown command {command:?}, see help")),
+    }
+}
+
+async fn game(state: &State, game_token: &str) -> Result<GameHandle, String> {
+    state
+        .state
+        .lock()
+        .await
+        .get(game_token)
+        .cloned()
+        .ok_or_else(|| format!("no game {game_token}"))
+}
+
+// games sorted by token, the lock is released before asking the game tasks
+async fn summaries(state: &State) -> Vec<(String, GameSummary)> {
+    let mut games: Vec<(String, GameHandle)> = state
+        .state
+        .lock()
+        .await
+        .iter()
+        .map(|(token, game)| (token.clone(), game.clone()))
+        .collect();
+    games.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut summaries = vec![];
+    for (game_token, game) in games {
+        // the game may have ended in between
+        if let Some(summary) = game.inspect().await {
+            summaries.push((game_token, summary));
+        }
+    }
+    summaries
+}
+
+fn player_line(player: &PlayerSummary) -> String {
+    let connection = match (player.bot, player.connected) {
+        (true, _) => "bot",
+        (false, true) => "connected",
+        (false, false) => "disconnected",
+    };
+    let character = match &*player.character {
+        "" => "-",
+        character => character,
+    };
+    format!(
+        "{} {} {character} hp={} {connection}",
+        player.player_num, player.pseudo, player.hp
+    )
+}
+
+fn game_output(summary: &GameSummary) -> String {
+    let mut output = String::new();
+    writeln!(
+        output,
+        "started={} fog_of_war={} turn={} turns={} spectators={}",
+        summary.started,
+        summary.fog_of_war,
+        summary.turn.get(..1).unwrap_or("-"),
+        summary.turn_count,
+        summary.spectators
+    )
+    .unwrap();
+    for player in &summary.players {
+        writeln!(output, "{}", player_line(player)).unwrap();
+    }
+    writeln!(output, "{}", summary.map).unwrap();
+    output
+}
@@ -0,0 +1,270 @@
+use crate::config::ResultsConfig;
+use anyhow::{bail, Context};
+use ring::digest::{digest, SHA256};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    // first entry of the log: the map the game started on, players and first items placed
+    Start,
+    // item spawned by the server during the game
+    Spawn,
+    Move,
+    Attack,
+    Skip,
+    // turn skipped by the turn timer
+    Timeout,
+    // player kicked by an operator
+    Kick,
+}
+
+// one entry of the replay log of a game, chained to the previous one: changing, removing
+// or reordering an entry changes every hash after it
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct LoggedAction {
+    // state version the action was played on
+    pub version: u32,
+    // empty for the entries of the server (start and spawns)
+    pub player_num: String,
+    pub kind: ActionKind,
+    // [x, y] of moves, attacks and spawns
+    pub target: Option<[i16; 2]>,
+    // tile of the spawned item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<char>,
+    // start map
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    // hex sha256 of the previous hash and of this action
+    pub hash: String,
+}
+
+impl LoggedAction {
+    // entry without hash yet, see chain_action
+    pub fn new(version: u32, player_num: &str, kind: ActionKind, target: Option<[i16; 2]>) -> Self {
+        Self {
+            version,
+            player_num: player_num.into(),
+            kind,
+            target,
+            item: None,
+            map: None,
+            hash: String::new(),
+        }
+    }
+}
+
+// what a finished game produced, signed as a whole by the server
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ResultRecord {
+    pub game_token: String,
+    // unix time
+    pub finished_at: u64,
+    // [player_num, pseudo, character] ordered by player number
+    pub players: Vec<[String; 3]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
+    // player number, or player numbers of the winning team
+    pub winner: String,
+    pub actions: Vec<LoggedAction>,
+    // hash of the last action
+    pub chain: String,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SignedRecord {
+    // json of the ResultRecord, kept as it was signed: the signature is checked against
+    // these bytes, not against the record serialized again
+    pub record: Box<RawValue>,
+    // hex ed25519 public key of the server and signature of the json record
+    pub public_key: String,
+    pub signature: String,
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// hash the start of the game is chained to, ties the log to its game
+pub fn chain_start(game_token: &str) -> String {
+    to_hex(digest(&SHA256, game_token.as_bytes()).as_ref())
+}
+
+pub fn chain_hash(previous: &str, action: &LoggedAction) -> String {
+    let LoggedAction {
+        version,
+        player_num,
+        kind,
+        target,
+        item,
+        map,
+        hash: _,
+    } = action;
+    let action =
+        serde_json::to_string(&(previous, version, player_num, kind, target, item, map)).unwrap();
+    to_hex(digest(&SHA256, action.as_bytes()).as_ref())
+}
+
+// next entry of the log, chained to the hash of the previous one (chain_start for the first)
+pub fn chain_action(previous: &str, mut action: LoggedAction) -> LoggedAction {
+    action.hash = chain_hash(previous, &action);
+    action
+}
+
+// check the hash chain and the signature of a record, against the public key the server
+// published (the key in the record is only compared to it), the record once both hold
+pub fn verify(signed: &SignedRecord, public_key: &str) -> anyhow::Result<ResultRecord> {
+    if !signed.public_key.eq_ignore_ascii_case(public_key.trim()) {
+        bail!("signed by another key ({})", signed.public_key);
+    }
+    let key = from_hex(public_key.trim()).context("public key is not hex")?;
+    let signature = from_hex(&signed.signature).context("signature is not hex")?;
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(signed.record.get().as_bytes(), &signature)
+        .ok()
+        .context("bad signature")?;
+
+    let record: ResultRecord = serde_json::from_str(signed.record.get())?;
+    // the map the actions were played on is part of the chain
+    if record.actions.first().map(|a| a.kind) != Some(ActionKind::Start) {
+        bail!("the log doesn't start with the map of the game");
+    }
+    let mut previous = chain_start(&record.game_token);
+    for (i, action) in record.actions.iter().enumerate() {
+        let hash = chain_hash(&previous, action);
+        if hash != action.hash {
+            bail!("hash chain broken at action {i}");
+        }
+        previous = hash;
+    }
+    if previous != record.chain {
+        bail!("chain doesn't end with the last action");
+    }
+    Ok(record)
+}
+
+// signs the records of the finished games and saves them to the results directory
+pub struct ResultSigner {
+    key: Ed25519KeyPair,
+    dir: PathBuf,
+}
+
+impl ResultSigner {
+    // the key (pkcs8) is generated on first use, its public key is written next to it
+    // (<key>.pub, hex) to be handed to whoever verifies the records
+    pub fn load(config: &ResultsConfig) -> anyhow::Result<Self> {
+        let pkcs8 = match fs::read(&config.key) {
+            Ok(pkcs8) => {
+                check_private(&config.key)?;
+                pkcs8
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+                    .ok()
+                    .context("can't generate signing key")?;
+                write_private(&config.key, pkcs8.as_ref())
+                    .with_context(|| format!("can't write {}", config.key.display()))?;
+                pkcs8.as_ref().to_vec()
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("can't read {}", config.key.display()))
+            }
+        };
+        let key = Ed25519KeyPair::from_pkcs8(&pkcs8)
+            .ok()
+            .with_context(|| format!("no ed25519 key in {}", config.key.display()))?;
+        let signer = Self {
+            key,
+            dir: config.dir.clone(),
+        };
+
+        let public_key_path = public_key_path(&config.key);
+        fs::write(&public_key_path, signer.public_key() + "\n")
+            .with_context(|| format!("can't write {}", public_key_path.display()))?;
+        fs::create_dir_all(&config.dir)
+            .with_context(|| format!("can't create {}", config.dir.display()))?;
+        Ok(signer)
+    }
+
+    pub fn public_key(&self) -> String {
+        to_hex(self.key.public_key().as_ref())
+    }
+
+    pub fn sign(&self, record: &ResultRecord) -> SignedRecord {
+        let record = RawValue::from_string(serde_json::to_string(record).unwrap()).unwrap();
+        let signature = self.key.sign(record.get().as_bytes());
+        SignedRecord {
+            record,
+            public_key: self.public_key(),
+            signature: to_hex(signature.as_ref()),
+        }
+    }
+
+    // <dir>/<game_token>.json
+    pub fn save(&self, record: ResultRecord) -> anyhow::Result<PathBuf> {
+        let path = self.dir.join(format!("{}.json", record.game_token));
+        let signed = self.sign(&record);
+        fs::write(&path, serde_json::to_string_pretty(&signed)?)
+            .with_context(|| format!("can't write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+// anyone able to read the key can sign records: only the user running the server can
+#[cfg(unix)]
+fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(content)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, content: &[u8]) -> io::Result<()> {
+    fs::write(path, content)
+}
+
+#[cfg(unix)]
+fn check_private(path: &Path) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        bail!(
+            "{} can be read by other users (mode {:o}), it should be 600",
+            path.display(),
+            mode & 0o777
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &Path) -> anyhow::Result<()> {
+    Ok(())
+}
+
+pub fn public_key_path(key: &Path) -> PathBuf {
+    let mut path = key.as_os_str().to_owned();
+    path.push(".pub");
+    PathBuf::from(path)
+}
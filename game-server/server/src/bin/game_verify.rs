@@ -0,0 +1,54 @@
+// checks signed game results offline: the signature against the public key of the server
+// and the hash chain of the actions
+use anyhow::Context;
+use clap::Parser;
+use game_server::audit::{verify, ResultRecord, SignedRecord};
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser, Debug)]
+#[command(about = "verify signed game results")]
+struct Args {
+    // <key>.pub file written by the server
+    #[arg(short, long)]
+    public_key: PathBuf,
+    // result records (<results dir>/<game token>.json)
+    #[arg(required = true)]
+    records: Vec<PathBuf>,
+}
+
+fn check(path: &PathBuf, public_key: &str) -> anyhow::Result<ResultRecord> {
+    let content = fs::read_to_string(path).context("can't read the record")?;
+    let signed: SignedRecord = serde_json::from_str(&content).context("invalid record")?;
+    verify(&signed, public_key)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args = Args::parse();
+    let public_key = fs::read_to_string(&args.public_key)
+        .with_context(|| format!("can't read {}", args.public_key.display()))?;
+
+    let mut valid = true;
+    for path in &args.records {
+        match check(path, &public_key) {
+            Ok(record) => {
+                println!(
+                    "ok {}: winner {}, {} actions",
+                    path.display(),
+                    record.winner,
+                    record.actions.len()
+                );
+            }
+            Err(e) => {
+                println!("invalid {}: {e:#}", path.display());
+                valid = false;
+            }
+        }
+    }
+
+    Ok(match valid {
+        true => ExitCode::SUCCESS,
+        false => ExitCode::FAILURE,
+    })
+}
@@ -0,0 +1,152 @@
+use net_utils::map::{GameDataType, GameMap, Point};
+use net_utils::rules::{attack_target, can_move, chebyshev_distance, distances, reachable_tiles};
+use rand::prelude::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// characters a bot can pick when added to a game
+const BOT_CHARACTERS: [&str; 3] = ["bar", "bow", "mag"];
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    // one tile per turn, misses some attacks
+    Easy,
+    // approaches the nearest enemy and attacks when in range
+    Normal,
+    // goes after the weakest enemy and finishes it off first
+    Hard,
+}
+
+impl Difficulty {
+    pub fn new(difficulty: &str) -> Option<Self> {
+        Some(match difficulty {
+            "easy" => Self::Easy,
+            "normal" => Self::Normal,
+            "hard" => Self::Hard,
+            _ => return None,
+        })
+    }
+
+    // time taken to play from the base bot delay, the harder the faster
+    pub fn think_time(self, bot_delay: Duration) -> Duration {
+        match self {
+            Self::Easy => bot_delay * 3 / 2,
+            Self::Normal => bot_delay,
+            Self::Hard => bot_delay / 2,
+        }
+    }
+}
+
+pub fn random_character() -> &'static str {
+    BOT_CHARACTERS.choose(&mut thread_rng()).unwrap()
+}
+
+// enemy still alive on the map
+pub struct Enemy {
+    pub position: Point,
+    pub hp: u8,
+}
+
+// action of the bot playing player_num with the given stats (atk, hp, ms, rng), checked
+// with the same rules as the actions of the players
+pub fn choose_action(
+    map: &GameMap,
+    player_num: char,
+    stats: (u8, u8, u8, u8),
+    enemies: &[Enemy],
+    difficulty: Difficulty,
+) -> GameDataType {
+    let (_, _, ms, rng) = stats;
+    let mut random = thread_rng();
+
+    let mut in_range: Vec<&Enemy> = enemies
+        .iter()
+        .filter(|e| attack_target(map, player_num, e.position, rng).is_some())
+        .collect();
+    if difficulty == Difficulty::Hard {
+        in_range.sort_by_key(|e| e.hp);
+    }
+    if let Some(target) = in_range.first() {
+        if difficulty != Difficulty::Easy || random.gen_bool(0.7) {
+            return GameDataType::Attack(target.position);
+        }
+    }
+
+    let position = match map.find(player_num) {
+        Some(p) => p,
+        None => return GameDataType::Skip,
+    };
+    let goal = match difficulty {
+        Difficulty::Hard => enemies
+            .iter()
+            .min_by_key(|e| (e.hp, chebyshev_distance(position, e.position))),
+        _ => enemies
+            .iter()
+            .min_by_key(|e| chebyshev_distance(position, e.position)),
+    };
+    let steps = match difficulty {
+        Difficulty::Easy => ms.min(1),
+        _ => ms,
+    };
+    let dest = match goal {
+        Some(goal) => approach(map, player_num, position, goal.position, steps),
+        // every enemy is hidden by the fog of war
+        None => wander(map, player_num, steps),
+    };
+
+    match dest {
+        Some(dest) => GameDataType::Movement(dest),
+        None => GameDataType::Skip,
+    }
+}
+
+// random reachable tile
+fn wander(map: &GameMap, player_num: char, steps: u8) -> Option<Point> {
+    reachable_tiles(map, player_num, steps)
+        .choose(&mut thread_rng())
+        .copied()
+}
+
+// reachable tile closest to goal (by path), None if the bot can't get closer
+fn approach(
+    map: &GameMap,
+    player_num: char,
+    position: Point,
+    goal: Point,
+    steps: u8,
+) -> Option<Point> {
+    let from_goal = distances(map, goal);
+    let from_bot = distances(map, position);
+    let goal_distance = |p: Point| from_goal[p.1 as usize][p.0 as usize];
+
+    // the bot tile isn't walkable, its distance is the one of its best neighbour
+    let current = [(0, -1), (0, 1), (-1, 0), (1, 0)]
+        .iter()
+        .map(|(dx, dy)| Point(position.0 + dx, position.1 + dy))
+        .filter(|p| map.contains(*p))
+        .filter_map(goal_distance)
+        .min()
+        .map(|d| d + 1);
+
+    let mut best: Option<(u16, u16, Point)> = None;
+    for (y, line) in from_bot.iter().enumerate() {
+        for (x, steps_needed) in line.iter().enumerate() {
+            let dest = Point(x as i16, y as i16);
+            let (steps_needed, distance) = match (steps_needed, goal_distance(dest)) {
+                (Some(s), Some(d)) if *s > 0 && *s <= steps as u16 => (*s, d),
+                _ => continue,
+            };
+            if best.is_none_or(|(d, s, _)| (distance, steps_needed) < (d, s)) {
+                best = Some((distance, steps_needed, dest));
+            }
+        }
+    }
+
+    let (distance, _, dest) = best?;
+    if current.is_some_and(|c| distance >= c) {
+        return None;
+    }
+    can_move(map, player_num, dest, steps).then_some(dest)
+}
@@ -0,0 +1,610 @@
+use anyhow::{bail, Context};
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use tracing::level_filters::LevelFilter;
+
+// shorter admin tokens are refused
+const MIN_ADMIN_TOKEN_LEN: usize = 16;
+
+// command line flags, each one can also be set through its environment variable
+// precedence: flags > environment variables > config file > defaults
+#[derive(Parser, Debug, Default)]
+#[command(about = "turn based game server")]
+pub struct Args {
+    // toml configuration file
+    #[arg(short, long, env = "GAME_SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[arg(long, env = "GAME_SERVER_BIND")]
+    pub bind: Option<SocketAddr>,
+    #[arg(long, env = "GAME_SERVER_LOG_LEVEL")]
+    pub log_level: Option<String>,
+    // seconds given to the connections to finish their request on shutdown
+    #[arg(long, env = "GAME_SERVER_SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+    // seconds given to a new connection to finish its tls or websocket handshake
+    #[arg(long, env = "GAME_SERVER_HANDSHAKE_TIMEOUT")]
+    pub handshake_timeout: Option<u64>,
+    #[arg(long, env = "GAME_SERVER_STORAGE")]
+    pub storage: Option<StorageBackend>,
+    #[arg(long, env = "GAME_SERVER_REDIS_URL")]
+    pub redis_url: Option<String>,
+    // memory backend: file the data is saved to on shutdown and loaded from at startup
+    #[arg(long, env = "GAME_SERVER_STORAGE_SNAPSHOT")]
+    pub storage_snapshot: Option<PathBuf>,
+    #[arg(long, env = "GAME_SERVER_MAP_HEIGHT")]
+    pub map_height: Option<u8>,
+    #[arg(long, env = "GAME_SERVER_MAP_WIDTH")]
+    pub map_width: Option<u8>,
+    #[arg(long, env = "GAME_SERVER_MAX_PLAYERS")]
+    pub max_players: Option<u8>,
+    // seconds, 0 disables the turn timer
+    #[arg(long, env = "GAME_SERVER_TURN_TIMEOUT")]
+    pub turn_timeout: Option<u64>,
+    #[arg(long, env = "GAME_SERVER_MAX_GAMES")]
+    pub max_games: Option<usize>,
+    #[arg(long, env = "GAME_SERVER_CHANNEL_CAPACITY")]
+    pub channel_capacity: Option<usize>,
+    // milliseconds taken by a normal bot to play
+    #[arg(long, env = "GAME_SERVER_BOT_DELAY")]
+    pub bot_delay: Option<u64>,
+    // tiles seen around a player in fog of war games
+    #[arg(long, env = "GAME_SERVER_VISION_RADIUS")]
+    pub vision_radius: Option<u8>,
+    // turns between two item spawns, 0 disables the items
+    #[arg(long, env = "GAME_SERVER_ITEM_INTERVAL")]
+    pub item_interval: Option<u32>,
+    #[arg(long, env = "GAME_SERVER_REQUESTS_PER_SECOND")]
+    pub requests_per_second: Option<u32>,
+    #[arg(long, env = "GAME_SERVER_REQUEST_BURST")]
+    pub request_burst: Option<u32>,
+    #[arg(long, env = "GAME_SERVER_IP_REQUESTS_PER_SECOND")]
+    pub ip_requests_per_second: Option<u32>,
+    #[arg(long, env = "GAME_SERVER_IP_REQUEST_BURST")]
+    pub ip_request_burst: Option<u32>,
+    #[arg(long, env = "GAME_SERVER_MAX_PLAYERS_PER_CONNECTION")]
+    pub max_players_per_connection: Option<u32>,
+    // seconds without activity before a player is removed
+    #[arg(long, env = "GAME_SERVER_PLAYER_TTL")]
+    pub player_ttl: Option<u64>,
+    // seconds without activity before a game is removed
+    #[arg(long, env = "GAME_SERVER_GAME_TTL")]
+    pub game_ttl: Option<u64>,
+    // seconds between two sweeps of the expired players and games
+    #[arg(long, env = "GAME_SERVER_SWEEP_INTERVAL")]
+    pub sweep_interval: Option<u64>,
+    // seconds a session token of a registered player stays valid after login
+    #[arg(long, env = "GAME_SERVER_SESSION_TTL")]
+    pub session_ttl: Option<u64>,
+    // address of the http endpoint exposing the metrics (disabled if not set)
+    #[arg(long, env = "GAME_SERVER_METRICS_BIND")]
+    pub metrics_bind: Option<SocketAddr>,
+    // local address of the admin console (disabled if not set), needs the admin token
+    #[arg(long, env = "GAME_SERVER_ADMIN_BIND")]
+    pub admin_bind: Option<SocketAddr>,
+    #[arg(long, env = "GAME_SERVER_ADMIN_TOKEN", hide_env_values = true)]
+    pub admin_token: Option<String>,
+    // address of the websocket listener (disabled if not set)
+    #[arg(long, env = "GAME_SERVER_WEBSOCKET_BIND")]
+    pub websocket_bind: Option<SocketAddr>,
+    #[arg(long, env = "GAME_SERVER_TLS_CERT")]
+    pub tls_cert: Option<PathBuf>,
+    #[arg(long, env = "GAME_SERVER_TLS_KEY")]
+    pub tls_key: Option<PathBuf>,
+    // directory the signed results of the finished games are written to (disabled if not set)
+    #[arg(long, env = "GAME_SERVER_RESULTS_DIR")]
+    pub results_dir: Option<PathBuf>,
+    // ed25519 key signing the results, generated if the file doesn't exist
+    #[arg(long, env = "GAME_SERVER_RESULTS_KEY")]
+    pub results_key: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Redis,
+    Memory,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: SocketAddr,
+    pub log_level: String,
+    // seconds given to the connections to finish their request on shutdown
+    pub shutdown_timeout: u64,
+    // seconds given to a new connection to finish its tls or websocket handshake, a client
+    // stalling it can't hold the connection open
+    pub handshake_timeout: u64,
+    pub storage: StorageConfig,
+    pub game: GameConfig,
+    pub limits: LimitsConfig,
+    pub expiry: ExpiryConfig,
+    pub metrics: Option<MetricsConfig>,
+    pub admin: Option<AdminConfig>,
+    pub websocket: Option<WebSocketConfig>,
+    pub tls: Option<TlsConfig>,
+    pub results: Option<ResultsConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+    // only used by the redis backend
+    pub url: String,
+    // only used by the memory backend: the data is saved to this file on shutdown
+    // and loaded from it at startup (redis persists its data by itself)
+    pub snapshot: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    pub map_height: u8,
+    pub map_width: u8,
+    // players needed to start a game
+    pub max_players: u8,
+    // seconds a player has to play before his turn is skipped (0: no limit)
+    pub turn_timeout: u64,
+    // games stored at the same time
+    pub max_games: usize,
+    // capacity of the channel used to broadcast packets inside a game
+    pub channel_capacity: usize,
+    // milliseconds taken by a normal bot to play (easy bots are slower, hard ones faster),
+    // bots always play before half of the turn timeout
+    pub bot_delay: u64,
+    // fog of war games: the players see the tiles at most vision_radius tiles away
+    // (in both directions)
+    pub vision_radius: u8,
+    // one item per player is spawned when a game starts, then one every item_interval
+    // turns (0: no items)
+    pub item_interval: u32,
+}
+
+// token buckets used to throttle clients: a bucket holds up to burst requests
+// and is refilled with requests_per_second requests every second
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    // per connection
+    pub requests_per_second: u32,
+    pub request_burst: u32,
+    // shared by every connection from the same ip
+    pub ip_requests_per_second: u32,
+    pub ip_request_burst: u32,
+    pub max_players_per_connection: u32,
+}
+
+// players and games without activity are removed by a periodic sweeper,
+// players still in a game are kept until the game itself expires
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExpiryConfig {
+    // seconds
+    pub player_ttl: u64,
+    pub game_ttl: u64,
+    pub sweep_interval: u64,
+    // lifetime of the session tokens given on register/login, activity doesn't extend it
+    pub session_ttl: u64,
+}
+
+// prometheus text format served on http://<bind>/metrics
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub bind: SocketAddr,
+}
+
+// text commands to manage the games (see admin.rs), only on a loopback address
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    pub bind: SocketAddr,
+    // sent by the operator before any command
+    pub token: String,
+}
+
+// the token stays out of the logs
+impl fmt::Debug for AdminConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConfig")
+            .field("bind", &self.bind)
+            .finish_non_exhaustive()
+    }
+}
+
+// same json packets as the tcp protocol, one per text frame
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketConfig {
+    pub bind: SocketAddr,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+// every finished game gets a signed result record (see audit.rs)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ResultsConfig {
+    pub dir: PathBuf,
+    // pkcs8, the public key is written to <key>.pub
+    pub key: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([127, 0, 0, 1], 8000)),
+            log_level: "info".into(),
+            shutdown_timeout: 10,
+            handshake_timeout: 10,
+            storage: StorageConfig::default(),
+            game: GameConfig::default(),
+            limits: LimitsConfig::default(),
+            expiry: ExpiryConfig::default(),
+            metrics: None,
+            admin: None,
+            websocket: None,
+            tls: None,
+            results: None,
+        }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            backend: StorageBackend::Redis,
+            url: "redis://127.0.0.1:6379".into(),
+            snapshot: None,
+        }
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            map_height: 5,
+            map_width: 10,
+            max_players: 2,
+            turn_timeout: 60,
+            max_games: 1000,
+            channel_capacity: 50,
+            bot_delay: 1000,
+            vision_radius: 3,
+            item_interval: 5,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 5,
+            request_burst: 20,
+            ip_requests_per_second: 20,
+            ip_request_burst: 60,
+            max_players_per_connection: 3,
+        }
+    }
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            player_ttl: 3600,
+            game_ttl: 1800,
+            sweep_interval: 60,
+            session_ttl: 604800,
+        }
+    }
+}
+
+impl Config {
+    // build the configuration from the config file (if any) and the command line / environment
+    pub fn load(args: Args) -> anyhow::Result<Self> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .with_context(|| format!("can't read config file {}", path.display()))?;
+                toml::from_str(&content)
+                    .with_context(|| format!("invalid config file {}", path.display()))?
+            }
+            None => Config::default(),
+        };
+        config.apply_args(args)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn apply_args(&mut self, args: Args) -> anyhow::Result<()> {
+        if let Some(bind) = args.bind {
+            self.bind = bind;
+        }
+        if let Some(log_level) = args.log_level {
+            self.log_level = log_level;
+        }
+        if let Some(shutdown_timeout) = args.shutdown_timeout {
+            self.shutdown_timeout = shutdown_timeout;
+        }
+        if let Some(handshake_timeout) = args.handshake_timeout {
+            self.handshake_timeout = handshake_timeout;
+        }
+        if let Some(backend) = args.storage {
+            self.storage.backend = backend;
+        }
+        if let Some(url) = args.redis_url {
+            self.storage.url = url;
+        }
+        if let Some(snapshot) = args.storage_snapshot {
+            self.storage.snapshot = Some(snapshot);
+        }
+        if let Some(map_height) = args.map_height {
+            self.game.map_height = map_height;
+        }
+        if let Some(map_width) = args.map_width {
+            self.game.map_width = map_width;
+        }
+        if let Some(max_players) = args.max_players {
+            self.game.max_players = max_players;
+        }
+        if let Some(turn_timeout) = args.turn_timeout {
+            self.game.turn_timeout = turn_timeout;
+        }
+        if let Some(max_games) = args.max_games {
+            self.game.max_games = max_games;
+        }
+        if let Some(channel_capacity) = args.channel_capacity {
+            self.game.channel_capacity = channel_capacity;
+        }
+        if let Some(bot_delay) = args.bot_delay {
+            self.game.bot_delay = bot_delay;
+        }
+        if let Some(vision_radius) = args.vision_radius {
+            self.game.vision_radius = vision_radius;
+        }
+        if let Some(item_interval) = args.item_interval {
+            self.game.item_interval = item_interval;
+        }
+
+        if let Some(requests_per_second) = args.requests_per_second {
+            self.limits.requests_per_second = requests_per_second;
+        }
+        if let Some(request_burst) = args.request_burst {
+            self.limits.request_burst = request_burst;
+        }
+        if let Some(ip_requests_per_second) = args.ip_requests_per_second {
+            self.limits.ip_requests_per_second = ip_requests_per_second;
+        }
+        if let Some(ip_request_burst) = args.ip_request_burst {
+            self.limits.ip_request_burst = ip_request_burst;
+        }
+        if let Some(max_players_per_connection) = args.max_players_per_connection {
+            self.limits.max_players_per_connection = max_players_per_connection;
+        }
+
+        if let Some(player_ttl) = args.player_ttl {
+            self.expiry.player_ttl = player_ttl;
+        }
+        if let Some(game_ttl) = args.game_ttl {
+            self.expiry.game_ttl = game_ttl;
+        }
+        if let Some(sweep_interval) = args.sweep_interval {
+            self.expiry.sweep_interval = sweep_interval;
+        }
+        if let Some(session_ttl) = args.session_ttl {
+            self.expiry.session_ttl = session_ttl;
+        }
+
+        if let Some(bind) = args.metrics_bind {
+            self.metrics = Some(MetricsConfig { bind });
+        }
+        if args.admin_bind.is_some() || args.admin_token.is_some() {
+            let admin = self.admin.take();
+            self.admin = Some(match (args.admin_bind, args.admin_token, admin) {
+                (Some(bind), Some(token), _) => AdminConfig { bind, token },
+                (Some(bind), None, Some(admin)) => AdminConfig { bind, ..admin },
+                (None, Some(token), Some(admin)) => AdminConfig { token, ..admin },
+                _ => {
+                    bail!("invalid config: the admin console needs both a bind address and a token")
+                }
+            });
+        }
+        if let Some(bind) = args.websocket_bind {
+            self.websocket = Some(WebSocketConfig { bind });
+        }
+
+        if args.tls_cert.is_some() || args.tls_key.is_some() {
+            let tls = self.tls.take();
+            self.tls = Some(match (args.tls_cert, args.tls_key, tls) {
+                (Some(cert), Some(key), _) => TlsConfig { cert, key },
+                (Some(cert), None, Some(tls)) => TlsConfig { cert, key: tls.key },
+                (None, Some(key), Some(tls)) => TlsConfig {
+                    cert: tls.cert,
+                    key,
+                },
+                _ => bail!("invalid config: tls needs both a certificate and a private key"),
+            });
+        }
+        if args.results_dir.is_some() || args.results_key.is_some() {
+            let results = self.results.take();
+            self.results = Some(match (args.results_dir, args.results_key, results) {
+                (Some(dir), Some(key), _) => ResultsConfig { dir, key },
+                (Some(dir), None, Some(results)) => ResultsConfig { dir, ..results },
+                (None, Some(key), Some(results)) => ResultsConfig { key, ..results },
+                _ => bail!("invalid config: signed results need both a directory and a key"),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.log_level.parse::<LevelFilter>().is_err() {
+            bail!(
+                "invalid config: log_level must be one of off, error, warn, info, debug, trace (got {:?})",
+                self.log_level
+            );
+        }
+        if self.storage.backend == StorageBackend::Redis
+            && redis::parse_redis_url(&self.storage.url).is_none()
+        {
+            bail!(
+                "invalid config: storage url {:?} is not a redis url",
+                self.storage.url
+            );
+        }
+        if self.shutdown_timeout > 300 {
+            bail!(
+                "invalid config: shutdown_timeout must be at most 300 seconds (got {})",
+                self.shutdown_timeout
+            );
+        }
+        if !(1..=60).contains(&self.handshake_timeout) {
+            bail!(
+                "invalid config: handshake_timeout must be between 1 and 60 seconds (got {})",
+                self.handshake_timeout
+            );
+        }
+
+        let game = &self.game;
+        // first and last rows are kept free for the players spawns
+        if !(3..=50).contains(&game.map_height) {
+            bail!(
+                "invalid config: map_height must be between 3 and 50 (got {})",
+                game.map_height
+            );
+        }
+        if !(2..=50).contains(&game.map_width) {
+            bail!(
+                "invalid config: map_width must be between 2 and 50 (got {})",
+                game.map_width
+            );
+        }
+        if !(2..=4).contains(&game.max_players) {
+            bail!(
+                "invalid config: max_players must be between 2 and 4 (got {})",
+                game.max_players
+            );
+        }
+        if game.turn_timeout > 3600 {
+            bail!(
+                "invalid config: turn_timeout must be at most 3600 seconds (got {})",
+                game.turn_timeout
+            );
+        }
+        if game.max_games == 0 {
+            bail!("invalid config: max_games must be greater than 0");
+        }
+        if game.channel_capacity == 0 {
+            bail!("invalid config: channel_capacity must be greater than 0");
+        }
+        if game.vision_radius == 0 {
+            bail!("invalid config: vision_radius must be greater than 0");
+        }
+        if game.bot_delay > 10000 {
+            bail!(
+                "invalid config: bot_delay must be at most 10000 milliseconds (got {})",
+                game.bot_delay
+            );
+        }
+
+        let limits = &self.limits;
+        for (name, value) in [
+            ("requests_per_second", limits.requests_per_second),
+            ("request_burst", limits.request_burst),
+            ("ip_requests_per_second", limits.ip_requests_per_second),
+            ("ip_request_burst", limits.ip_request_burst),
+            (
+                "max_players_per_connection",
+                limits.max_players_per_connection,
+            ),
+        ] {
+            if value == 0 {
+                bail!("invalid config: {name} must be greater than 0");
+            }
+        }
+
+        let expiry = &self.expiry;
+        for (name, value) in [
+            ("player_ttl", expiry.player_ttl),
+            ("game_ttl", expiry.game_ttl),
+            ("sweep_interval", expiry.sweep_interval),
+            ("session_ttl", expiry.session_ttl),
+        ] {
+            if value == 0 {
+                bail!("invalid config: {name} must be greater than 0");
+            }
+        }
+        // a game waiting for a slow player must not expire
+        if game.turn_timeout >= expiry.game_ttl {
+            bail!(
+                "invalid config: game_ttl must be greater than turn_timeout (got {} <= {})",
+                expiry.game_ttl,
+                game.turn_timeout
+            );
+        }
+
+        if let Some(metrics) = &self.metrics {
+            if metrics.bind == self.bind {
+                bail!("invalid config: metrics bind address is the game server address");
+            }
+        }
+        if let Some(websocket) = &self.websocket {
+            if websocket.bind == self.bind
+                || self
+                    .metrics
+                    .as_ref()
+                    .is_some_and(|m| m.bind == websocket.bind)
+            {
+                bail!("invalid config: websocket bind address is already used");
+            }
+        }
+
+        if let Some(admin) = &self.admin {
+            if !admin.bind.ip().is_loopback() {
+                bail!(
+                    "invalid config: admin bind address must be a loopback address (got {})",
+                    admin.bind
+                );
+            }
+            let used = [
+                Some(self.bind),
+                self.metrics.as_ref().map(|m| m.bind),
+                self.websocket.as_ref().map(|w| w.bind),
+            ];
+            if used.contains(&Some(admin.bind)) {
+                bail!("invalid config: admin bind address is already used");
+            }
+            if admin.token.len() < MIN_ADMIN_TOKEN_LEN {
+                bail!(
+                    "invalid config: admin token must be at least {MIN_ADMIN_TOKEN_LEN} characters long"
+                );
+            }
+        }
+
+        if let Some(tls) = &self.tls {
+            for path in [&tls.cert, &tls.key] {
+                if !path.is_file() {
+                    bail!("invalid config: tls file {} not found", path.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level.parse().unwrap_or(LevelFilter::INFO)
+    }
+}
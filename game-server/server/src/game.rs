@@ -0,0 +1,1298 @@
+use crate::action_check::reach_destination;
+use crate::audit::{chain_action, chain_start, ActionKind, LoggedAction, ResultRecord};
+use crate::bot::{choose_action, random_character, Difficulty, Enemy};
+use crate::response::packet_sizes::*;
+use crate::response::*;
+use crate::server::State;
+use crate::stats::{record_game, GameResult};
+use crate::storage::{new_game_player, unix_time, GameInfo, Storage};
+use async_channel::Sender;
+use net_utils::character::CharacterClass;
+use net_utils::item::*;
+use net_utils::map::{GameDataType, GameMap, Point, TileChange, EMPTY_TILE};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::status_codes::*;
+use net_utils::rules::{attack_target, can_move};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde_json::json;
+use std::future::pending;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::{info, info_span, warn, Instrument};
+
+// packet for the player who sent the command, or the error status to send him
+pub type Reply = Result<String, (u64, u16)>;
+
+// players of a game with teams (two teams of two)
+pub const TEAM_GAME_PLAYERS: u8 = 4;
+// items lying on the map at the same time
+const MAX_ITEMS: usize = 5;
+
+pub enum GameCommand {
+    // join the game, or reconnect to it if the player is already in
+    Join {
+        player_token: String,
+        pseudo: String,
+        ip: IpAddr,
+        sender: Sender<String>,
+        reply: oneshot::Sender<Reply>,
+    },
+    ChooseCharacter {
+        player_token: String,
+        character: String,
+        reply: oneshot::Sender<Reply>,
+    },
+    // the host fills a seat with a bot
+    AddBot {
+        player_token: String,
+        difficulty: Difficulty,
+        reply: oneshot::Sender<Reply>,
+    },
+    Start {
+        player_token: String,
+        reply: oneshot::Sender<Reply>,
+    },
+    Play {
+        player_token: String,
+        action: GameDataType,
+        reply: oneshot::Sender<Reply>,
+    },
+    // watch the game (full map), refused to the accounts and addresses of the players in fog
+    // of war games
+    Spectate {
+        player_token: String,
+        pseudo: String,
+        ip: IpAddr,
+        sender: Sender<String>,
+        reply: oneshot::Sender<Reply>,
+    },
+    // current map and state version, for a player or spectator who missed a packet
+    Snapshot {
+        player_token: String,
+        reply: oneshot::Sender<Reply>,
+    },
+    // admin console: state of the game
+    Inspect {
+        reply: oneshot::Sender<GameSummary>,
+    },
+    // admin console: a player (number or pseudo) is removed and his connection goes back to the
+    // lobby, he is counted as dead in a started game and leaves the game before its start (the
+    // game ends if he is the host)
+    Kick {
+        player: String,
+        reply: oneshot::Sender<Result<(), &'static str>>,
+    },
+    // admin console: the game is over without winner and without recording the results
+    End {
+        done: oneshot::Sender<()>,
+    },
+    // server shutdown: the players are told, the game is saved and the task stops
+    Shutdown {
+        done: oneshot::Sender<()>,
+    },
+    // the game expired: the players and spectators are told the game is over (without winner),
+    // the task stops without saving
+    Stop {
+        done: oneshot::Sender<()>,
+    },
+}
+
+// used by the player connections to send commands to the game task
+#[derive(Clone)]
+pub struct GameHandle {
+    commands: mpsc::Sender<GameCommand>,
+}
+
+impl GameHandle {
+    // None if the game task stopped
+    pub async fn request(
+        &self,
+        command: impl FnOnce(oneshot::Sender<Reply>) -> GameCommand,
+    ) -> Option<Reply> {
+        let (reply, response) = oneshot::channel();
+        self.commands.send(command(reply)).await.ok()?;
+        response.await.ok()
+    }
+
+    pub async fn shutdown(&self) {
+        let (done, stopped) = oneshot::channel();
+        if self
+            .commands
+            .send(GameCommand::Shutdown { done })
+            .await
+            .is_ok()
+        {
+            let _ = stopped.await;
+        }
+    }
+
+    pub async fn stop(&self) {
+        let (done, stopped) = oneshot::channel();
+        if self.commands.send(GameCommand::Stop { done }).await.is_ok() {
+            let _ = stopped.await;
+        }
+    }
+
+    // None if the game task stopped
+    pub async fn inspect(&self) -> Option<GameSummary> {
+        let (reply, summary) = oneshot::channel();
+        self.commands
+            .send(GameCommand::Inspect { reply })
+            .await
+            .ok()?;
+        summary.await.ok()
+    }
+
+    pub async fn kick(&self, player: &str) -> Option<Result<(), &'static str>> {
+        let (reply, result) = oneshot::channel();
+        let player = player.into();
+        self.commands
+            .send(GameCommand::Kick { player, reply })
+            .await
+            .ok()?;
+        result.await.ok()
+    }
+
+    pub async fn end(&self) {
+        let (done, ended) = oneshot::channel();
+        if self.commands.send(GameCommand::End { done }).await.is_ok() {
+            let _ = ended.await;
+        }
+    }
+}
+
+// game as shown by the admin console
+pub struct GameSummary {
+    pub started: bool,
+    pub fog_of_war: bool,
+    pub max_players: u8,
+    // player numbers of the players alive in playing order (a player can appear twice in team
+    // games)
+    pub turn: String,
+    pub turn_count: u32,
+    pub map: String,
+    pub players: Vec<PlayerSummary>,
+    pub spectators: usize,
+}
+
+pub struct PlayerSummary {
+    pub player_num: String,
+    pub pseudo: String,
+    pub character: String,
+    pub hp: u8,
+    pub connected: bool,
+    pub bot: bool,
+}
+
+pub struct GamePlayer {
+    pub token: String,
+    pub pseudo: String,
+    // player number, character and stats (hp included)
+    pub infos: GamePlayerInfos,
+    // None while the player is not connected (e.g. after a restart)
+    pub sender: Option<Sender<String>>,
+    // address of the last connection of the player, None for the bots and the players not
+    // connected since a restart
+    pub ip: Option<IpAddr>,
+}
+
+// bots have no player record, their token is only known by the game
+pub fn bot_token(player_num: &str) -> String {
+    format!("bot-{player_num}")
+}
+
+pub fn bot_pseudo(player_num: &str) -> String {
+    format!("bot{player_num}")
+}
+
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => pending().await,
+    }
+}
+
+// players 1 to 4 spawn in the top left, bottom right, top right and bottom left corners
+fn place_players(map: &mut GameMap, player_count: u8) {
+    let (height, width) = (map.height() as i16, map.width() as i16);
+    let spawns = [
+        Point(0, 0),
+        Point(width - 1, height - 1),
+        Point(width - 1, 0),
+        Point(0, height - 1),
+    ];
+    for (num, spawn) in spawns.iter().take(player_count as usize).enumerate() {
+        map.set_tile(*spawn, char::from(b'1' + num as u8));
+    }
+}
+
+// one task per game owning its state, the storage only gets snapshots of it
+struct Game {
+    state: Arc<State>,
+    token: String,
+    info: GameInfo,
+    map: GameMap,
+    // ordered by player number
+    players: Vec<GamePlayer>,
+    store: Box<dyn Storage>,
+    // end of the current turn, None if the game isn't started or has no turn timer
+    deadline: Option<Instant>,
+    // when the bot whose turn it is plays, None if the current player isn't a bot
+    bot_deadline: Option<Instant>,
+    // (player token, sender) of the connections watching the game, not saved
+    spectators: Vec<(String, Sender<String>)>,
+}
+
+pub fn spawn_game(
+    state: &Arc<State>,
+    game_token: String,
+    info: GameInfo,
+    players: Vec<GamePlayer>,
+) -> anyhow::Result<GameHandle> {
+    let store = state.storage.get_connection()?;
+    let map = info.map.parse().map_err(anyhow::Error::msg)?;
+    let (commands, receiver) = mpsc::channel(state.config.channel_capacity);
+
+    let span = info_span!("game", game = %game_token);
+    let mut game = Game {
+        state: Arc::clone(state),
+        token: game_token,
+        info,
+        map,
+        players,
+        store,
+        deadline: None,
+        bot_deadline: None,
+        spectators: vec![],
+    };
+    if game.info.started {
+        game.reset_deadline();
+    }
+    tokio::spawn(game.run(receiver).instrument(span));
+
+    Ok(GameHandle { commands })
+}
+
+impl Game {
+    async fn run(mut self, mut commands: mpsc::Receiver<GameCommand>) {
+        loop {
+            let command = tokio::select! {
+                command = commands.recv() => command,
+                _ = sleep_until_some(self.bot_deadline) => {
+                    self.play_bot();
+                    if self.is_over() {
+                        self.finish().await;
+                        return;
+                    }
+                    continue;
+                },
+                _ = sleep_until_some(self.deadline) => {
+                    self.skip_turn();
+                    continue;
+                },
+            };
+
+            match command {
+                Some(GameCommand::Join {
+                    player_token,
+                    pseudo,
+                    ip,
+                    sender,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.join(player_token, pseudo, ip, sender));
+                }
+                Some(GameCommand::ChooseCharacter {
+                    player_token,
+                    character,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.choose_character(&player_token, character));
+                }
+                Some(GameCommand::AddBot {
+                    player_token,
+                    difficulty,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.add_bot(&player_token, difficulty));
+                }
+                Some(GameCommand::Spectate {
+                    player_token,
+                    pseudo,
+                    ip,
+                    sender,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.spectate(player_token, &pseudo, ip, sender));
+                }
+                Some(GameCommand::Snapshot {
+                    player_token,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.snapshot(&player_token));
+                }
+                Some(GameCommand::Start {
+                    player_token,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.start(&player_token));
+                }
+                Some(GameCommand::Play {
+                    player_token,
+                    action,
+                    reply,
+                }) => {
+                    let _ = reply.send(self.play(&player_token, action));
+                    if self.is_over() {
+                        self.finish().await;
+                        return;
+                    }
+                }
+                Some(GameCommand::Inspect { reply }) => {
+                    let _ = reply.send(self.summary());
+                }
+                Some(GameCommand::Kick { player, reply }) => {
+                    let _ = reply.send(self.kick(&player));
+                    if self.is_over() {
+                        self.finish().await;
+                        return;
+                    }
+                    // without host the game can't be started
+                    if !self.info.started && self.info.host_player.is_empty() {
+                        info!("game ended, its host was kicked");
+                        self.broadcast_all(&GameOver::json_string(String::new()).unwrap());
+                        self.remove().await;
+                        return;
+                    }
+                }
+                Some(GameCommand::End { done }) => {
+                    info!("game ended by an operator");
+                    self.broadcast_all(&GameOver::json_string(String::new()).unwrap());
+                    self.remove().await;
+                    let _ = done.send(());
+                    return;
+                }
+                Some(GameCommand::Shutdown { done }) => {
+                    self.broadcast_all(&json!({ "status": SERV_SHUTDOWN }).to_string());
+                    self.persist();
+                    let _ = done.send(());
+                    return;
+                }
+                Some(GameCommand::Stop { done }) => {
+                    info!("game expired");
+                    self.broadcast_all(&GameOver::json_string(String::new()).unwrap());
+                    let _ = done.send(());
+                    return;
+                }
+                // every handle was dropped
+                None => return,
+            }
+        }
+    }
+
+    fn player(&self, player_token: &str) -> Option<&GamePlayer> {
+        self.players.iter().find(|p| p.token == player_token)
+    }
+
+    // [player_number, pseudo, character, is_host, team] of every player
+    fn player_vec(&self) -> Vec<[String; 5]> {
+        self.players
+            .iter()
+            .map(|p| {
+                let team = self.team(&p.infos.player_num);
+                [
+                    p.infos.player_num.clone(),
+                    p.pseudo.clone(),
+                    p.infos.character.clone(),
+                    u8::from(p.token == self.info.host_player).to_string(),
+                    team.map_or(String::new(), |team| (team + 1).to_string()),
+                ]
+            })
+            .collect()
+    }
+
+    // index of the team of the player, None if the game has no teams
+    fn team(&self, player_num: &str) -> Option<usize> {
+        self.info
+            .teams
+            .iter()
+            .position(|team| team.contains(player_num))
+    }
+
+    fn teammates(&self, player_num: &str, other_num: &str) -> bool {
+        self.team(player_num)
+            .is_some_and(|team| self.team(other_num) == Some(team))
+    }
+
+    // number of the player who has to play
+    fn current_player(&self) -> String {
+        String::from(&self.info.turn[0..1])
+    }
+
+    fn join(
+        &mut self,
+        player_token: String,
+        pseudo: String,
+        ip: IpAddr,
+        sender: Sender<String>,
+    ) -> Reply {
+        let rejoining = self.player(&player_token).is_some();
+        if rejoining {
+            info!("game rejoined");
+        } else {
+            if self.info.started {
+                return Err((ERR_GM_AL_START, GM_AL_START_SIZE));
+            }
+            if self.info.player_count >= self.info.max_players {
+                return Err((ERR_GM_FULL, GM_FULL_SIZE));
+            }
+
+            let player_num = self.free_player_num();
+            self.add_player(GamePlayer {
+                token: player_token.clone(),
+                pseudo,
+                infos: new_game_player(player_num),
+                sender: None,
+                ip: None,
+            });
+            info!(player_num, "game joined");
+        }
+
+        let player = self
+            .players
+            .iter_mut()
+            .find(|p| p.token == player_token)
+            .unwrap();
+        player.sender = Some(sender.clone());
+        player.ip = Some(ip);
+        let pseudo = player.pseudo.clone();
+        self.touch();
+        self.persist();
+
+        let json = GameJoining::json_string(&pseudo, self.player_vec()).unwrap();
+        self.broadcast(&player_token, &json);
+        if rejoining && self.info.started {
+            // current map and turn of the resumed game, sent after the join response
+            let map = self.map_view(self.player(&player_token));
+            let json = GameStarting::json_string(self.current_player(), map, self.info.turn_count);
+            let _ = sender.try_send(json.unwrap());
+        }
+        Ok(json)
+    }
+
+    fn choose_character(&mut self, player_token: &str, character: String) -> Reply {
+        let started = self.info.started;
+        let player = match self.players.iter_mut().find(|p| p.token == player_token) {
+            Some(p) => p,
+            None => return Err((ERR_GM_NOT_JOIN, GM_NOT_JOIN_SIZE)),
+        };
+        if started {
+            return Err((ERR_GM_AL_START, GM_AL_START_SIZE));
+        }
+        let character_class = match CharacterClass::new(&character) {
+            Some(c) => c,
+            None => return Err((ERR_MAL_REQ, MAL_REQ_SIZE)),
+        };
+
+        player.infos.stats = character_class.get_stats();
+        let json = CharacterChoosing::json_string(&player.pseudo, &character).unwrap();
+        player.infos.character = character;
+        self.touch();
+        self.persist();
+        self.broadcast(player_token, &json);
+        Ok(json)
+    }
+
+    fn start(&mut self, player_token: &str) -> Reply {
+        if self.info.started {
+            return Err((ERR_GM_AL_START, GM_AL_START_SIZE));
+        }
+        // only the host player can start the game
+        if self.info.host_player != player_token {
+            return Err((ERR_MAL_REQ, MAL_REQ_SIZE));
+        }
+        if self.info.player_count != self.info.max_players {
+            return Err((ERR_GM_NOT_FULL, GM_NOT_FULL_SIZE));
+        }
+
+        self.info.started = true;
+        //todo first: shuffle spawns (was not shuffled to test with client)
+        place_players(&mut self.map, self.info.player_count);
+        if self.state.config.item_interval > 0 {
+            for _ in 0..self.info.player_count {
+                self.spawn_item();
+            }
+        }
+        let mut start = LoggedAction::new(self.info.turn_count, "", ActionKind::Start, None);
+        start.map = Some(self.map.to_string());
+        self.append(start);
+        self.touch();
+        self.persist();
+        self.reset_deadline();
+        info!("game started");
+
+        let player_turn = self.current_player();
+        let packet = |player: Option<&GamePlayer>| {
+            let map = self.map_view(player);
+            GameStarting::json_string(player_turn.clone(), map, self.info.turn_count).unwrap()
+        };
+        self.broadcast_views(Some(player_token), &packet);
+        Ok(packet(self.player(player_token)))
+    }
+
+    fn play(&mut self, player_token: &str, action: GameDataType) -> Reply {
+        let player = match self.player(player_token) {
+            Some(p) => p,
+            None => return Err((ERR_GM_NOT_JOIN, GM_NOT_JOIN_SIZE)),
+        };
+        if !self.info.started {
+            return Err((ERR_GM_NOT_START, GM_NOT_START_SIZE));
+        }
+        let player_num = player.infos.player_num.clone();
+        if player_num != self.current_player() {
+            return Err((ERR_NOT_TURN, NOT_TURN_SIZE));
+        }
+
+        let num = player_num.chars().next().unwrap();
+        let (atk, _, ms, rng) = player.infos.stats;
+        // actions are checked against the map the player sees, the hidden tiles of fog of war
+        // games can't be probed with refused actions
+        let sight = self.view(&self.map, Some(player));
+        let before = self.map.clone();
+        let (data_type, enemy, item) = match action {
+            GameDataType::Movement(dest) if !can_move(&sight, num, dest, ms) => {
+                return Err((ERR_MAL_REQ, MAL_REQ_SIZE));
+            }
+            GameDataType::Movement(dest) => match reach_destination(&mut self.map, num, dest, ms) {
+                Some(item) => (GM_DATA_MOV, ("".into(), 0), item),
+                None => return Err((ERR_MAL_REQ, MAL_REQ_SIZE)),
+            },
+            GameDataType::Attack(target) => {
+                let enemy_num = match attack_target(&sight, num, target, rng) {
+                    Some(e) => e.to_string(),
+                    None => return Err((ERR_MAL_REQ, MAL_REQ_SIZE)),
+                };
+                // no friendly fire
+                if self.teammates(&player_num, &enemy_num) {
+                    return Err((ERR_MAL_REQ, MAL_REQ_SIZE));
+                }
+                let enemy = match self
+                    .players
+                    .iter_mut()
+                    .find(|p| p.infos.player_num == enemy_num)
+                {
+                    Some(e) => e,
+                    None => return Err((ERR_MAL_REQ, MAL_REQ_SIZE)),
+                };
+
+                let damage = atk.min(enemy.infos.stats.1);
+                enemy.infos.stats.1 -= damage;
+                let enemy_hp = enemy.infos.stats.1;
+                let player = self
+                    .players
+                    .iter_mut()
+                    .find(|p| p.token == player_token)
+                    .unwrap();
+                player.infos.damage_dealt += damage as u32;
+                if enemy_hp == 0 {
+                    player.infos.kills += 1;
+                    // dead players leave the map and the turn order
+                    self.map.set_tile(target, EMPTY_TILE);
+                    self.leave_turn(&enemy_num);
+                    info!(player_num = %enemy_num, "player killed");
+                }
+                (GM_DATA_ATK, (enemy_num, enemy_hp), None)
+            }
+            GameDataType::Skip => (GM_DATA_SKIP, ("".into(), 0), None),
+        };
+
+        let (kind, target) = match action {
+            GameDataType::Movement(Point(x, y)) => (ActionKind::Move, Some([x, y])),
+            GameDataType::Attack(Point(x, y)) => (ActionKind::Attack, Some([x, y])),
+            GameDataType::Skip => (ActionKind::Skip, None),
+        };
+        self.log(&player_num, kind, target);
+        self.wear_off(&player_num);
+        if let Some(item) = item {
+            self.pick_up(&player_num, item);
+        }
+        self.next_turn();
+        self.touch();
+        self.persist();
+
+        let (player_turn, version) = (self.current_player(), self.info.turn_count);
+        let stats = self.stats(&player_num);
+        let packet = |player: Option<&GamePlayer>| {
+            let (enemy, player_turn) = (enemy.clone(), player_turn.clone());
+            let changes = self.view_changes(&before, player);
+            GameData::json_string(
+                data_type,
+                player_turn,
+                (player_num.clone(), item, stats),
+                version,
+                changes,
+                enemy,
+            )
+            .unwrap()
+        };
+        self.broadcast_views(Some(player_token), &packet);
+        Ok(packet(self.player(player_token)))
+    }
+
+    // the current player didn't play before the turn timeout
+    fn skip_turn(&mut self) {
+        let player_num = self.current_player();
+        let before = self.map.clone();
+        self.log(&player_num, ActionKind::Timeout, None);
+        self.wear_off(&player_num);
+        self.next_turn();
+        self.persist();
+
+        // no player moved, only an item may have been spawned
+        let (player_turn, version) = (self.current_player(), self.info.turn_count);
+        let stats = self.stats(&player_num);
+        let packet = |player: Option<&GamePlayer>| {
+            let (player_turn, player_num) = (player_turn.clone(), player_num.clone());
+            let changes = self.view_changes(&before, player);
+            let enemy = ("".into(), 0);
+            GameData::json_string(
+                GM_DATA_SKIP,
+                player_turn,
+                (player_num, None, stats),
+                version,
+                changes,
+                enemy,
+            )
+            .unwrap()
+        };
+        self.broadcast_views(None, &packet);
+    }
+
+    // a dead player leaves the turn order, which is rebuilt from the living players in team
+    // games so the teams keep playing in turn: a lone survivor plays every other turn
+    fn leave_turn(&mut self, player_num: &str) {
+        let turn = self.info.turn.replace(player_num, "");
+        // each living player once, in the order they play from the current turn on
+        let mut alive: Vec<char> = vec![];
+        for num in turn.chars() {
+            if !alive.contains(&num) {
+                alive.push(num);
+            }
+        }
+        let first = match alive.first() {
+            Some(num) if !self.info.teams.is_empty() => num.to_string(),
+            _ => {
+                self.info.turn = turn;
+                return;
+            }
+        };
+        let (team, others): (Vec<char>, Vec<char>) = alive
+            .into_iter()
+            .partition(|num| self.teammates(&first, &num.to_string()));
+        if others.is_empty() {
+            self.info.turn = team.into_iter().collect();
+            return;
+        }
+        self.info.turn = (0..team.len().max(others.len()))
+            .flat_map(|i| [team[i % team.len()], others[i % others.len()]])
+            .collect();
+    }
+
+    fn next_turn(&mut self) {
+        let turn = &self.info.turn;
+        self.info.turn = format!("{}{}", &turn[1..], &turn[0..1]);
+        self.info.turn_count += 1;
+        let item_interval = self.state.config.item_interval;
+        if item_interval > 0 && self.info.turn_count.is_multiple_of(item_interval) {
+            // logged since the replay can't draw the same random tile and item
+            if let Some((Point(x, y), item)) = self.spawn_item() {
+                let mut spawn =
+                    LoggedAction::new(self.info.turn_count, "", ActionKind::Spawn, Some([x, y]));
+                spawn.item = Some(item.tile());
+                self.append(spawn);
+            }
+        }
+        self.reset_deadline();
+    }
+
+    // a random item on a random empty tile, unless there are enough of them on the map
+    fn spawn_item(&mut self) -> Option<(Point, Item)> {
+        let items = self.map.find_all(|tile| Item::from_tile(tile).is_some());
+        let empty = self.map.find_all(|tile| tile == EMPTY_TILE);
+        let mut rng = thread_rng();
+        match (items.len() < MAX_ITEMS, empty.choose(&mut rng)) {
+            (true, Some(&tile)) => {
+                let item = *Item::ALL.choose(&mut rng).unwrap();
+                self.map.set_tile(tile, item.tile());
+                Some((tile, item))
+            }
+            _ => None,
+        }
+    }
+
+    // atk, hp, ms, rng of a player, items included
+    fn stats(&self, player_num: &str) -> (u8, u8, u8, u8) {
+        self.players
+            .iter()
+            .find(|p| p.infos.player_num == player_num)
+            .map_or((0, 0, 0, 0), |p| p.infos.stats)
+    }
+
+    // the potion heals right away, the other items give their bonus for a few turns
+    fn pick_up(&mut self, player_num: &str, item: Item) {
+        let player = match self
+            .players
+            .iter_mut()
+            .find(|p| p.infos.player_num == player_num)
+        {
+            Some(p) => p,
+            None => return,
+        };
+        let infos = &mut player.infos;
+        match item {
+            Item::HealthPotion => {
+                let max_hp = CharacterClass::new(&infos.character).map_or(0, |c| c.get_stats().1);
+                infos.stats.1 = infos.stats.1.saturating_add(POTION_HP).min(max_hp);
+            }
+            Item::AttackBuff => infos.stats.0 = infos.stats.0.saturating_add(ATTACK_BONUS),
+            Item::Boots => infos.stats.2 = infos.stats.2.saturating_add(BOOTS_BONUS),
+        }
+        if item != Item::HealthPotion {
+            infos.effects.push(Effect {
+                item,
+                turns: EFFECT_TURNS,
+            });
+        }
+        info!(player_num, item = item.name(), "item picked up");
+    }
+
+    // end of a turn of the player: his bonuses last one turn less
+    fn wear_off(&mut self, player_num: &str) {
+        let player = match self
+            .players
+            .iter_mut()
+            .find(|p| p.infos.player_num == player_num)
+        {
+            Some(p) => p,
+            None => return,
+        };
+        let infos = &mut player.infos;
+        for effect in &mut infos.effects {
+            effect.turns -= 1;
+            match (effect.turns, effect.item) {
+                (0, Item::AttackBuff) => infos.stats.0 = infos.stats.0.saturating_sub(ATTACK_BONUS),
+                (0, Item::Boots) => infos.stats.2 = infos.stats.2.saturating_sub(BOOTS_BONUS),
+                _ => (),
+            }
+        }
+        infos.effects.retain(|effect| effect.turns > 0);
+    }
+
+    fn reset_deadline(&mut self) {
+        let turn_timeout = self.state.config.turn_timeout;
+        self.deadline =
+            (turn_timeout > 0).then(|| Instant::now() + Duration::from_secs(turn_timeout));
+
+        let current_player = self.current_player();
+        let bot = self
+            .players
+            .iter()
+            .find(|p| p.infos.player_num == current_player)
+            .and_then(|p| p.infos.bot);
+        self.bot_deadline = bot.map(|difficulty| {
+            let mut think_time =
+                difficulty.think_time(Duration::from_millis(self.state.config.bot_delay));
+            if turn_timeout > 0 {
+                think_time = think_time.min(Duration::from_secs(turn_timeout) / 2);
+            }
+            Instant::now() + think_time
+        });
+    }
+
+    fn add_bot(&mut self, player_token: &str, difficulty: Difficulty) -> Reply {
+        if self.info.host_player != player_token {
+            return Err((ERR_MAL_REQ, MAL_REQ_SIZE));
+        }
+        if self.info.started {
+            return Err((ERR_GM_AL_START, GM_AL_START_SIZE));
+        }
+        if self.info.player_count >= self.info.max_players {
+            return Err((ERR_GM_FULL, GM_FULL_SIZE));
+        }
+
+        let player_num = self.free_player_num();
+        let character = random_character();
+        let mut infos = new_game_player(player_num);
+        infos.character = character.into();
+        infos.stats = CharacterClass::new(character).unwrap().get_stats();
+        infos.bot = Some(difficulty);
+        let pseudo = bot_pseudo(&infos.player_num);
+        self.add_player(GamePlayer {
+            token: bot_token(&infos.player_num),
+            pseudo: pseudo.clone(),
+            infos,
+            sender: None,
+            ip: None,
+        });
+        info!(player_num, ?difficulty, "bot added");
+        self.touch();
+        self.persist();
+
+        let json = GameJoining::json_string(&pseudo, self.player_vec()).unwrap();
+        self.broadcast(player_token, &json);
+        // the bot picks its character right away
+        self.broadcast_all(&CharacterChoosing::json_string(&pseudo, character).unwrap());
+        Ok(json)
+    }
+
+    // the bot whose turn it is plays, like a player would
+    fn play_bot(&mut self) {
+        self.bot_deadline = None;
+        let current_player = self.current_player();
+        let bot = match self
+            .players
+            .iter()
+            .find(|p| p.infos.player_num == current_player)
+        {
+            Some(p) => p,
+            None => return,
+        };
+        let (token, infos) = (bot.token.clone(), bot.infos.clone());
+        let num = infos.player_num.chars().next().unwrap();
+        // bots don't see through the fog either
+        let map = match self.map.find(num) {
+            Some(position) if self.info.fog_of_war => {
+                self.map.view(position, self.state.config.vision_radius)
+            }
+            _ => self.map.clone(),
+        };
+        let enemies: Vec<Enemy> = self
+            .players
+            .iter()
+            .filter(|p| p.token != token && p.infos.stats.1 > 0)
+            .filter(|p| !self.teammates(&infos.player_num, &p.infos.player_num))
+            .filter_map(|p| {
+                let num = p.infos.player_num.chars().next()?;
+                Some(Enemy {
+                    position: map.find(num)?,
+                    hp: p.infos.stats.1,
+                })
+            })
+            .collect();
+
+        let difficulty = infos.bot.unwrap_or(Difficulty::Normal);
+        let action = choose_action(&map, num, infos.stats, &enemies, difficulty);
+        if self.play(&token, action).is_err() {
+            let _ = self.play(&token, GameDataType::Skip);
+        }
+    }
+
+    // one player (or one team) left standing
+    fn is_over(&self) -> bool {
+        let mut alive = self.info.turn.chars();
+        let first = alive.next().map(String::from).unwrap_or_default();
+        self.info.started && alive.all(|num| self.teammates(&first, &num.to_string()))
+    }
+
+    async fn finish(&mut self) {
+        let winner = self.current_player();
+        // every player of the team wins, the dead ones included
+        let winner = match self.team(&winner) {
+            Some(team) => self.info.teams[team].clone(),
+            None => winner,
+        };
+        info!(winner = %winner, "game over");
+        self.broadcast_all(&GameOver::json_string(winner.clone()).unwrap());
+        if let Err(e) = self.record_results(&winner) {
+            warn!("can't record game results: {e}");
+        }
+        self.sign_results(winner);
+        self.remove().await;
+    }
+
+    // the host can host another game
+    async fn remove(&mut self) {
+        if let Err(e) = self.store.remove_game(&self.token) {
+            warn!("can't remove finished game: {e}");
+        }
+        self.state.state.lock().await.remove(&self.token);
+    }
+
+    fn summary(&self) -> GameSummary {
+        let players = self
+            .players
+            .iter()
+            .map(|p| PlayerSummary {
+                player_num: p.infos.player_num.clone(),
+                pseudo: p.pseudo.clone(),
+                character: p.infos.character.clone(),
+                hp: p.infos.stats.1,
+                connected: p.sender.as_ref().is_some_and(|s| !s.is_closed()),
+                bot: p.infos.bot.is_some(),
+            })
+            .collect();
+        GameSummary {
+            started: self.info.started,
+            fog_of_war: self.info.fog_of_war,
+            max_players: self.info.max_players,
+            turn: self.info.turn.clone(),
+            turn_count: self.info.turn_count,
+            map: self.map.to_string(),
+            players,
+            spectators: self
+                .spectators
+                .iter()
+                .filter(|(_, s)| !s.is_closed())
+                .count(),
+        }
+    }
+
+    // the others see the kicked player die, like a skipped turn of his
+    fn kick(&mut self, player: &str) -> Result<(), &'static str> {
+        let index = self
+            .players
+            .iter()
+            .position(|p| p.infos.player_num == player || p.pseudo == player)
+            .ok_or("no such player in the game")?;
+        if !self.info.started {
+            self.leave_lobby(index);
+            return Ok(());
+        }
+        let player = &mut self.players[index];
+        let player_num = player.infos.player_num.clone();
+        if !self.info.turn.contains(&player_num) {
+            return Err("player already dead");
+        }
+
+        player.infos.stats.1 = 0;
+        if let Some(sender) = player.sender.take() {
+            let _ = sender.try_send(json!({ "status": GM_KICKED }).to_string());
+        }
+        let player_token = player.token.clone();
+        if player_token == self.info.host_player {
+            self.release_host();
+        }
+        let before = self.map.clone();
+        if let Some(position) = player_num.chars().next().and_then(|n| self.map.find(n)) {
+            self.map.set_tile(position, EMPTY_TILE);
+        }
+        let current = self.current_player() == player_num;
+        self.log(&player_num, ActionKind::Kick, None);
+        self.leave_turn(&player_num);
+        // new state version, the turn only moves on if it was the one of the kicked player
+        self.info.turn_count += 1;
+        if current {
+            self.reset_deadline();
+        }
+        self.persist();
+        info!(player_num = %player_num, "player kicked");
+
+        let (player_turn, version) = (self.current_player(), self.info.turn_count);
+        let stats = self.stats(&player_num);
+        let packet = |player: Option<&GamePlayer>| {
+            let (player_turn, player_num) = (player_turn.clone(), player_num.clone());
+            let changes = self.view_changes(&before, player);
+            let enemy = (player_num.clone(), 0);
+            GameData::json_string(
+                GM_DATA_SKIP,
+                player_turn,
+                (player_num, None, stats),
+                version,
+                changes,
+                enemy,
+            )
+            .unwrap()
+        };
+        self.broadcast_views(Some(&player_token), &packet);
+        Ok(())
+    }
+
+    // the player kicked before the start leaves the game, his number is given to the next
+    // player joining
+    fn leave_lobby(&mut self, index: usize) {
+        let player = self.players.remove(index);
+        if let Some(sender) = &player.sender {
+            let _ = sender.try_send(json!({ "status": GM_KICKED }).to_string());
+        }
+        self.info.player_count -= 1;
+        if let Err(e) = self.store.remove_game_player(&self.token, &player.token) {
+            warn!("can't remove player from the game: {e}");
+        }
+        if player.token == self.info.host_player {
+            self.release_host();
+        }
+        self.persist();
+        info!(player_num = %player.infos.player_num, "player kicked before the start");
+
+        let json = PlayerLeaving::json_string(&player.pseudo, self.player_vec()).unwrap();
+        self.broadcast_all(&json);
+    }
+
+    // smallest player number not taken, the players are kept ordered by number
+    fn free_player_num(&self) -> u8 {
+        (1..)
+            .find(|num: &u8| {
+                let num = num.to_string();
+                self.players.iter().all(|p| p.infos.player_num != num)
+            })
+            .unwrap()
+    }
+
+    fn add_player(&mut self, player: GamePlayer) {
+        self.players.push(player);
+        self.players
+            .sort_by(|a, b| a.infos.player_num.cmp(&b.infos.player_num));
+        self.info.player_count += 1;
+    }
+
+    // the host left the game for good: he can host another one, and the game is left without
+    // host (removing it must not touch the hosting flag of his next game)
+    fn release_host(&mut self) {
+        let host_player = std::mem::take(&mut self.info.host_player);
+        let released = self
+            .store
+            .player_infos(&host_player)
+            .and_then(|infos| match infos {
+                Some(mut infos) => {
+                    infos.hosting = 0;
+                    self.store.set_player_infos(&host_player, &infos)
+                }
+                None => Ok(()),
+            });
+        if let Err(e) = released {
+            warn!("can't release host: {e}");
+        }
+    }
+
+    fn record_results(&mut self, winner: &str) -> anyhow::Result<()> {
+        let mut results = vec![];
+        for player in &self.players {
+            // anonymous players have no session
+            let registered = player.infos.bot.is_none()
+                && self
+                    .store
+                    .player_infos(&player.token)?
+                    .is_some_and(|infos| infos.session_expiry.is_some());
+            results.push(GameResult {
+                pseudo: player.pseudo.clone(),
+                registered,
+                character: player.infos.character.clone(),
+                won: winner.contains(&player.infos.player_num),
+                damage_dealt: player.infos.damage_dealt,
+                kills: player.infos.kills,
+            });
+        }
+        record_game(self.store.as_mut(), &results)
+    }
+
+    // write the signed result record of the game, if the server keeps them
+    fn sign_results(&mut self, winner: String) {
+        let signer = match &self.state.results {
+            Some(s) => s,
+            None => return,
+        };
+        let actions = match self.store.actions(&self.token) {
+            Ok(actions) => actions,
+            Err(e) => {
+                warn!("can't read the actions of the game: {e}");
+                return;
+            }
+        };
+        let record = ResultRecord {
+            game_token: self.token.clone(),
+            finished_at: unix_time(),
+            players: self
+                .players
+                .iter()
+                .map(|p| {
+                    let infos = &p.infos;
+                    [
+                        infos.player_num.clone(),
+                        p.pseudo.clone(),
+                        infos.character.clone(),
+                    ]
+                })
+                .collect(),
+            teams: self.info.teams.clone(),
+            winner,
+            chain: match &*self.info.chain {
+                "" => chain_start(&self.token),
+                chain => chain.into(),
+            },
+            actions,
+        };
+        match signer.save(record) {
+            Ok(path) => info!("signed results written to {}", path.display()),
+            Err(e) => warn!("can't write signed results: {e}"),
+        }
+    }
+
+    // add an action to the replay log, before the state version changes
+    fn log(&mut self, player_num: &str, kind: ActionKind, target: Option<[i16; 2]>) {
+        self.append(LoggedAction::new(
+            self.info.turn_count,
+            player_num,
+            kind,
+            target,
+        ));
+    }
+
+    // chain an entry to the last one and add it to the replay log
+    fn append(&mut self, action: LoggedAction) {
+        let previous = match &*self.info.chain {
+            "" => chain_start(&self.token),
+            chain => chain.into(),
+        };
+        let action = chain_action(&previous, action);
+        if let Err(e) = self.store.append_action(&self.token, &action) {
+            warn!("can't log action: {e}");
+        }
+        self.info.chain = action.hash;
+    }
+
+    // player action (skipped turns don't count), see sweeper
+    fn touch(&mut self) {
+        self.info.last_activity = unix_time();
+    }
+
+    // save a snapshot of the game to the storage
+    fn persist(&mut self) {
+        self.info.map = self.map.to_string();
+        let saved = self
+            .store
+            .set_game_info(&self.token, &self.info)
+            .and_then(|_| {
+                self.players
+                    .iter()
+                    .try_for_each(|p| self.store.set_game_player(&self.token, &p.token, &p.infos))
+            });
+        if let Err(e) = saved {
+            warn!("can't save game: {e}");
+        }
+    }
+
+    // map seen by a player: the tiles around his character in fog of war games, the whole
+    // map for the spectators (None) and the dead players
+    fn view(&self, map: &GameMap, player: Option<&GamePlayer>) -> GameMap {
+        let position = player
+            .filter(|_| self.info.fog_of_war)
+            .and_then(|p| p.infos.player_num.chars().next())
+            .and_then(|num| map.find(num));
+        match position {
+            Some(position) => map.view(position, self.state.config.vision_radius),
+            None => map.clone(),
+        }
+    }
+
+    fn map_view(&self, player: Option<&GamePlayer>) -> String {
+        self.view(&self.map, player).to_string()
+    }
+
+    // tiles of the map seen by the player that changed since the map was before
+    fn view_changes(&self, before: &GameMap, player: Option<&GamePlayer>) -> Vec<TileChange> {
+        self.view(before, player)
+            .changes(&self.view(&self.map, player))
+    }
+
+    // send packet to every connected player except the one who triggered it, and to the
+    // spectators
+    fn broadcast(&self, from_player: &str, json: &str) {
+        for player in &self.players {
+            if player.token != from_player {
+                Self::send(player, json);
+            }
+        }
+        self.send_spectators(json);
+    }
+
+    fn broadcast_all(&self, json: &str) {
+        for player in &self.players {
+            Self::send(player, json);
+        }
+        self.send_spectators(json);
+    }
+
+    // same as broadcast, with a packet built for each of them (None for the spectators)
+    fn broadcast_views(
+        &self,
+        from_player: Option<&str>,
+        packet: &dyn Fn(Option<&GamePlayer>) -> String,
+    ) {
+        for player in &self.players {
+            if Some(player.token.as_str()) != from_player && player.sender.is_some() {
+                Self::send(player, &packet(Some(player)));
+            }
+        }
+        if !self.spectators.is_empty() {
+            self.send_spectators(&packet(None));
+        }
+    }
+
+    fn send(player: &GamePlayer, json: &str) {
+        if let Some(sender) = &player.sender {
+            // a closed channel means the player disconnected, a full one that he doesn't
+            // read his packets: the game doesn't wait for him
+            if sender.try_send(json.into()).is_err() && !sender.is_closed() {
+                warn!(player_num = %player.infos.player_num, "packet dropped, player too slow");
+            }
+        }
+    }
+
+    fn send_spectators(&self, json: &str) {
+        for (_, sender) in &self.spectators {
+            if sender.try_send(json.into()).is_err() && !sender.is_closed() {
+                warn!("packet dropped, spectator too slow");
+            }
+        }
+    }
+
+    fn spectate(
+        &mut self,
+        player_token: String,
+        pseudo: &str,
+        ip: IpAddr,
+        sender: Sender<String>,
+    ) -> Reply {
+        // the players would see through the fog of war from another connection, or with
+        // another player created for it
+        let participant = self.players.iter().any(|p| {
+            p.token == player_token
+                || self.info.fog_of_war && (p.pseudo == pseudo || p.ip == Some(ip))
+        });
+        if participant {
+            return Err((ERR_WRONG_STATE, WRONG_STATE_SIZE));
+        }
+        // the spectators who left
+        self.spectators.retain(|(_, s)| !s.is_closed());
+        self.spectators.push((player_token, sender.clone()));
+        info!(spectators = self.spectators.len(), "spectator joined");
+
+        let json = Spectating::json_string(self.player_vec(), self.info.started).unwrap();
+        if self.info.started {
+            let map = self.map.to_string();
+            let json = GameStarting::json_string(self.current_player(), map, self.info.turn_count);
+            let _ = sender.try_send(json.unwrap());
+        }
+        Ok(json)
+    }
+
+    fn snapshot(&self, player_token: &str) -> Reply {
+        let player = self.player(player_token);
+        let spectating = self
+            .spectators
+            .iter()
+            .any(|(token, _)| token == player_token);
+        if player.is_none() && !spectating {
+            return Err((ERR_GM_NOT_JOIN, GM_NOT_JOIN_SIZE));
+        }
+        if !self.info.started {
+            return Err((ERR_GM_NOT_START, GM_NOT_START_SIZE));
+        }
+
+        let map = self.map_view(player);
+        Ok(Snapshot::json_string(self.current_player(), map, self.info.turn_count).unwrap())
+    }
+}
@@ -0,0 +1,1178 @@
+use crate::account::{hash_password, valid_password, verify_password};
+use crate::bot::Difficulty;
+use crate::game::{
+    bot_pseudo, spawn_game, GameCommand, GameHandle, GamePlayer, Reply, TEAM_GAME_PLAYERS,
+};
+use crate::metrics::{request_name, METRICS};
+use crate::rate_limit::TokenBucket;
+use crate::response::packet_sizes::*;
+use crate::response::*;
+use crate::server::{Channel, State};
+use crate::storage::{new_game_player, unix_time, Account, GameInfo, PlayerInfos, Storage};
+use crate::stream::*;
+use net_utils::encoding::{Encoding, ENCODINGS, SUPPORTED_VERSIONS};
+use net_utils::map::{GameDataType, Point};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use rand::prelude::SliceRandom;
+use rand::{thread_rng, Rng};
+use serde_json::Value;
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{oneshot, watch};
+use tokio::task::spawn_blocking;
+use tracing::{debug, field, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+//todo: limit number of trees, rocks and water pools
+fn generate_random_map(height: u8, width: u8) -> String {
+    let mut rng = thread_rng();
+    let mut obstacle_cnt = 0;
+    let mut map = "0".repeat(width as usize);
+    map.push('\n');
+    for _ in 0..(height - 2) {
+        for _ in 0..width {
+            let o = ['0', 'R', 'W', 'T'][rng.gen_range(0..=3)];
+            map.push(if obstacle_cnt != width {
+                if o != '0' {
+                    obstacle_cnt += 1;
+                }
+                o
+            } else {
+                '0'
+            });
+        }
+        map.push('\n');
+        obstacle_cnt = 0;
+    }
+    map.push_str(&"0".repeat(width as usize));
+    map
+
+    /*
+    (0..height * width)
+        .enumerate()
+        .map(|(i, _)| {
+            if i < width as usize || i >= ((height - 1) * width) as usize {
+                '0'
+            } else {
+                // nothing, rock, water, tree
+                ['0', 'R', 'W', 'T'][rng.gen_range(0..=3)]
+            }
+        })
+        .collect()
+     */
+}
+
+// player token of the request if it is valid, the error is sent otherwise
+async fn verify_player_token<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: &Value,
+) -> io::Result<Option<String>> {
+    let player_token = match json_req["player_token"].as_str() {
+        Some(p_token) => {
+            let valid = p_token.len() == 36
+                && store
+                    .player_infos(p_token)
+                    .unwrap()
+                    .is_some_and(|infos| !infos.session_expired(unix_time()));
+            if !valid {
+                write_packet_from_code(stream, ERR_INV_PL_TOK, INV_PL_TOK_SIZE).await?;
+                return Ok(None);
+            }
+            p_token
+        }
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(player_token.to_owned()))
+}
+
+// infos of a player whose token was checked, the error is sent if the player was removed
+// since or if the storage failed
+async fn player_infos<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    player_token: &str,
+) -> io::Result<Option<PlayerInfos>> {
+    match store.player_infos(player_token) {
+        Ok(Some(infos)) => Ok(Some(infos)),
+        Ok(None) => {
+            write_packet_from_code(stream, ERR_INV_PL_TOK, INV_PL_TOK_SIZE).await?;
+            Ok(None)
+        }
+        Err(e) => {
+            warn!("can't read player: {e}");
+            write_packet_from_code(stream, ERR_INTERNAL_SERV, INTERNAL_SERV_SIZE).await?;
+            Ok(None)
+        }
+    }
+}
+
+// game token of the request if the game exists, the error is sent otherwise
+async fn verify_game_token<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: &Value,
+) -> io::Result<Option<String>> {
+    let game_token = match json_req["game_token"].as_str() {
+        Some(g_token) => {
+            if g_token.len() != 36 || !store.game_exists(g_token).unwrap() {
+                write_packet_from_code(stream, ERR_INV_GM_TOK, INV_GM_TOK_SIZE).await?;
+                return Ok(None);
+            }
+            g_token
+        }
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(None);
+        }
+    };
+
+    Ok(Some(game_token.to_owned()))
+}
+
+// pseudo of the request if it is valid, the error is sent otherwise
+async fn verify_pseudo<'a, S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    json_req: &'a Value,
+) -> io::Result<Option<&'a str>> {
+    match json_req["pseudo"].as_str() {
+        Some(p) => {
+            if p.is_empty() || p.len() > 32 || !p.chars().all(char::is_alphanumeric) {
+                write_packet_from_code(stream, ERR_INV_PSEUD, INV_PSEUD_SIZE).await?;
+                return Ok(None);
+            }
+            Ok(Some(p))
+        }
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            Ok(None)
+        }
+    }
+}
+
+// true if the player was created
+async fn player_creation<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<bool> {
+    let pseudo = match verify_pseudo(stream, &json_req).await? {
+        Some(p) => p,
+        None => return Ok(false),
+    };
+    // registered pseudos are reserved to their owner
+    if store.account(pseudo).unwrap().is_some() {
+        write_packet_from_code(stream, ERR_PSEUD_TAKEN, PSEUD_TAKEN_SIZE).await?;
+        return Ok(false);
+    }
+
+    let player_infos = PlayerInfos {
+        pseudo: pseudo.into(),
+        hosting: 0,
+        last_activity: unix_time(),
+        session_expiry: None,
+    };
+    loop {
+        let player_token = Uuid::new_v4().to_string();
+        if store.insert_player(&player_token, &player_infos).unwrap() {
+            let json = PlayerCreation::json_string(&player_token).unwrap();
+            write_packet_from_json(stream, &json).await?;
+            info!(player = %player_token, "player created");
+            //todo: wait for client response (to avoid inserting player in database if write_packet_from_json fails)
+            return Ok(true);
+        }
+    }
+}
+
+// pseudo and password of a REGISTER or LOGIN request, the error is sent if they are invalid
+async fn verify_credentials<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    json_req: &Value,
+) -> io::Result<Option<(String, String)>> {
+    let pseudo = match verify_pseudo(stream, json_req).await? {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    let password = match json_req["password"].as_str() {
+        Some(p) => p,
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(None);
+        }
+    };
+    if !valid_password(password) {
+        write_packet_from_code(stream, ERR_INV_PASSWD, INV_PASSWD_SIZE).await?;
+        return Ok(None);
+    }
+    Ok(Some((pseudo.into(), password.into())))
+}
+
+// new player token for a registered player, valid for session_ttl seconds
+async fn open_session<S: AsyncStream>(
+    state: &State,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    pseudo: String,
+    status: u64,
+) -> io::Result<()> {
+    let now = unix_time();
+    let expiry = now + state.expiry.session_ttl;
+    let player_infos = PlayerInfos {
+        pseudo,
+        hosting: 0,
+        last_activity: now,
+        session_expiry: Some(expiry),
+    };
+    loop {
+        let player_token = Uuid::new_v4().to_string();
+        if store.insert_player(&player_token, &player_infos).unwrap() {
+            let json = Session::json_string(status, &player_token, expiry).unwrap();
+            write_packet_from_json(stream, &json).await?;
+            info!(player = %player_token, pseudo = %player_infos.pseudo, "session opened");
+            return Ok(());
+        }
+    }
+}
+
+// true if the account was created (the player is logged in)
+async fn registration<S: AsyncStream>(
+    state: &State,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<bool> {
+    let (pseudo, password) = match verify_credentials(stream, &json_req).await? {
+        Some(c) => c,
+        None => return Ok(false),
+    };
+    // checked before hashing, insert_account still refuses a pseudo registered meanwhile
+    if store.account(&pseudo).unwrap().is_some() {
+        write_packet_from_code(stream, ERR_PSEUD_TAKEN, PSEUD_TAKEN_SIZE).await?;
+        return Ok(false);
+    }
+
+    let password_hash = spawn_blocking(move || hash_password(&password))
+        .await
+        .unwrap()
+        .unwrap();
+    let account = Account {
+        pseudo: pseudo.clone(),
+        password_hash,
+        created: unix_time(),
+    };
+    if !store.insert_account(&account).unwrap() {
+        write_packet_from_code(stream, ERR_PSEUD_TAKEN, PSEUD_TAKEN_SIZE).await?;
+        return Ok(false);
+    }
+    info!(pseudo = %pseudo, "account registered");
+
+    open_session(state, stream, store, pseudo, OK_REGISTER).await?;
+    Ok(true)
+}
+
+// true if the player is logged in
+async fn login<S: AsyncStream>(
+    state: &State,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<bool> {
+    let (pseudo, password) = match verify_credentials(stream, &json_req).await? {
+        Some(c) => c,
+        None => return Ok(false),
+    };
+
+    let account = store.account(&pseudo).unwrap();
+    let password_hash = account.as_ref().map(|a| a.password_hash.clone());
+    let valid = spawn_blocking(move || match password_hash {
+        Some(hash) => verify_password(&password, &hash),
+        // as slow as a wrong password, unknown pseudos can't be told apart
+        None => {
+            let _ = hash_password(&password);
+            false
+        }
+    })
+    .await
+    .unwrap();
+
+    match account {
+        Some(account) if valid => {
+            // pseudo as registered
+            open_session(state, stream, store, account.pseudo, OK_LOGIN).await?;
+            Ok(true)
+        }
+        _ => {
+            write_packet_from_code(stream, ERR_INV_LOGIN, INV_LOGIN_SIZE).await?;
+            Ok(false)
+        }
+    }
+}
+
+// players in the leaderboard when the request doesn't say
+const DEFAULT_LEADERBOARD_SIZE: u64 = 10;
+const MAX_LEADERBOARD_SIZE: u64 = 100;
+
+async fn leaderboard<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: &Value,
+) -> io::Result<()> {
+    let count = match &json_req["count"] {
+        Value::Null => DEFAULT_LEADERBOARD_SIZE,
+        count => match count.as_u64() {
+            Some(c) => c.min(MAX_LEADERBOARD_SIZE),
+            None => {
+                write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+                return Ok(());
+            }
+        },
+    };
+    let players = store.leaderboard(count as usize).unwrap();
+    write_packet_from_json(stream, &Leaderboard::json_string(players).unwrap()).await
+}
+
+async fn player_stats<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: &Value,
+) -> io::Result<()> {
+    let pseudo = match verify_pseudo(stream, json_req).await? {
+        Some(p) => p,
+        None => return Ok(()),
+    };
+    match store.player_stats(pseudo).unwrap() {
+        Some(stats) => write_packet_from_json(stream, &Stats::json_string(stats).unwrap()).await,
+        None => write_packet_from_code(stream, ERR_NO_STATS, NO_STATS_SIZE).await,
+    }
+}
+
+// version and encoding asked by a HELLO request (json when none is given), the error status
+// otherwise
+fn requested_protocol<S: AsyncStream>(
+    stream: &PacketStream<S>,
+    json_req: &Value,
+) -> Result<(u64, Encoding), (u64, u16)> {
+    let encoding = match &json_req["encoding"] {
+        Value::Null => Some(Encoding::Json.name()),
+        encoding => encoding.as_str(),
+    };
+    let (version, encoding) = match (json_req["version"].as_u64(), encoding) {
+        (Some(v), Some(e)) => (v, Encoding::from_name(e)),
+        _ => return Err((ERR_MAL_REQ, MAL_REQ_SIZE)),
+    };
+    match encoding {
+        Some(e) if SUPPORTED_VERSIONS.contains(&version) && stream.can_use(e) => Ok((version, e)),
+        _ => Err((ERR_UNSUP_PROTO, UNSUP_PROTO_SIZE)),
+    }
+}
+
+// encodings the client can ask for on this connection
+fn usable_encodings<S: AsyncStream>(stream: &PacketStream<S>) -> Vec<&'static str> {
+    ENCODINGS
+        .into_iter()
+        .filter(|e| stream.can_use(*e))
+        .map(Encoding::name)
+        .collect()
+}
+
+// longest client name accepted in a HELLO request
+const MAX_CLIENT_NAME_LEN: usize = 64;
+
+// handshake, the client learns what the server supports and incompatible clients are
+// disconnected with the versions and encodings they could have used
+async fn hello<S: AsyncStream>(stream: &mut PacketStream<S>, json_req: &Value) -> io::Result<Flow> {
+    let client = match json_req["client"].as_str() {
+        Some(c) if !c.is_empty() && c.len() <= MAX_CLIENT_NAME_LEN => c,
+        _ => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(Flow::Continue);
+        }
+    };
+
+    match requested_protocol(stream, json_req) {
+        Ok((version, encoding)) => {
+            info!(client, version, encoding = encoding.name(), "hello");
+            let json =
+                Hello::json_string(version, encoding.name(), usable_encodings(stream)).unwrap();
+            write_packet_from_json(stream, &json).await?;
+            stream.set_encoding(encoding);
+            Ok(Flow::Continue)
+        }
+        Err((ERR_UNSUP_PROTO, _)) => {
+            info!(client, version = %json_req["version"], "incompatible client refused");
+            METRICS.error_sent(ERR_UNSUP_PROTO);
+            let json = Refused::json_string(usable_encodings(stream)).unwrap();
+            write_packet_from_json(stream, &json).await?;
+            Ok(Flow::Close)
+        }
+        Err((code, size)) => {
+            write_packet_from_code(stream, code, size).await?;
+            Ok(Flow::Continue)
+        }
+    }
+}
+
+// teams chosen by the host, e.g. ["13", "24"]: two teams of two of the four player numbers
+// (in joining order), empty without the option and None if invalid
+fn parse_teams(teams: &Value) -> Option<Vec<String>> {
+    let teams = match teams {
+        Value::Null => return Some(vec![]),
+        Value::Array(teams) => teams,
+        _ => return None,
+    };
+    // "31" is stored as "13"
+    let teams: Vec<String> = teams
+        .iter()
+        .map(|team| {
+            let mut players: Vec<char> = team.as_str()?.chars().collect();
+            players.sort();
+            Some(String::from_iter(players))
+        })
+        .collect::<Option<_>>()?;
+    let mut players: Vec<char> = teams.iter().flat_map(|team| team.chars()).collect();
+    players.sort();
+    let valid = teams.len() == 2
+        && teams.iter().all(|team| team.len() == 2)
+        && players == ['1', '2', '3', '4'];
+    valid.then_some(teams)
+}
+
+// the teams play in turn, in a random order
+fn team_turn(teams: &[String]) -> String {
+    let mut rng = thread_rng();
+    let mut teams: Vec<Vec<char>> = teams.iter().map(|team| team.chars().collect()).collect();
+    teams.shuffle(&mut rng);
+    for team in &mut teams {
+        team.shuffle(&mut rng);
+    }
+    (0..2).flat_map(|i| [teams[0][i], teams[1][i]]).collect()
+}
+
+async fn game_creation<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+    ip: IpAddr,
+) -> io::Result<Option<Channel>> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(None),
+    };
+
+    let player_infos = match player_infos(stream, store, &player_token).await? {
+        Some(infos) => infos,
+        None => return Ok(None),
+    };
+    // one game hosted at a time
+    if player_infos.hosting == 1 {
+        write_packet_from_code(stream, ERR_WRONG_STATE, WRONG_STATE_SIZE).await?;
+        return Ok(None);
+    }
+
+    if store.game_count().unwrap() >= state.config.max_games {
+        write_packet_from_code(stream, ERR_SERV_FULL, SERV_FULL_SIZE).await?;
+        return Ok(None);
+    }
+
+    let fog_of_war = match &json_req["fog_of_war"] {
+        Value::Null => false,
+        Value::Bool(f) => *f,
+        _ => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(None);
+        }
+    };
+
+    let teams = match parse_teams(&json_req["teams"]) {
+        Some(teams) => teams,
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(None);
+        }
+    };
+
+    // team games are always 2v2
+    let (max_players, turn) = match teams.is_empty() {
+        true => {
+            let max_players = state.config.max_players;
+            let mut turn_vec: Vec<char> = (1..=max_players).map(|n| char::from(b'0' + n)).collect();
+            turn_vec.shuffle(&mut thread_rng());
+            (max_players, String::from_iter(turn_vec))
+        }
+        false => (TEAM_GAME_PLAYERS, team_turn(&teams)),
+    };
+    let game_info = GameInfo {
+        started: false,
+        host_player: player_token,
+        player_count: 1,
+        max_players,
+        map: generate_random_map(state.config.map_height, state.config.map_width),
+        turn,
+        turn_count: 0,
+        last_activity: unix_time(),
+        fog_of_war,
+        teams,
+        chain: String::new(),
+    };
+
+    let mut game_token;
+    loop {
+        game_token = Uuid::new_v4().to_string();
+        if store
+            .create_game(&game_token, &game_info, &player_infos)
+            .unwrap()
+        {
+            break;
+        }
+        // game token already exists (very rare but still a possibility)
+    }
+
+    let channel: Channel = async_channel::bounded(state.config.channel_capacity);
+    let host = GamePlayer {
+        token: game_info.host_player.clone(),
+        pseudo: player_infos.pseudo,
+        infos: new_game_player(1),
+        sender: Some(channel.0.clone()),
+        ip: Some(ip),
+    };
+    let game = spawn_game(state, game_token.clone(), game_info, vec![host]).unwrap();
+    // the game can be joined as soon as its token is known
+    state.state.lock().await.insert(game_token.clone(), game);
+
+    let json = GameCreation::json_string(&game_token).unwrap();
+    write_packet_from_json(stream, &json).await?;
+    info!(game = %game_token, "game created");
+    Ok(Some(channel))
+}
+
+// handle of the task running the game, ERR_INV_GM_TOK if the game is over
+async fn game_handle<S: AsyncStream>(
+    state: &State,
+    stream: &mut PacketStream<S>,
+    game_token: &str,
+) -> io::Result<Option<GameHandle>> {
+    let game = state.state.lock().await.get(game_token).cloned();
+    if game.is_none() {
+        write_packet_from_code(stream, ERR_INV_GM_TOK, INV_GM_TOK_SIZE).await?;
+    }
+    Ok(game)
+}
+
+// send a command to the game task and write its reply
+async fn game_request_reply<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    game: &GameHandle,
+    command: impl FnOnce(oneshot::Sender<Reply>) -> GameCommand,
+) -> io::Result<bool> {
+    match game.request(command).await {
+        Some(Ok(json)) => {
+            write_packet_from_json(stream, &json).await?;
+            Ok(true)
+        }
+        Some(Err((code, size))) => {
+            write_packet_from_code(stream, code, size).await?;
+            Ok(false)
+        }
+        // the game just ended
+        None => {
+            write_packet_from_code(stream, ERR_INV_GM_TOK, INV_GM_TOK_SIZE).await?;
+            Ok(false)
+        }
+    }
+}
+
+async fn game_joining<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+    ip: IpAddr,
+) -> io::Result<Option<Channel>> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(None),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(None),
+    };
+
+    let game = match game_handle(state, stream, &game_token).await? {
+        Some(game) => game,
+        None => return Ok(None),
+    };
+    let pseudo = match player_infos(stream, store, &player_token).await? {
+        Some(infos) => infos.pseudo,
+        None => return Ok(None),
+    };
+    let channel: Channel = async_channel::bounded(state.config.channel_capacity);
+    let sender = channel.0.clone();
+    // a player already in the game reconnects to it (after a disconnection or a server restart)
+    let joined = game_request_reply(stream, &game, |reply| GameCommand::Join {
+        player_token,
+        pseudo,
+        ip,
+        sender,
+        reply,
+    })
+    .await?;
+
+    Ok(joined.then_some(channel))
+}
+
+// the connection receives the packets of the game (with the full map) without playing
+async fn game_spectating<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+    ip: IpAddr,
+) -> io::Result<Option<Channel>> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(None),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(None),
+    };
+
+    let game = match game_handle(state, stream, &game_token).await? {
+        Some(game) => game,
+        None => return Ok(None),
+    };
+    let pseudo = match player_infos(stream, store, &player_token).await? {
+        Some(infos) => infos.pseudo,
+        None => return Ok(None),
+    };
+    let channel: Channel = async_channel::bounded(state.config.channel_capacity);
+    let sender = channel.0.clone();
+    let spectating = game_request_reply(stream, &game, |reply| GameCommand::Spectate {
+        player_token,
+        pseudo,
+        ip,
+        sender,
+        reply,
+    })
+    .await?;
+
+    Ok(spectating.then_some(channel))
+}
+
+async fn character_choosing<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<()> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(()),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(()),
+    };
+
+    let character = match json_req["character"].as_str() {
+        Some(c) => c.to_string(),
+        None => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(game) = game_handle(state, stream, &game_token).await? {
+        game_request_reply(stream, &game, |reply| GameCommand::ChooseCharacter {
+            player_token,
+            character,
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+async fn game_starting<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<()> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(()),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(()),
+    };
+
+    if let Some(game) = game_handle(state, stream, &game_token).await? {
+        game_request_reply(stream, &game, |reply| GameCommand::Start {
+            player_token,
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+// map seen by the player (the full map for the spectators) with the state version
+async fn game_snapshot<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<()> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(()),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(()),
+    };
+
+    if let Some(game) = game_handle(state, stream, &game_token).await? {
+        game_request_reply(stream, &game, |reply| GameCommand::Snapshot {
+            player_token,
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+async fn bot_adding<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<()> {
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(()),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(()),
+    };
+
+    // normal bot by default
+    let difficulty = match json_req["difficulty"].as_str().map(Difficulty::new) {
+        None => Difficulty::Normal,
+        Some(Some(d)) => d,
+        Some(None) => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(game) = game_handle(state, stream, &game_token).await? {
+        game_request_reply(stream, &game, |reply| GameCommand::AddBot {
+            player_token,
+            difficulty,
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+fn parse_target(json_req: &Value) -> Option<Point> {
+    let target = json_req["target"].as_array()?;
+    Some(Point(
+        target.first()?.as_i64()? as i16,
+        target.get(1)?.as_i64()? as i16,
+    ))
+}
+
+async fn game_data_parsing<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json_req: Value,
+) -> io::Result<()> {
+    let action = match (json_req["gm_code"].as_u64(), parse_target(&json_req)) {
+        (Some(GM_DATA_MOV), Some(target)) => GameDataType::Movement(target),
+        (Some(GM_DATA_ATK), Some(target)) => GameDataType::Attack(target),
+        (Some(GM_DATA_SKIP), _) => GameDataType::Skip,
+        _ => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(());
+        }
+    };
+
+    let player_token = match verify_player_token(stream, store, &json_req).await? {
+        Some(p_token) => p_token,
+        None => return Ok(()),
+    };
+
+    let game_token = match verify_game_token(stream, store, &json_req).await? {
+        Some(g_token) => g_token,
+        None => return Ok(()),
+    };
+
+    // the game task applies the actions one at a time: concurrent requests
+    // can't play the same turn twice
+    if let Some(game) = game_handle(state, stream, &game_token).await? {
+        game_request_reply(stream, &game, |reply| GameCommand::Play {
+            player_token,
+            action,
+            reply,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+// start a task for every game found in the storage (after a restart)
+pub fn resume_games(state: &Arc<State>) -> anyhow::Result<usize> {
+    let mut store = state.storage.get_connection()?;
+    let mut resumed = 0;
+    for game_token in store.games()? {
+        let game_info = match store.game_info(&game_token)? {
+            Some(g) => g,
+            None => continue,
+        };
+        let mut players = vec![];
+        for (player_token, infos) in store.game_players(&game_token)? {
+            let pseudo = match (infos.bot, store.player_infos(&player_token)?) {
+                (Some(_), _) => bot_pseudo(&infos.player_num),
+                (None, Some(p)) => p.pseudo,
+                (None, None) => continue,
+            };
+            players.push(GamePlayer {
+                token: player_token,
+                pseudo,
+                infos,
+                sender: None,
+                ip: None,
+            });
+        }
+
+        let game = spawn_game(state, game_token.clone(), game_info, players)?;
+        state.state.try_lock().unwrap().insert(game_token, game);
+        resumed += 1;
+    }
+    Ok(resumed)
+}
+
+// keep the player alive as long as he makes requests (see sweeper)
+fn touch_player(store: &mut dyn Storage, json_req: &Value) {
+    if let Some(player_token) = json_req["player_token"].as_str() {
+        store.touch_player(player_token, unix_time()).unwrap();
+    }
+}
+
+// per connection and per ip throttling, the client is told to slow down when a bucket is empty
+async fn request_allowed<S: AsyncStream>(
+    state: &State,
+    stream: &mut PacketStream<S>,
+    bucket: &mut TokenBucket,
+    ip: IpAddr,
+) -> io::Result<bool> {
+    if bucket.try_take() && state.ip_limiter.try_take(ip) {
+        return Ok(true);
+    }
+
+    write_packet_from_code(stream, ERR_THROTTLED, THROTTLED_SIZE).await?;
+    Ok(false)
+}
+
+// resolves once the server is shutting down
+async fn shutdown_requested(shutdown: &mut watch::Receiver<bool>) {
+    // an error means the state is gone, the server is stopping anyway
+    let _ = shutdown.wait_for(|s| *s).await;
+}
+
+// what the connection loop does once a request is handled
+enum Flow {
+    Continue,
+    Joined(Channel),
+    Close,
+}
+
+// span of a request, with the player and game it concerns when given
+fn request_span(json_req: &Value) -> Span {
+    let span = info_span!(
+        "request",
+        kind = request_name(json_req["request_type"].as_u64()),
+        player = field::Empty,
+        game = field::Empty,
+    );
+    if let Some(player_token) = json_req["player_token"].as_str() {
+        span.record("player", player_token);
+    }
+    if let Some(game_token) = json_req["game_token"].as_str() {
+        span.record("game", game_token);
+    }
+    span
+}
+
+// requests accepted before the player is in a game
+async fn lobby_request<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json: Value,
+    ip: IpAddr,
+    players_created: &mut u32,
+) -> io::Result<Flow> {
+    let flow = match json["request_type"].as_u64() {
+        Some(PL_CREAT) => {
+            if *players_created >= state.limits.max_players_per_connection {
+                write_packet_from_code(stream, ERR_PL_LIMIT, PL_LIMIT_SIZE).await?;
+            } else if player_creation(stream, store, json).await? {
+                *players_created += 1;
+            }
+            Flow::Continue
+        }
+        Some(REGISTER | LOGIN) => {
+            if *players_created >= state.limits.max_players_per_connection {
+                write_packet_from_code(stream, ERR_PL_LIMIT, PL_LIMIT_SIZE).await?;
+            } else {
+                let logged_in = if json["request_type"] == REGISTER {
+                    registration(state, stream, store, json).await?
+                } else {
+                    login(state, stream, store, json).await?
+                };
+                if logged_in {
+                    *players_created += 1;
+                }
+            }
+            Flow::Continue
+        }
+        Some(LEADERBOARD) => {
+            leaderboard(stream, store, &json).await?;
+            Flow::Continue
+        }
+        Some(PL_STATS) => {
+            player_stats(stream, store, &json).await?;
+            Flow::Continue
+        }
+        // replaced by HELLO, the connection stays in json
+        Some(PROTO_NEG) => {
+            write_packet_from_code(stream, ERR_UNSUP_PROTO, UNSUP_PROTO_SIZE).await?;
+            Flow::Continue
+        }
+        Some(HELLO) => hello(stream, &json).await?,
+        // the player has to create, join or spectate a game first
+        Some(GM_DATA | CHAR_CHOOSING | GM_START | ADD_BOT | GM_SNAPSHOT) => {
+            write_packet_from_code(stream, ERR_WRONG_STATE, WRONG_STATE_SIZE).await?;
+            Flow::Continue
+        }
+        Some(GM_CREAT) => match game_creation(state, stream, store, json, ip).await? {
+            Some(c) => Flow::Joined(c),
+            None => Flow::Continue,
+        },
+        Some(GM_JOIN) => match game_joining(state, stream, store, json, ip).await? {
+            Some(c) => Flow::Joined(c),
+            None => Flow::Continue,
+        },
+        Some(GM_SPECTATE) => match game_spectating(state, stream, store, json, ip).await? {
+            Some(c) => Flow::Joined(c),
+            None => Flow::Continue,
+        },
+        Some(TERM_CON) => {
+            write_packet_from_code(stream, OK_TERM_CON, TERM_CON_SIZE).await?;
+            Flow::Close
+        }
+        _ => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            Flow::Close
+        }
+    };
+    Ok(flow)
+}
+
+// requests accepted once the player created or joined a game
+async fn game_request<S: AsyncStream>(
+    state: &Arc<State>,
+    stream: &mut PacketStream<S>,
+    store: &mut dyn Storage,
+    json: Value,
+) -> io::Result<Flow> {
+    match json["request_type"].as_u64() {
+        Some(GM_DATA) => game_data_parsing(state, stream, store, json).await?,
+        Some(CHAR_CHOOSING) => character_choosing(state, stream, store, json).await?,
+        Some(GM_START) => game_starting(state, stream, store, json).await?,
+        Some(ADD_BOT) => bot_adding(state, stream, store, json).await?,
+        Some(GM_SNAPSHOT) => game_snapshot(state, stream, store, json).await?,
+        Some(LEADERBOARD) => leaderboard(stream, store, &json).await?,
+        Some(PL_STATS) => player_stats(stream, store, &json).await?,
+        Some(PROTO_NEG) => {
+            write_packet_from_code(stream, ERR_UNSUP_PROTO, UNSUP_PROTO_SIZE).await?
+        }
+        Some(HELLO) => return hello(stream, &json).await,
+        // one game per connection, back in the lobby once it is over
+        Some(PL_CREAT | REGISTER | LOGIN | GM_CREAT | GM_JOIN | GM_SPECTATE) => {
+            write_packet_from_code(stream, ERR_WRONG_STATE, WRONG_STATE_SIZE).await?
+        }
+        Some(TERM_CON) => {
+            write_packet_from_code(stream, OK_TERM_CON, TERM_CON_SIZE).await?;
+            return Ok(Flow::Close);
+        }
+        _ => {
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+            return Ok(Flow::Close);
+        }
+    }
+    Ok(Flow::Continue)
+}
+
+// last packet sent by the game task to this player
+fn leaves_game(packet: &str) -> bool {
+    serde_json::from_str::<Value>(packet)
+        .is_ok_and(|json| json["status"] == OK_GM_OVER || json["status"] == GM_KICKED)
+}
+
+// the connection ends when the client closes it, or on a packet that can't be read (the
+// client is told when it sent one the server refuses, the next packets can't be found in the
+// stream after it)
+async fn close_on_read_error<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    error: io::Error,
+) -> io::Result<()> {
+    match error.kind() {
+        // between two packets: a normal disconnection (websocket clients always leave this way,
+        // the bridge closes its side when the websocket is closed)
+        ErrorKind::UnexpectedEof if !stream.has_buffered() => debug!("closed by the client"),
+        ErrorKind::InvalidData => {
+            info!("packet refused: {error}");
+            write_packet_from_code(stream, ERR_MAL_REQ, MAL_REQ_SIZE).await?;
+        }
+        _ => info!("connection lost: {error}"),
+    }
+    Ok(())
+}
+
+// a client gone while a packet was written to it ends the connection like a read error
+pub async fn handle_player<S: AsyncStream>(
+    state: Arc<State>,
+    stream: PacketStream<S>,
+    ip: IpAddr,
+    store: &mut dyn Storage,
+) -> anyhow::Result<()> {
+    if let Err(e) = serve_player(state, stream, ip, store).await {
+        info!("connection lost: {e}");
+    }
+    Ok(())
+}
+
+async fn serve_player<S: AsyncStream>(
+    state: Arc<State>,
+    mut stream: PacketStream<S>,
+    ip: IpAddr,
+    store: &mut dyn Storage,
+) -> io::Result<()> {
+    let mut bucket = TokenBucket::new(state.limits.requests_per_second, state.limits.request_burst);
+    let mut players_created = 0;
+    let mut shutdown = state.shutdown.subscribe();
+    let mut notices = state.notices.subscribe();
+
+    // back to the lobby when the game is over
+    loop {
+        // players are created (up to the per connection limit) until a game is created or joined
+        let channel = loop {
+            let json = tokio::select! {
+                json = read_packet(&mut stream) => match json {
+                    Ok(json) => json,
+                    Err(e) => return close_on_read_error(&mut stream, e).await,
+                },
+                Ok(notice) = notices.recv() => {
+                    write_packet_from_json(&mut stream, &notice).await?;
+                    continue;
+                },
+                _ = shutdown_requested(&mut shutdown) => {
+                    return write_packet_from_code(&mut stream, SERV_SHUTDOWN, SERV_SHUTDOWN_SIZE).await;
+                },
+            };
+            if !request_allowed(&state, &mut stream, &mut bucket, ip).await? {
+                continue;
+            }
+            touch_player(store, &json);
+
+            let request_code = json["request_type"].as_u64();
+            let span = request_span(&json);
+            let start = Instant::now();
+            let flow = lobby_request(&state, &mut stream, store, json, ip, &mut players_created)
+                .instrument(span)
+                .await?;
+            METRICS.request_handled(request_code, start.elapsed());
+
+            match flow {
+                Flow::Continue => (),
+                Flow::Joined(c) => break c,
+                Flow::Close => return Ok(()),
+            }
+        };
+
+        loop {
+            tokio::select! {
+                Ok(packet) = channel.1.recv() => {
+                    write_packet_from_json(&mut stream, &packet).await?;
+                    if leaves_game(&packet) {
+                        break;
+                    }
+                },
+
+                Ok(notice) = notices.recv() => {
+                    write_packet_from_json(&mut stream, &notice).await?;
+                },
+
+                json = read_packet(&mut stream) => {
+                    let json = match json {
+                        Ok(json) => json,
+                        Err(e) => return close_on_read_error(&mut stream, e).await,
+                    };
+                    if !request_allowed(&state, &mut stream, &mut bucket, ip).await? {
+                        continue;
+                    }
+                    touch_player(store, &json);
+
+                    let request_code = json["request_type"].as_u64();
+                    let span = request_span(&json);
+                    let start = Instant::now();
+                    let flow = game_request(&state, &mut stream, store, json).instrument(span).await?;
+                    METRICS.request_handled(request_code, start.elapsed());
+
+                    if let Flow::Close = flow {
+                        return Ok(());
+                    }
+                },
+
+                _ = shutdown_requested(&mut shutdown) => {
+                    // the shutdown status is sent through the game channel
+                    while let Ok(packet) = channel.1.try_recv() {
+                        write_packet_from_json(&mut stream, &packet).await?;
+                    }
+                    return Ok(());
+                },
+            }
+        }
+    }
+}
+
+//todo: set ttl for tcp streams
+//todo: try_write() or try_read() to avoid blocking the mutex
+
+//todo: don't respond to certain requests (ex: player starting game but is not the host)
+
+//todo: manage unwraps, panics, bails
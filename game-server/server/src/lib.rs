@@ -1,2 +1,18 @@
+pub mod account;
 pub mod action_check;
+pub mod admin;
+pub mod audit;
+pub mod bot;
+pub mod config;
+pub mod game;
+pub mod handler;
+pub mod metrics;
+pub mod rate_limit;
 pub mod response;
+pub mod server;
+pub mod stats;
+pub mod storage;
+pub mod stream;
+pub mod sweeper;
+pub mod tls;
+pub mod websocket;
@@ -0,0 +1,222 @@
+use crate::server::State;
+use net_utils::packet::request_codes::*;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+// request and error codes are all below this value, 0 is used for unknown codes
+const MAX_CODE: usize = 80;
+// upper bounds of the handler latency histogram buckets (seconds), +Inf is implicit
+const LATENCY_BUCKETS: [f64; 8] = [0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+// process wide counters, updated from every connection task
+pub static METRICS: Metrics = Metrics::new();
+
+pub struct Metrics {
+    connections_total: AtomicU64,
+    connections_active: AtomicU64,
+    requests: [AtomicU64; MAX_CODE],
+    errors: [AtomicU64; MAX_CODE],
+    // per request code: cumulative count of requests under each bucket bound
+    latency_buckets: [[AtomicU64; LATENCY_BUCKETS.len()]; MAX_CODE],
+    latency_sum_us: [AtomicU64; MAX_CODE],
+}
+
+fn code_index(code: Option<u64>) -> usize {
+    match code {
+        Some(c) if (c as usize) < MAX_CODE => c as usize,
+        _ => 0,
+    }
+}
+
+fn code_label(index: usize) -> String {
+    match index {
+        0 => "unknown".into(),
+        i => i.to_string(),
+    }
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            connections_total: AtomicU64::new(0),
+            connections_active: AtomicU64::new(0),
+            requests: [const { AtomicU64::new(0) }; MAX_CODE],
+            errors: [const { AtomicU64::new(0) }; MAX_CODE],
+            latency_buckets: [const { [const { AtomicU64::new(0) }; LATENCY_BUCKETS.len()] };
+                MAX_CODE],
+            latency_sum_us: [const { AtomicU64::new(0) }; MAX_CODE],
+        }
+    }
+
+    pub fn connection_opened(&self) {
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn request_handled(&self, request_code: Option<u64>, latency: Duration) {
+        let i = code_index(request_code);
+        self.requests[i].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_us[i].fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        let secs = latency.as_secs_f64();
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets[i]) {
+            if secs <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn error_sent(&self, status_code: u64) {
+        self.errors[code_index(Some(status_code))].fetch_add(1, Ordering::Relaxed);
+    }
+
+    // prometheus text exposition format
+    pub fn render(&self, active_games: usize) -> String {
+        let mut out = String::new();
+        let connections_total = self.connections_total.load(Ordering::Relaxed);
+        let connections_active = self.connections_active.load(Ordering::Relaxed);
+
+        writeln!(out, "# TYPE game_server_connections_total counter").unwrap();
+        writeln!(out, "game_server_connections_total {connections_total}").unwrap();
+        writeln!(out, "# TYPE game_server_connections_active gauge").unwrap();
+        writeln!(out, "game_server_connections_active {connections_active}").unwrap();
+        writeln!(out, "# TYPE game_server_games_active gauge").unwrap();
+        writeln!(out, "game_server_games_active {active_games}").unwrap();
+
+        writeln!(out, "# TYPE game_server_requests_total counter").unwrap();
+        for (i, count) in self.requests.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                let code = code_label(i);
+                writeln!(out, "game_server_requests_total{{code=\"{code}\"}} {count}").unwrap();
+            }
+        }
+
+        writeln!(out, "# TYPE game_server_errors_total counter").unwrap();
+        for (i, count) in self.errors.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count > 0 {
+                let status = code_label(i);
+                writeln!(
+                    out,
+                    "game_server_errors_total{{status=\"{status}\"}} {count}"
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out, "# TYPE game_server_handler_seconds histogram").unwrap();
+        for (i, count) in self.requests.iter().enumerate() {
+            let count = count.load(Ordering::Relaxed);
+            if count == 0 {
+                continue;
+            }
+            let code = code_label(i);
+            for (bound, bucket) in LATENCY_BUCKETS.iter().zip(&self.latency_buckets[i]) {
+                let bucket = bucket.load(Ordering::Relaxed);
+                writeln!(
+                    out,
+                    "game_server_handler_seconds_bucket{{code=\"{code}\",le=\"{bound}\"}} {bucket}"
+                )
+                .unwrap();
+            }
+            writeln!(
+                out,
+                "game_server_handler_seconds_bucket{{code=\"{code}\",le=\"+Inf\"}} {count}"
+            )
+            .unwrap();
+            let sum = self.latency_sum_us[i].load(Ordering::Relaxed) as f64 / 1_000_000.0;
+            writeln!(
+                out,
+                "game_server_handler_seconds_sum{{code=\"{code}\"}} {sum}"
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "game_server_handler_seconds_count{{code=\"{code}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+// decrements the active connections when the connection task ends (even on panic)
+pub struct ConnectionGuard;
+
+impl ConnectionGuard {
+    pub fn open() -> Self {
+        METRICS.connection_opened();
+        Self
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        METRICS.connection_closed();
+    }
+}
+
+pub fn request_name(request_code: Option<u64>) -> &'static str {
+    match request_code {
+        Some(TERM_CON) => "TERM_CON",
+        Some(PL_CREAT) => "PL_CREAT",
+        Some(GM_CREAT) => "GM_CREAT",
+        Some(GM_JOIN) => "GM_JOIN",
+        Some(CHAR_CHOOSING) => "CHAR_CHOOSING",
+        Some(GM_START) => "GM_START",
+        Some(GM_DATA) => "GM_DATA",
+        Some(REGISTER) => "REGISTER",
+        Some(LOGIN) => "LOGIN",
+        Some(LEADERBOARD) => "LEADERBOARD",
+        Some(PL_STATS) => "PL_STATS",
+        Some(ADD_BOT) => "ADD_BOT",
+        Some(GM_SPECTATE) => "GM_SPECTATE",
+        Some(PROTO_NEG) => "PROTO_NEG",
+        Some(GM_SNAPSHOT) => "GM_SNAPSHOT",
+        Some(HELLO) => "HELLO",
+        _ => "unknown",
+    }
+}
+
+// minimal http server answering GET /metrics, meant to be bound on a local address
+pub async fn serve_metrics(listener: TcpListener, state: Arc<State>) {
+    while let Ok((stream, addr)) = listener.accept().await {
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if let Err(e) = metrics_request(stream, &state).await {
+                debug!("metrics request from {addr} failed: {e}");
+            }
+        });
+    }
+    warn!("metrics listener stopped");
+}
+
+async fn metrics_request(mut stream: TcpStream, state: &State) -> anyhow::Result<()> {
+    // only the request line matters, headers and body are ignored
+    let mut request = vec![0; 1024];
+    let n = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..n]);
+
+    let response = if request.starts_with("GET /metrics ") {
+        let body = METRICS.render(state.state.lock().await.len());
+        format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".into()
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
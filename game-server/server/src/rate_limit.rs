@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// buckets of idle ips are dropped once the limiter tracks more ips than this
+const MAX_IDLE_IPS: usize = 1024;
+
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    // tokens added per second
+    refill_rate: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(refill_rate: u32, capacity: u32) -> Self {
+        Self {
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            refill_rate: refill_rate as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.last_refill = now;
+    }
+
+    // false if the bucket is empty (the request must be throttled)
+    pub fn try_take(&mut self) -> bool {
+        self.refill();
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.capacity
+    }
+}
+
+// one token bucket per client ip, shared by every connection from that ip
+pub struct IpRateLimiter {
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+    refill_rate: u32,
+    capacity: u32,
+}
+
+impl IpRateLimiter {
+    pub fn new(refill_rate: u32, capacity: u32) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            refill_rate,
+            capacity,
+        }
+    }
+
+    pub fn try_take(&self, ip: IpAddr) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_IDLE_IPS {
+            // a full bucket is the same as a new one
+            buckets.retain(|_, bucket| !bucket.is_full());
+        }
+
+        buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(self.refill_rate, self.capacity))
+            .try_take()
+    }
+}
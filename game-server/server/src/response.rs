@@ -1,6 +1,18 @@
+use crate::bot::Difficulty;
+use crate::storage::PlayerStats;
+use net_utils::encoding::SUPPORTED_VERSIONS;
+use net_utils::item::Item;
+use net_utils::map::TileChange;
+use net_utils::packet::capabilities::*;
 use net_utils::packet::status_codes::*;
 use serde::{Deserialize, Serialize};
 
+// name and version sent in the HELLO reply
+pub const SERVER_NAME: &str = concat!("game-server/", env!("CARGO_PKG_VERSION"));
+const CAPABILITIES: [&str; 7] = [
+    ACCOUNTS, BOTS, SPECTATE, FOG_OF_WAR, MAP_DELTAS, TEAMS, ITEMS,
+];
+
 pub mod packet_sizes {
     // default: only status code
     pub const DEF: u16 = 13;
@@ -14,6 +26,8 @@ pub mod packet_sizes {
     pub const GM_CREAT_SIZE: u16 = 65;
 
     // err responses
+    // internal server error (e.g. the storage can't be read)
+    pub const INTERNAL_SERV_SIZE: u16 = DEF;
     // malformed request
     pub const MAL_REQ_SIZE: u16 = DEF;
     // invalid pseudo
@@ -32,14 +46,54 @@ pub mod packet_sizes {
     pub const GM_NOT_JOIN_SIZE: u16 = DEF;
     // game not started (can't send game data)
     pub const GM_NOT_START_SIZE: u16 = DEF;
+    // server full (too many games)
+    pub const SERV_FULL_SIZE: u16 = DEF;
+    // too many requests
+    pub const THROTTLED_SIZE: u16 = DEF;
+    // too many players created on the connection
+    pub const PL_LIMIT_SIZE: u16 = DEF;
+    // server shutting down
+    pub const SERV_SHUTDOWN_SIZE: u16 = DEF;
+    // not the player turn
+    pub const NOT_TURN_SIZE: u16 = DEF;
+    // pseudo already registered
+    pub const PSEUD_TAKEN_SIZE: u16 = DEF;
+    // unknown pseudo or wrong password
+    pub const INV_LOGIN_SIZE: u16 = DEF;
+    // invalid password
+    pub const INV_PASSWD_SIZE: u16 = DEF;
+    // no stats for the pseudo
+    pub const NO_STATS_SIZE: u16 = DEF;
+    // protocol version or encoding not supported
+    pub const UNSUP_PROTO_SIZE: u16 = DEF;
+    // request not allowed now
+    pub const WRONG_STATE_SIZE: u16 = DEF;
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct GamePlayerInfos {
     pub player_num: String,
     pub character: String,
     // atk, hp, ms, rng
     pub stats: (u8, u8, u8, u8),
+    // totals of the game, recorded in the player stats when it is over
+    #[serde(default)]
+    pub damage_dealt: u32,
+    #[serde(default)]
+    pub kills: u32,
+    // played by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bot: Option<Difficulty>,
+    // items whose bonus is included in the stats
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Effect {
+    pub item: Item,
+    // turns of the player left, the bonus is removed at the end of the last one
+    pub turns: u8,
 }
 impl GamePlayerInfos {
     pub fn json_string(
@@ -51,12 +105,14 @@ impl GamePlayerInfos {
             player_num,
             character,
             stats,
+            damage_dealt: 0,
+            kills: 0,
+            bot: None,
+            effects: vec![],
         })
     }
 }
 
-////
-
 #[derive(Serialize, Deserialize)]
 pub struct PlayerCreation<'a> {
     status: u64,
@@ -71,6 +127,29 @@ impl<'a> PlayerCreation<'a> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct Session<'a> {
+    // OK_REGISTER or OK_LOGIN
+    status: u64,
+    // used as player token until it expires
+    player_token: &'a str,
+    // unix time
+    expires: u64,
+}
+impl<'a> Session<'a> {
+    pub fn json_string(
+        status: u64,
+        player_token: &'a str,
+        expires: u64,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status,
+            player_token,
+            expires,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GameCreation<'a> {
     status: u64,
@@ -90,13 +169,14 @@ pub struct GameJoining<'a> {
     status: u64,
     // pseudo of newly joined player
     pseudo: &'a str,
-    // player currently in the game Vec<[player_number, pseudo, character, is_host]>
-    player_vec: Vec<[String; 4]>,
+    // player currently in the game Vec<[player_number, pseudo, character, is_host, team]>
+    // (team "1" or "2", empty if the game has no teams)
+    player_vec: Vec<[String; 5]>,
 }
 impl<'a> GameJoining<'a> {
     pub fn json_string(
         pseudo: &'a str,
-        player_vec: Vec<[String; 4]>,
+        player_vec: Vec<[String; 5]>,
     ) -> serde_json::Result<String> {
         serde_json::to_string(&Self {
             status: OK_GM_JOIN,
@@ -106,6 +186,45 @@ impl<'a> GameJoining<'a> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct PlayerLeaving<'a> {
+    status: u64,
+    // pseudo of the player removed from the game
+    pseudo: &'a str,
+    // same as GameJoining, without the player
+    player_vec: Vec<[String; 5]>,
+}
+impl<'a> PlayerLeaving<'a> {
+    pub fn json_string(
+        pseudo: &'a str,
+        player_vec: Vec<[String; 5]>,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: GM_PL_LEFT,
+            pseudo,
+            player_vec,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Spectating {
+    status: u64,
+    // same as GameJoining
+    player_vec: Vec<[String; 5]>,
+    // the map and turn follow in a GameStarting packet if true
+    started: bool,
+}
+impl Spectating {
+    pub fn json_string(player_vec: Vec<[String; 5]>, started: bool) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_GM_SPECTATE,
+            player_vec,
+            started,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CharacterChoosing<'a> {
     status: u64,
@@ -127,13 +246,43 @@ pub struct GameStarting {
     status: u64,
     player_turn: String,
     map: String,
+    // state version the game data packets build on
+    version: u32,
 }
 impl GameStarting {
-    pub fn json_string(player_turn: String, map: String) -> serde_json::Result<String> {
+    pub fn json_string(
+        player_turn: String,
+        map: String,
+        version: u32,
+    ) -> serde_json::Result<String> {
         serde_json::to_string(&Self {
             status: OK_GM_START,
             player_turn,
             map,
+            version,
+        })
+    }
+}
+
+// same as GameStarting, asked for by a client who missed a game data packet
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    status: u64,
+    player_turn: String,
+    map: String,
+    version: u32,
+}
+impl Snapshot {
+    pub fn json_string(
+        player_turn: String,
+        map: String,
+        version: u32,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_GM_SNAPSHOT,
+            player_turn,
+            map,
+            version,
         })
     }
 }
@@ -144,25 +293,147 @@ pub struct GameData {
     data_type: u64,
     player_num: String,
     player_turn: String,
-    map: String,
+    // state version after the action, one more than the previous packet
+    version: u32,
+    // tiles of the map seen by the receiver changed by the action
+    changes: Vec<TileChange>,
     // (enemy_number, enemy_remaining_hp)
     enemy: (String, u8),
+    // item picked up by the player
+    item: Option<Item>,
+    // atk, hp, ms, rng of the player after the action, items included
+    stats: (u8, u8, u8, u8),
 }
 impl GameData {
+    // player: (player_number, item picked up, stats)
     pub fn json_string(
         data_type: u64,
         player_turn: String,
-        player_num: String,
-        map: String,
+        player: (String, Option<Item>, (u8, u8, u8, u8)),
+        version: u32,
+        changes: Vec<TileChange>,
         enemy: (String, u8),
     ) -> serde_json::Result<String> {
+        let (player_num, item, stats) = player;
         serde_json::to_string(&Self {
             status: OK_GM_DATA,
             data_type,
             player_num,
             player_turn,
-            map,
+            version,
+            changes,
             enemy,
+            item,
+            stats,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GameOver {
+    status: u64,
+    // number of the last player alive (numbers of the players of the last team standing in
+    // team games), empty when an operator ended the game or it expired
+    winner: String,
+}
+impl GameOver {
+    pub fn json_string(winner: String) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_GM_OVER,
+            winner,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Leaderboard {
+    status: u64,
+    // best rating first
+    players: Vec<PlayerStats>,
+}
+impl Leaderboard {
+    pub fn json_string(players: Vec<PlayerStats>) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_LEADERBOARD,
+            players,
+        })
+    }
+}
+
+// written with the previous encoding, the next packets use the one asked by the client
+#[derive(Serialize, Deserialize)]
+pub struct Hello<'a> {
+    status: u64,
+    server: &'a str,
+    version: u64,
+    encoding: &'a str,
+    versions: Vec<u64>,
+    encodings: Vec<&'a str>,
+    capabilities: Vec<&'a str>,
+}
+impl<'a> Hello<'a> {
+    pub fn json_string(
+        version: u64,
+        encoding: &'a str,
+        encodings: Vec<&'a str>,
+    ) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_HELLO,
+            server: SERVER_NAME,
+            version,
+            encoding,
+            versions: SUPPORTED_VERSIONS.to_vec(),
+            encodings,
+            capabilities: CAPABILITIES.to_vec(),
+        })
+    }
+}
+
+// incompatible client, sent before the connection is closed
+#[derive(Serialize, Deserialize)]
+pub struct Refused<'a> {
+    status: u64,
+    server: &'a str,
+    versions: Vec<u64>,
+    encodings: Vec<&'a str>,
+}
+impl<'a> Refused<'a> {
+    pub fn json_string(encodings: Vec<&'a str>) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: ERR_UNSUP_PROTO,
+            server: SERVER_NAME,
+            versions: SUPPORTED_VERSIONS.to_vec(),
+            encodings,
+        })
+    }
+}
+
+// sent to every connection, in the lobby or in a game
+#[derive(Serialize, Deserialize)]
+pub struct Notice<'a> {
+    status: u64,
+    message: &'a str,
+}
+impl<'a> Notice<'a> {
+    pub fn json_string(message: &'a str) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: SERV_NOTICE,
+            message,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Stats {
+    status: u64,
+    #[serde(flatten)]
+    stats: PlayerStats,
+}
+impl Stats {
+    pub fn json_string(stats: PlayerStats) -> serde_json::Result<String> {
+        serde_json::to_string(&Self {
+            status: OK_PL_STATS,
+            stats,
         })
     }
 }
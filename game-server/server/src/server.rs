@@ -0,0 +1,271 @@
+use crate::admin::serve_admin;
+use crate::audit::ResultSigner;
+use crate::config::{Config, ExpiryConfig, GameConfig, LimitsConfig};
+use crate::game::GameHandle;
+use crate::handler::{handle_player, resume_games};
+use crate::metrics::{serve_metrics, ConnectionGuard};
+use crate::rate_limit::IpRateLimiter;
+use crate::storage::{Storage, StorageClient};
+use crate::stream::{AsyncStream, PacketStream};
+use crate::sweeper::run_sweeper;
+use crate::tls::load_acceptor;
+use crate::websocket::handle_websocket;
+use async_channel::{Receiver, Sender};
+use std::collections::HashMap;
+use std::future::{pending, Future};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::signal;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, info_span, warn, Instrument};
+
+pub type Channel = (Sender<String>, Receiver<String>);
+
+// notices kept for a connection busy writing, the older ones are dropped
+const NOTICE_CAPACITY: usize = 16;
+
+pub struct State {
+    // hashmap storing game tokens with the handle of the task running the game
+    pub state: Mutex<HashMap<String, GameHandle>>,
+    pub config: GameConfig,
+    pub limits: LimitsConfig,
+    pub expiry: ExpiryConfig,
+    pub ip_limiter: IpRateLimiter,
+    pub storage: StorageClient,
+    // time given to the connections to finish their request on shutdown
+    pub shutdown_timeout: Duration,
+    // time given to a new connection to finish its tls or websocket handshake
+    pub handshake_timeout: Duration,
+    // set to true once the server stops accepting connections
+    pub shutdown: watch::Sender<bool>,
+    // notices of the operators (admin console), forwarded by every connection
+    pub notices: broadcast::Sender<String>,
+    // signs the results of the finished games, None if they aren't recorded
+    pub results: Option<ResultSigner>,
+}
+impl State {
+    pub fn new(config: &Config, storage: StorageClient, results: Option<ResultSigner>) -> Self {
+        Self {
+            state: Mutex::new(HashMap::new()),
+            config: config.game.clone(),
+            limits: config.limits.clone(),
+            expiry: config.expiry.clone(),
+            ip_limiter: IpRateLimiter::new(
+                config.limits.ip_requests_per_second,
+                config.limits.ip_request_burst,
+            ),
+            storage,
+            shutdown_timeout: Duration::from_secs(config.shutdown_timeout),
+            handshake_timeout: Duration::from_secs(config.handshake_timeout),
+            shutdown: watch::channel(false).0,
+            notices: broadcast::channel(NOTICE_CAPACITY).0,
+            results,
+        }
+    }
+
+    // tell every game the server is going down, then every connection to stop
+    pub async fn shutdown(&self) {
+        // the lock is released first, a game ending removes itself from the map
+        let games: Vec<GameHandle> = self.state.lock().await.values().cloned().collect();
+        for game in games {
+            game.shutdown().await;
+        }
+        self.shutdown.send_replace(true);
+    }
+}
+
+// resolves on ctrl-c (SIGINT) or SIGTERM
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .unwrap()
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = signal::ctrl_c() => (),
+        _ = terminate => (),
+    }
+}
+
+pub async fn run(config: Config) -> anyhow::Result<()> {
+    let storage = StorageClient::open(&config.storage)?;
+    let tls_acceptor = match &config.tls {
+        Some(tls) => Some(load_acceptor(&tls.cert, &tls.key)?),
+        None => None,
+    };
+    let results = config
+        .results
+        .as_ref()
+        .map(ResultSigner::load)
+        .transpose()?;
+    if let Some(results) = &results {
+        info!("game results signed with key {}", results.public_key());
+    }
+    let listener = TcpListener::bind(config.bind).await?;
+    info!(
+        "listening on {} (tls: {}, storage: {:?})",
+        config.bind,
+        tls_acceptor.is_some(),
+        config.storage.backend
+    );
+
+    let state = Arc::new(State::new(&config, storage, results));
+    let resumed = resume_games(&state)?;
+    if resumed > 0 {
+        info!("{resumed} started games resumed");
+    }
+    tokio::spawn(run_sweeper(Arc::clone(&state)));
+    if let Some(metrics) = &config.metrics {
+        let metrics_listener = TcpListener::bind(metrics.bind).await?;
+        info!("metrics on http://{}/metrics", metrics.bind);
+        tokio::spawn(serve_metrics(metrics_listener, Arc::clone(&state)));
+    }
+    if let Some(admin) = &config.admin {
+        let admin_listener = TcpListener::bind(admin.bind).await?;
+        info!("admin console on {}", admin.bind);
+        let token = admin.token.clone();
+        tokio::spawn(serve_admin(admin_listener, Arc::clone(&state), token));
+    }
+    let ws_listener = match &config.websocket {
+        Some(websocket) => {
+            info!("websocket listening on {}", websocket.bind);
+            Some(TcpListener::bind(websocket.bind).await?)
+        }
+        None => None,
+    };
+    serve(
+        listener,
+        ws_listener,
+        Arc::clone(&state),
+        tls_acceptor,
+        shutdown_signal(),
+    )
+    .await?;
+    state.storage.persist()?;
+    info!("server stopped");
+    Ok(())
+}
+
+// never resolves without websocket listener
+async fn accept_websocket(listener: &Option<TcpListener>) -> io::Result<(TcpStream, SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => pending().await,
+    }
+}
+
+async fn handle_stream<S: AsyncStream>(
+    state: Arc<State>,
+    stream: S,
+    ip: IpAddr,
+    websocket: bool,
+    store: &mut dyn Storage,
+) -> anyhow::Result<()> {
+    match websocket {
+        true => handle_websocket(state, stream, ip, store).await,
+        false => handle_player(state, PacketStream::new(stream), ip, store).await,
+    }
+}
+
+// accept connections (tcp and websocket) until the shutdown future resolves, then wait for
+// the connections to close (at most shutdown_timeout)
+pub async fn serve(
+    listener: TcpListener,
+    ws_listener: Option<TcpListener>,
+    state: Arc<State>,
+    tls_acceptor: Option<TlsAcceptor>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        let (accepted, websocket) = tokio::select! {
+            accepted = listener.accept() => (accepted, false),
+            accepted = accept_websocket(&ws_listener) => (accepted, true),
+            // forget the connections that ended
+            Some(_) = connections.join_next() => continue,
+            _ = &mut shutdown => break,
+        };
+        let (stream, addr) = match accepted {
+            Ok(a) => a,
+            Err(e) => {
+                warn!("can't accept connection: {e}");
+                continue;
+            }
+        };
+
+        let state = Arc::clone(&state);
+        let mut store = match state.storage.get_connection() {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("can't open storage for {addr}: {e}");
+                continue;
+            }
+        };
+        let tls_acceptor = tls_acceptor.clone();
+        let span = info_span!("connection", %addr, websocket);
+        connections.spawn(
+            async move {
+                let _guard = ConnectionGuard::open();
+                info!("connected");
+                let handled = match tls_acceptor {
+                    Some(acceptor) => {
+                        let handshake = acceptor.accept(stream);
+                        let stream = match timeout(state.handshake_timeout, handshake).await {
+                            Ok(Ok(s)) => s,
+                            // failed or stalled handshake, drop the connection
+                            Ok(Err(e)) => {
+                                info!("tls handshake failed: {e}");
+                                return;
+                            }
+                            Err(_) => {
+                                info!("tls handshake timed out");
+                                return;
+                            }
+                        };
+                        handle_stream(state, stream, addr.ip(), websocket, &mut *store).await
+                    }
+                    None => handle_stream(state, stream, addr.ip(), websocket, &mut *store).await,
+                };
+                if let Err(e) = handled {
+                    warn!("connection closed on error: {e}");
+                }
+                info!("disconnected");
+            }
+            .instrument(span),
+        );
+    }
+
+    drop(listener);
+    drop(ws_listener);
+    info!(
+        "shutting down, waiting for {} connections",
+        connections.len()
+    );
+    state.shutdown().await;
+    let drained = timeout(state.shutdown_timeout, async {
+        while connections.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!(
+            "{} connections still open after {:?}, aborting them",
+            connections.len(),
+            state.shutdown_timeout
+        );
+        connections.shutdown().await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use crate::storage::{PlayerStats, Storage};
+use std::collections::BTreeMap;
+
+// rating of a player before his first recorded game
+pub const INITIAL_RATING: i32 = 1200;
+// maximum rating change of a single pairing
+const K_FACTOR: f64 = 32.0;
+
+// result of one player in a finished game
+pub struct GameResult {
+    pub pseudo: String,
+    // only the registered players have stats, the others (anonymous players and bots) only
+    // count as opponents, in unrated games
+    pub registered: bool,
+    pub character: String,
+    pub won: bool,
+    pub damage_dealt: u32,
+    pub kills: u32,
+}
+
+impl PlayerStats {
+    pub fn new(pseudo: &str) -> Self {
+        Self {
+            pseudo: pseudo.into(),
+            wins: 0,
+            losses: 0,
+            damage_dealt: 0,
+            kills: 0,
+            classes: BTreeMap::new(),
+            rating: INITIAL_RATING,
+        }
+    }
+}
+
+// probability for the player to beat the opponent
+fn expected_score(rating: i32, opponent_rating: i32) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent_rating - rating) as f64 / 400.0))
+}
+
+// the winner beat every loser: one elo pairing per loser, computed from the ratings
+// before the game, returns the new ratings
+pub fn updated_ratings(winner: i32, losers: &[i32]) -> (i32, Vec<i32>) {
+    let mut new_winner = winner;
+    let new_losers = losers
+        .iter()
+        .map(|loser| {
+            let change = (K_FACTOR * (1.0 - expected_score(winner, *loser))).round() as i32;
+            new_winner += change;
+            loser - change
+        })
+        .collect();
+    (new_winner, new_losers)
+}
+
+// add the results of a finished game to the stats of its registered players, their ratings
+// only change if every player of the game is registered
+pub fn record_game(store: &mut dyn Storage, results: &[GameResult]) -> anyhow::Result<()> {
+    let mut stats = vec![];
+    for result in results {
+        let player_stats = if result.registered {
+            let player_stats = store.player_stats(&result.pseudo)?;
+            Some(player_stats.unwrap_or_else(|| PlayerStats::new(&result.pseudo)))
+        } else {
+            None
+        };
+        stats.push(player_stats);
+    }
+
+    let rating = |i: usize| stats[i].as_ref().map_or(INITIAL_RATING, |s| s.rating);
+    let (winners, losers): (Vec<usize>, Vec<usize>) =
+        (0..results.len()).partition(|i| results[*i].won);
+    let before: Vec<i32> = (0..results.len()).map(rating).collect();
+    let mut ratings = before.clone();
+    // only games between registered players are rated: beating anonymous players or bots,
+    // that anyone can bring to a game, would be free rating
+    let winners = match stats.iter().all(Option::is_some) {
+        true => winners,
+        false => vec![],
+    };
+    // in team games each winner beat every loser
+    let loser_ratings: Vec<i32> = losers.iter().map(|i| before[*i]).collect();
+    for winner in winners {
+        let (winner_rating, new_loser_ratings) = updated_ratings(before[winner], &loser_ratings);
+        ratings[winner] = winner_rating;
+        for ((i, loser_rating), new_loser_rating) in
+            losers.iter().zip(&loser_ratings).zip(new_loser_ratings)
+        {
+            ratings[*i] += new_loser_rating - loser_rating;
+        }
+    }
+
+    for ((result, player_stats), rating) in results.iter().zip(stats).zip(ratings) {
+        let mut player_stats = match player_stats {
+            Some(s) => s,
+            None => continue,
+        };
+        if result.won {
+            player_stats.wins += 1;
+        } else {
+            player_stats.losses += 1;
+        }
+        player_stats.damage_dealt += result.damage_dealt;
+        player_stats.kills += result.kills;
+        *player_stats
+            .classes
+            .entry(result.character.clone())
+            .or_default() += 1;
+        player_stats.rating = rating;
+        store.set_player_stats(&player_stats)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+use crate::audit::LoggedAction;
+use crate::response::GamePlayerInfos;
+use crate::storage::{new_game_player, Account, GameInfo, PlayerInfos, PlayerStats, Storage};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Default, Serialize, Deserialize)]
+struct MemoryData {
+    players: HashMap<String, PlayerInfos>,
+    games: HashMap<String, MemoryGame>,
+    // lowercase pseudo -> account
+    #[serde(default)]
+    accounts: HashMap<String, Account>,
+    // lowercase pseudo -> stats
+    #[serde(default)]
+    stats: HashMap<String, PlayerStats>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MemoryGame {
+    info: GameInfo,
+    players: HashMap<String, GamePlayerInfos>,
+    #[serde(default)]
+    actions: Vec<LoggedAction>,
+}
+
+// process local storage, every clone shares the same data (lost when the server stops)
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    data: Arc<Mutex<MemoryData>>,
+}
+
+impl MemoryStorage {
+    // data saved by a previous run, empty storage if the file doesn't exist yet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("can't read storage snapshot {}", path.display()))?;
+        let data: MemoryData = serde_json::from_str(&content)
+            .with_context(|| format!("invalid storage snapshot {}", path.display()))?;
+        Ok(Self {
+            data: Arc::new(Mutex::new(data)),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = serde_json::to_string(&*self.data.lock().unwrap())?;
+        // written next to the snapshot then renamed so a crash can't leave half a file
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)
+            .with_context(|| format!("can't write storage snapshot {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn insert_player(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        if data.players.contains_key(player_token) {
+            return Ok(false);
+        }
+        data.players.insert(player_token.into(), infos.clone());
+        Ok(true)
+    }
+
+    fn player_infos(&mut self, player_token: &str) -> anyhow::Result<Option<PlayerInfos>> {
+        Ok(self.data.lock().unwrap().players.get(player_token).cloned())
+    }
+
+    fn set_player_infos(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.players.insert(player_token.into(), infos.clone());
+        Ok(())
+    }
+
+    fn touch_player(&mut self, player_token: &str, now: u64) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(infos) = data.players.get_mut(player_token) {
+            infos.last_activity = now;
+        }
+        Ok(())
+    }
+
+    fn players(&mut self) -> anyhow::Result<Vec<(String, PlayerInfos)>> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .players
+            .iter()
+            .map(|(token, infos)| (token.clone(), infos.clone()))
+            .collect())
+    }
+
+    fn remove_player(&mut self, player_token: &str) -> anyhow::Result<()> {
+        self.data.lock().unwrap().players.remove(player_token);
+        Ok(())
+    }
+
+    fn insert_account(&mut self, account: &Account) -> anyhow::Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        let key = account.pseudo.to_lowercase();
+        if data.accounts.contains_key(&key) {
+            return Ok(false);
+        }
+        data.accounts.insert(key, account.clone());
+        Ok(true)
+    }
+
+    fn account(&mut self, pseudo: &str) -> anyhow::Result<Option<Account>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.accounts.get(&pseudo.to_lowercase()).cloned())
+    }
+
+    fn player_stats(&mut self, pseudo: &str) -> anyhow::Result<Option<PlayerStats>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.stats.get(&pseudo.to_lowercase()).cloned())
+    }
+
+    fn set_player_stats(&mut self, stats: &PlayerStats) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.stats
+            .insert(stats.pseudo.to_lowercase(), stats.clone());
+        Ok(())
+    }
+
+    fn leaderboard(&mut self, count: usize) -> anyhow::Result<Vec<PlayerStats>> {
+        let data = self.data.lock().unwrap();
+        let mut players: Vec<PlayerStats> = data.stats.values().cloned().collect();
+        players.sort_by(|a, b| {
+            b.rating
+                .cmp(&a.rating)
+                .then_with(|| a.pseudo.cmp(&b.pseudo))
+        });
+        players.truncate(count);
+        Ok(players)
+    }
+
+    fn game_exists(&mut self, game_token: &str) -> anyhow::Result<bool> {
+        Ok(self.data.lock().unwrap().games.contains_key(game_token))
+    }
+
+    fn game_count(&mut self) -> anyhow::Result<usize> {
+        Ok(self.data.lock().unwrap().games.len())
+    }
+
+    fn games(&mut self) -> anyhow::Result<Vec<String>> {
+        Ok(self.data.lock().unwrap().games.keys().cloned().collect())
+    }
+
+    fn create_game(
+        &mut self,
+        game_token: &str,
+        game_info: &GameInfo,
+        host_infos: &PlayerInfos,
+    ) -> anyhow::Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        if data.games.contains_key(game_token) {
+            return Ok(false);
+        }
+
+        let host_player = game_info.host_player.clone();
+        let mut host_infos = host_infos.clone();
+        host_infos.hosting = 1;
+        data.players.insert(host_player.clone(), host_infos);
+        data.games.insert(
+            game_token.into(),
+            MemoryGame {
+                info: game_info.clone(),
+                players: HashMap::from([(host_player, new_game_player(1))]),
+                actions: vec![],
+            },
+        );
+        Ok(true)
+    }
+
+    fn game_info(&mut self, game_token: &str) -> anyhow::Result<Option<GameInfo>> {
+        let data = self.data.lock().unwrap();
+        Ok(data.games.get(game_token).map(|g| g.info.clone()))
+    }
+
+    fn set_game_info(&mut self, game_token: &str, game_info: &GameInfo) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(game) = data.games.get_mut(game_token) {
+            game.info = game_info.clone();
+        }
+        Ok(())
+    }
+
+    fn remove_game(&mut self, game_token: &str) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(game) = data.games.remove(game_token) {
+            if let Some(host_infos) = data.players.get_mut(&game.info.host_player) {
+                host_infos.hosting = 0;
+            }
+        }
+        Ok(())
+    }
+
+    fn game_players(&mut self, game_token: &str) -> anyhow::Result<Vec<(String, GamePlayerInfos)>> {
+        let data = self.data.lock().unwrap();
+        let mut players: Vec<(String, GamePlayerInfos)> = match data.games.get(game_token) {
+            Some(game) => game
+                .players
+                .iter()
+                .map(|(token, infos)| (token.clone(), infos.clone()))
+                .collect(),
+            None => vec![],
+        };
+        players.sort_by(|a, b| a.1.player_num.cmp(&b.1.player_num));
+        Ok(players)
+    }
+
+    fn set_game_player(
+        &mut self,
+        game_token: &str,
+        player_token: &str,
+        infos: &GamePlayerInfos,
+    ) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(game) = data.games.get_mut(game_token) {
+            game.players.insert(player_token.into(), infos.clone());
+        }
+        Ok(())
+    }
+
+    fn remove_game_player(&mut self, game_token: &str, player_token: &str) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(game) = data.games.get_mut(game_token) {
+            game.players.remove(player_token);
+        }
+        Ok(())
+    }
+
+    fn append_action(&mut self, game_token: &str, action: &LoggedAction) -> anyhow::Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(game) = data.games.get_mut(game_token) {
+            game.info.chain = action.hash.clone();
+            game.actions.push(action.clone());
+        }
+        Ok(())
+    }
+
+    fn actions(&mut self, game_token: &str) -> anyhow::Result<Vec<LoggedAction>> {
+        let data = self.data.lock().unwrap();
+        Ok(data
+            .games
+            .get(game_token)
+            .map_or(vec![], |game| game.actions.clone()))
+    }
+}
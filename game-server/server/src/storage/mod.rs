@@ -0,0 +1,206 @@
+mod memory_backend;
+mod redis_backend;
+
+use crate::audit::LoggedAction;
+use crate::config::{StorageBackend, StorageConfig};
+use crate::response::GamePlayerInfos;
+pub use memory_backend::MemoryStorage;
+pub use redis_backend::RedisStorage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PlayerInfos {
+    pub pseudo: String,
+    pub hosting: u8,
+    // unix time of the last request made with this player token
+    #[serde(default)]
+    pub last_activity: u64,
+    // registered players only: unix time after which the session token is rejected
+    #[serde(default)]
+    pub session_expiry: Option<u64>,
+}
+
+impl PlayerInfos {
+    pub fn session_expired(&self, now: u64) -> bool {
+        self.session_expiry.is_some_and(|expiry| expiry <= now)
+    }
+}
+
+// registered player, never expires
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Account {
+    // as registered, the accounts are looked up case insensitively
+    pub pseudo: String,
+    // argon2 PHC string (algorithm, parameters, salt and hash)
+    pub password_hash: String,
+    // unix time
+    pub created: u64,
+}
+
+// results of the finished games of a registered player
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerStats {
+    // as registered
+    pub pseudo: String,
+    pub wins: u32,
+    pub losses: u32,
+    // hp removed from the enemies
+    pub damage_dealt: u32,
+    pub kills: u32,
+    // character class -> games played with it
+    pub classes: BTreeMap<String, u32>,
+    // elo
+    pub rating: i32,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GameInfo {
+    pub started: bool,
+    pub host_player: String,
+    pub player_count: u8,
+    // players needed to start the game
+    pub max_players: u8,
+    pub map: String,
+    // player numbers in playing order, the first one is the current player (in team games the
+    // players of a team outnumbered by the other play more than once per round)
+    pub turn: String,
+    // number of turns played (or skipped) since the game started
+    pub turn_count: u32,
+    // unix time of the last player action (skipped turns don't count)
+    pub last_activity: u64,
+    // the players only see the tiles around them
+    #[serde(default)]
+    pub fog_of_war: bool,
+    // player numbers of each team (2v2 games), empty if every player is on his own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub teams: Vec<String>,
+    // hash of the last action of the replay log (see audit.rs), the log itself is only
+    // appended to
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub chain: String,
+}
+
+// operations on the players and games records, one storage per player connection
+pub trait Storage: Send {
+    // false if the player token is already taken
+    fn insert_player(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<bool>;
+    fn player_infos(&mut self, player_token: &str) -> anyhow::Result<Option<PlayerInfos>>;
+    fn set_player_infos(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<()>;
+    // update the last activity of an existing player
+    fn touch_player(&mut self, player_token: &str, now: u64) -> anyhow::Result<()>;
+    fn players(&mut self) -> anyhow::Result<Vec<(String, PlayerInfos)>>;
+    fn remove_player(&mut self, player_token: &str) -> anyhow::Result<()>;
+
+    // false if the pseudo is already registered (case insensitive)
+    fn insert_account(&mut self, account: &Account) -> anyhow::Result<bool>;
+    fn account(&mut self, pseudo: &str) -> anyhow::Result<Option<Account>>;
+
+    // stats of a registered player (case insensitive pseudo)
+    fn player_stats(&mut self, pseudo: &str) -> anyhow::Result<Option<PlayerStats>>;
+    fn set_player_stats(&mut self, stats: &PlayerStats) -> anyhow::Result<()>;
+    // best ratings first
+    fn leaderboard(&mut self, count: usize) -> anyhow::Result<Vec<PlayerStats>>;
+
+    fn game_exists(&mut self, game_token: &str) -> anyhow::Result<bool>;
+    fn game_count(&mut self) -> anyhow::Result<usize>;
+    fn games(&mut self) -> anyhow::Result<Vec<String>>;
+    // false if the game token is already taken
+    // the host player is added to the game as player 1 and marked as hosting
+    fn create_game(
+        &mut self,
+        game_token: &str,
+        game_info: &GameInfo,
+        host_infos: &PlayerInfos,
+    ) -> anyhow::Result<bool>;
+    fn game_info(&mut self, game_token: &str) -> anyhow::Result<Option<GameInfo>>;
+    fn set_game_info(&mut self, game_token: &str, game_info: &GameInfo) -> anyhow::Result<()>;
+    // delete every record of the game, the host player can host a new game afterwards
+    fn remove_game(&mut self, game_token: &str) -> anyhow::Result<()>;
+    // (player_token, infos) of every player in the game, ordered by player number
+    fn game_players(&mut self, game_token: &str) -> anyhow::Result<Vec<(String, GamePlayerInfos)>>;
+    fn set_game_player(
+        &mut self,
+        game_token: &str,
+        player_token: &str,
+        infos: &GamePlayerInfos,
+    ) -> anyhow::Result<()>;
+    // the player left the game before its start
+    fn remove_game_player(&mut self, game_token: &str, player_token: &str) -> anyhow::Result<()>;
+    // add an action at the end of the replay log of the game, its hash becomes the chain
+    fn append_action(&mut self, game_token: &str, action: &LoggedAction) -> anyhow::Result<()>;
+    // replay log of the game, oldest action first
+    fn actions(&mut self, game_token: &str) -> anyhow::Result<Vec<LoggedAction>>;
+}
+
+// handle on the configured backend, used to open a storage for each connection
+#[derive(Clone)]
+pub enum StorageClient {
+    Redis(redis::Client),
+    Memory {
+        storage: MemoryStorage,
+        snapshot: Option<PathBuf>,
+    },
+}
+
+impl StorageClient {
+    pub fn open(config: &StorageConfig) -> anyhow::Result<Self> {
+        Ok(match config.backend {
+            StorageBackend::Redis => {
+                let client = redis::Client::open(&*config.url)?;
+                if client.get_connection().is_err() {
+                    anyhow::bail!("redis instance not started ({})", config.url);
+                }
+                Self::Redis(client)
+            }
+            StorageBackend::Memory => Self::Memory {
+                storage: match &config.snapshot {
+                    Some(path) => MemoryStorage::load(path)?,
+                    None => MemoryStorage::default(),
+                },
+                snapshot: config.snapshot.clone(),
+            },
+        })
+    }
+
+    // save what can't survive a restart, called on shutdown
+    pub fn persist(&self) -> anyhow::Result<()> {
+        if let Self::Memory {
+            storage,
+            snapshot: Some(path),
+        } = self
+        {
+            storage.save(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_connection(&self) -> anyhow::Result<Box<dyn Storage>> {
+        Ok(match self {
+            Self::Redis(client) => Box::new(RedisStorage::new(client.get_connection()?)),
+            Self::Memory { storage, .. } => Box::new(storage.clone()),
+        })
+    }
+}
+
+// player entry written when a player joins a game, before he picks a character
+pub fn new_game_player(player_num: u8) -> GamePlayerInfos {
+    GamePlayerInfos {
+        player_num: player_num.to_string(),
+        character: String::new(),
+        stats: (0, 0, 0, 0),
+        damage_dealt: 0,
+        kills: 0,
+        bot: None,
+        effects: vec![],
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
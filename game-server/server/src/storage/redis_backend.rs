@@ -0,0 +1,315 @@
+use crate::audit::LoggedAction;
+use crate::response::GamePlayerInfos;
+use crate::storage::{new_game_player, Account, GameInfo, PlayerInfos, PlayerStats, Storage};
+use anyhow::Context;
+use redis::{Commands, Connection};
+use std::collections::HashMap;
+
+// keys:
+// "player" hash: player_token -> PlayerInfos json
+// "account" hash: lowercase pseudo -> Account json
+// "stats" hash: lowercase pseudo -> PlayerStats json
+// "rating" sorted set: lowercase pseudos scored by rating
+// "game" set: game tokens
+// "game_info:<game_token>" hash: GameInfo fields
+// "game_player:<game_token>" hash: player_token -> GamePlayerInfos json
+// "game_action:<game_token>" list: LoggedAction json, oldest first
+pub struct RedisStorage {
+    con: Connection,
+}
+
+impl RedisStorage {
+    pub fn new(con: Connection) -> Self {
+        Self { con }
+    }
+}
+
+fn game_info_key(game_token: &str) -> String {
+    format!("game_info:{game_token}")
+}
+
+fn game_player_key(game_token: &str) -> String {
+    format!("game_player:{game_token}")
+}
+
+fn game_action_key(game_token: &str) -> String {
+    format!("game_action:{game_token}")
+}
+
+fn game_info_fields(game_info: &GameInfo) -> Vec<(&'static str, String)> {
+    vec![
+        ("started", if game_info.started { "1" } else { "0" }.into()),
+        ("host_player", game_info.host_player.clone()),
+        ("player_count", game_info.player_count.to_string()),
+        ("max_players", game_info.max_players.to_string()),
+        ("map", game_info.map.clone()),
+        ("turn", game_info.turn.clone()),
+        ("turn_count", game_info.turn_count.to_string()),
+        ("last_activity", game_info.last_activity.to_string()),
+        (
+            "fog_of_war",
+            if game_info.fog_of_war { "1" } else { "0" }.into(),
+        ),
+        ("teams", game_info.teams.join(",")),
+        ("chain", game_info.chain.clone()),
+    ]
+}
+
+fn parse_game_info(mut fields: HashMap<String, String>) -> anyhow::Result<GameInfo> {
+    let mut field = |name: &str| {
+        fields
+            .remove(name)
+            .with_context(|| format!("game info field {name} missing"))
+    };
+
+    Ok(GameInfo {
+        started: field("started")? == "1",
+        host_player: field("host_player")?,
+        player_count: field("player_count")?.parse()?,
+        max_players: field("max_players")?.parse()?,
+        map: field("map")?,
+        turn: field("turn")?,
+        turn_count: field("turn_count")?.parse()?,
+        // games stored before activities were tracked are considered stale
+        last_activity: field("last_activity").map_or(Ok(0), |t| t.parse())?,
+        fog_of_war: field("fog_of_war").is_ok_and(|f| f == "1"),
+        teams: field("teams").map_or(vec![], |teams| {
+            teams
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(String::from)
+                .collect()
+        }),
+        chain: field("chain").unwrap_or_default(),
+    })
+}
+
+impl Storage for RedisStorage {
+    fn insert_player(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<bool> {
+        let infos = serde_json::to_string(infos)?;
+        Ok(self.con.hset_nx("player", player_token, infos)?)
+    }
+
+    fn player_infos(&mut self, player_token: &str) -> anyhow::Result<Option<PlayerInfos>> {
+        let infos: Option<String> = self.con.hget("player", player_token)?;
+        Ok(match infos {
+            Some(infos) => Some(serde_json::from_str(&infos)?),
+            None => None,
+        })
+    }
+
+    fn set_player_infos(&mut self, player_token: &str, infos: &PlayerInfos) -> anyhow::Result<()> {
+        let infos = serde_json::to_string(infos)?;
+        self.con
+            .hset::<_, _, _, ()>("player", player_token, infos)?;
+        Ok(())
+    }
+
+    fn touch_player(&mut self, player_token: &str, now: u64) -> anyhow::Result<()> {
+        if let Some(mut infos) = self.player_infos(player_token)? {
+            infos.last_activity = now;
+            self.set_player_infos(player_token, &infos)?;
+        }
+        Ok(())
+    }
+
+    fn players(&mut self) -> anyhow::Result<Vec<(String, PlayerInfos)>> {
+        let players: HashMap<String, String> = self.con.hgetall("player")?;
+        players
+            .into_iter()
+            .map(|(token, infos)| Ok((token, serde_json::from_str(&infos)?)))
+            .collect()
+    }
+
+    fn remove_player(&mut self, player_token: &str) -> anyhow::Result<()> {
+        self.con.hdel::<_, _, ()>("player", player_token)?;
+        Ok(())
+    }
+
+    fn insert_account(&mut self, account: &Account) -> anyhow::Result<bool> {
+        let json = serde_json::to_string(account)?;
+        Ok(self
+            .con
+            .hset_nx("account", account.pseudo.to_lowercase(), json)?)
+    }
+
+    fn account(&mut self, pseudo: &str) -> anyhow::Result<Option<Account>> {
+        let account: Option<String> = self.con.hget("account", pseudo.to_lowercase())?;
+        Ok(match account {
+            Some(account) => Some(serde_json::from_str(&account)?),
+            None => None,
+        })
+    }
+
+    fn player_stats(&mut self, pseudo: &str) -> anyhow::Result<Option<PlayerStats>> {
+        let stats: Option<String> = self.con.hget("stats", pseudo.to_lowercase())?;
+        Ok(match stats {
+            Some(stats) => Some(serde_json::from_str(&stats)?),
+            None => None,
+        })
+    }
+
+    fn set_player_stats(&mut self, stats: &PlayerStats) -> anyhow::Result<()> {
+        let key = stats.pseudo.to_lowercase();
+        redis::pipe()
+            .atomic()
+            .hset("stats", &key, serde_json::to_string(stats)?)
+            .ignore()
+            .zadd("rating", &key, stats.rating)
+            .ignore()
+            .query::<()>(&mut self.con)?;
+        Ok(())
+    }
+
+    fn leaderboard(&mut self, count: usize) -> anyhow::Result<Vec<PlayerStats>> {
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let pseudos: Vec<String> = self.con.zrevrange("rating", 0, count as isize - 1)?;
+        let mut players = vec![];
+        for pseudo in pseudos {
+            if let Some(stats) = self.player_stats(&pseudo)? {
+                players.push(stats);
+            }
+        }
+        Ok(players)
+    }
+
+    fn game_exists(&mut self, game_token: &str) -> anyhow::Result<bool> {
+        Ok(self.con.sismember("game", game_token)?)
+    }
+
+    fn game_count(&mut self) -> anyhow::Result<usize> {
+        Ok(self.con.scard("game")?)
+    }
+
+    fn games(&mut self) -> anyhow::Result<Vec<String>> {
+        Ok(self.con.smembers("game")?)
+    }
+
+    fn create_game(
+        &mut self,
+        game_token: &str,
+        game_info: &GameInfo,
+        host_infos: &PlayerInfos,
+    ) -> anyhow::Result<bool> {
+        let game_info_key = game_info_key(game_token);
+        let game_player_key = game_player_key(game_token);
+        let mut host_infos = host_infos.clone();
+        host_infos.hosting = 1;
+        let host_infos = serde_json::to_string(&host_infos)?;
+        let host_game_player = serde_json::to_string(&new_game_player(1))?;
+
+        let created = redis::transaction(
+            &mut self.con,
+            &["game", "player", &game_info_key, &game_player_key],
+            |con, pipe| {
+                if con.sismember("game", game_token)? {
+                    // game token already exists (very rare but still a possibility)
+                    return Ok(Some(false));
+                }
+
+                let ret: Option<()> = pipe
+                    .sadd("game", game_token)
+                    .ignore()
+                    .hset_multiple(&game_info_key, &game_info_fields(game_info))
+                    .ignore()
+                    .hset("player", &game_info.host_player, &host_infos)
+                    .ignore()
+                    .hset(&game_player_key, &game_info.host_player, &host_game_player)
+                    .ignore()
+                    .query(con)?;
+                Ok(ret.map(|_| true))
+            },
+        )?;
+
+        Ok(created)
+    }
+
+    fn game_info(&mut self, game_token: &str) -> anyhow::Result<Option<GameInfo>> {
+        let fields: HashMap<String, String> = self.con.hgetall(game_info_key(game_token))?;
+        if fields.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(parse_game_info(fields)?))
+    }
+
+    fn set_game_info(&mut self, game_token: &str, game_info: &GameInfo) -> anyhow::Result<()> {
+        self.con.hset_multiple::<_, _, _, ()>(
+            game_info_key(game_token),
+            &game_info_fields(game_info),
+        )?;
+        Ok(())
+    }
+
+    fn remove_game(&mut self, game_token: &str) -> anyhow::Result<()> {
+        let host_player: Option<String> =
+            self.con.hget(game_info_key(game_token), "host_player")?;
+        redis::pipe()
+            .atomic()
+            .srem("game", game_token)
+            .ignore()
+            .del(game_info_key(game_token))
+            .ignore()
+            .del(game_player_key(game_token))
+            .ignore()
+            .del(game_action_key(game_token))
+            .ignore()
+            .query::<()>(&mut self.con)?;
+
+        if let Some(host_player) = host_player {
+            if let Some(mut host_infos) = self.player_infos(&host_player)? {
+                host_infos.hosting = 0;
+                self.set_player_infos(&host_player, &host_infos)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn game_players(&mut self, game_token: &str) -> anyhow::Result<Vec<(String, GamePlayerInfos)>> {
+        let players: HashMap<String, String> = self.con.hgetall(game_player_key(game_token))?;
+        let mut players = players
+            .into_iter()
+            .map(|(token, infos)| Ok((token, serde_json::from_str(&infos)?)))
+            .collect::<anyhow::Result<Vec<(String, GamePlayerInfos)>>>()?;
+        players.sort_by(|a, b| a.1.player_num.cmp(&b.1.player_num));
+        Ok(players)
+    }
+
+    fn set_game_player(
+        &mut self,
+        game_token: &str,
+        player_token: &str,
+        infos: &GamePlayerInfos,
+    ) -> anyhow::Result<()> {
+        let infos = serde_json::to_string(infos)?;
+        self.con
+            .hset::<_, _, _, ()>(game_player_key(game_token), player_token, infos)?;
+        Ok(())
+    }
+
+    fn remove_game_player(&mut self, game_token: &str, player_token: &str) -> anyhow::Result<()> {
+        self.con
+            .hdel::<_, _, ()>(game_player_key(game_token), player_token)?;
+        Ok(())
+    }
+
+    fn append_action(&mut self, game_token: &str, action: &LoggedAction) -> anyhow::Result<()> {
+        redis::pipe()
+            .atomic()
+            .rpush(game_action_key(game_token), serde_json::to_string(action)?)
+            .ignore()
+            .hset(game_info_key(game_token), "chain", &action.hash)
+            .ignore()
+            .query::<()>(&mut self.con)?;
+        Ok(())
+    }
+
+    fn actions(&mut self, game_token: &str) -> anyhow::Result<Vec<LoggedAction>> {
+        let actions: Vec<String> = self.con.lrange(game_action_key(game_token), 0, -1)?;
+        actions
+            .iter()
+            .map(|action| Ok(serde_json::from_str(action)?))
+            .collect()
+    }
+}
@@ -0,0 +1,151 @@
+use crate::metrics::METRICS;
+use net_utils::encoding::Encoding;
+use net_utils::packet::status_codes::{is_error, SERV_SHUTDOWN};
+use serde_json::{json, Value};
+use std::io::{self, ErrorKind};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+// any byte stream a player can be connected through (plain tcp or tls)
+pub trait AsyncStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AsyncStream for T {}
+
+// bytes asked from the stream at a time
+const READ_CHUNK_SIZE: usize = 4096;
+
+// packets of a connection, json until the client negotiates another encoding
+pub struct PacketStream<S> {
+    stream: S,
+    encoding: Encoding,
+    // false when the transport frames the json packets itself (websocket bridge)
+    binary_allowed: bool,
+    // bytes received but not read as a packet yet (the start of the next packets)
+    buffer: Vec<u8>,
+}
+
+impl<S: AsyncStream> PacketStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            encoding: Encoding::Json,
+            binary_allowed: true,
+            buffer: vec![],
+        }
+    }
+
+    pub fn json_only(stream: S) -> Self {
+        Self {
+            binary_allowed: false,
+            ..Self::new(stream)
+        }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn can_use(&self, encoding: Encoding) -> bool {
+        encoding == Encoding::Json || self.binary_allowed
+    }
+
+    // used from the next packet on
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    // true if bytes of a packet were received but not read yet
+    pub fn has_buffered(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    // first packet of the buffer, None until all its bytes were received, InvalidData if it
+    // is too large or isn't a packet of the encoding
+    fn next_packet(&mut self) -> io::Result<Option<Value>> {
+        let encoding = self.encoding;
+        let header_size = encoding.header_size();
+        if self.buffer.len() < header_size {
+            return Ok(None);
+        }
+        let size = encoding.packet_size(&self.buffer[..header_size]);
+        if size > encoding.max_packet_size() {
+            let message = format!("packet of {size} bytes refused");
+            return Err(io::Error::new(ErrorKind::InvalidData, message));
+        }
+        if self.buffer.len() < header_size + size {
+            return Ok(None);
+        }
+
+        let packet: Vec<u8> = self.buffer.drain(..header_size + size).collect();
+        match encoding.decode(&packet[header_size..]) {
+            Some(json) => Ok(Some(json)),
+            None => Err(io::Error::new(ErrorKind::InvalidData, "malformed packet")),
+        }
+    }
+}
+
+// InvalidData if the payload doesn't fit in a frame of the encoding
+fn frame(encoding: Encoding, payload: &[u8]) -> io::Result<Vec<u8>> {
+    encoding.frame(payload).ok_or_else(|| {
+        let message = format!("packet of {} bytes too large to be sent", payload.len());
+        io::Error::new(ErrorKind::InvalidData, message)
+    })
+}
+
+// an error means the packet wasn't sent, the connection can't be used anymore
+pub async fn write_packet_from_json<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    json: &str,
+) -> io::Result<()> {
+    let payload = match stream.encoding {
+        Encoding::Json => json.as_bytes().to_vec(),
+        encoding => {
+            let json = serde_json::from_str(json).map_err(io::Error::from)?;
+            encoding.encode(&json)
+        }
+    };
+    let packet = frame(stream.encoding, &payload)?;
+    stream.stream.write_all(&packet).await?;
+    stream.stream.flush().await
+}
+
+pub async fn write_packet_from_code<S: AsyncStream>(
+    stream: &mut PacketStream<S>,
+    code: u64,
+    size: u16,
+) -> io::Result<()> {
+    if is_error(code) && code != SERV_SHUTDOWN {
+        METRICS.error_sent(code);
+        debug!("error status {code} sent");
+    }
+    let packet = match stream.encoding {
+        Encoding::Json => {
+            let mut packet = size.to_be_bytes().to_vec();
+            packet.extend_from_slice(json!({ "status": code }).to_string().as_bytes());
+            packet
+        }
+        encoding => frame(encoding, &encoding.encode(&json!({ "status": code })))?,
+    };
+    stream.stream.write_all(&packet).await?;
+    stream.stream.flush().await
+}
+
+// cancel safe: the connections wait for a packet and for the game packets or the notices
+// at the same time, the bytes of a packet received so far stay in the buffer
+// UnexpectedEof once the client closed the connection
+pub async fn read_packet<S: AsyncStream>(stream: &mut PacketStream<S>) -> io::Result<Value> {
+    loop {
+        if let Some(packet) = stream.next_packet()? {
+            return Ok(packet);
+        }
+        let mut chunk = [0; READ_CHUNK_SIZE];
+        let read = stream.stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+        stream.buffer.extend_from_slice(&chunk[..read]);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::server::State;
+use crate::storage::unix_time;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::interval;
+use tracing::{debug, info, warn};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SweepStats {
+    pub games_removed: usize,
+    pub players_removed: usize,
+    // game tasks stopped because their game expired or is gone from the storage
+    pub tasks_stopped: usize,
+}
+
+// periodically remove expired players and games, never returns
+pub async fn run_sweeper(state: Arc<State>) {
+    let mut ticks = interval(Duration::from_secs(state.expiry.sweep_interval));
+    let mut total = SweepStats::default();
+    loop {
+        ticks.tick().await;
+
+        let start = Instant::now();
+        match sweep(&state).await {
+            Ok(stats) => {
+                total.games_removed += stats.games_removed;
+                total.players_removed += stats.players_removed;
+                total.tasks_stopped += stats.tasks_stopped;
+                if stats == SweepStats::default() {
+                    debug!("sweep: nothing expired ({:?})", start.elapsed());
+                } else {
+                    info!(
+                        "sweep: removed {} games, {} players, {} game tasks stopped in {:?} (total: {} games, {} players, {} game tasks)",
+                        stats.games_removed,
+                        stats.players_removed,
+                        stats.tasks_stopped,
+                        start.elapsed(),
+                        total.games_removed,
+                        total.players_removed,
+                        total.tasks_stopped
+                    );
+                }
+            }
+            Err(e) => warn!("sweep failed: {e}"),
+        }
+    }
+}
+
+// remove the games without activity for game_ttl seconds, then the players without
+// activity for player_ttl seconds (or whose session expired) that are not part of a
+// remaining game, the accounts are kept
+pub async fn sweep(state: &State) -> anyhow::Result<SweepStats> {
+    let mut stats = SweepStats::default();
+    let now = unix_time();
+    let mut store = state.storage.get_connection()?;
+
+    let mut live_games = HashSet::new();
+    let mut live_players = HashSet::new();
+    for game_token in store.games()? {
+        let expired = match store.game_info(&game_token)? {
+            Some(game_info) => game_info.last_activity + state.expiry.game_ttl <= now,
+            // half deleted game
+            None => true,
+        };
+
+        if expired {
+            // the game task is stopped first so that it can't save the game again
+            let game = state.state.lock().await.remove(&game_token);
+            if let Some(game) = game {
+                game.stop().await;
+                stats.tasks_stopped += 1;
+            }
+            store.remove_game(&game_token)?;
+            stats.games_removed += 1;
+        } else {
+            for (player_token, _) in store.game_players(&game_token)? {
+                live_players.insert(player_token);
+            }
+            live_games.insert(game_token);
+        }
+    }
+
+    // keep the state map consistent with the storage, games created during the sweep
+    // are not in live_games but exist in the storage
+    let orphans: Vec<_> = {
+        let mut lock = state.state.lock().await;
+        let orphan_tokens: Vec<String> = lock
+            .keys()
+            .filter(|game_token| {
+                !live_games.contains(*game_token) && !store.game_exists(game_token).unwrap_or(true)
+            })
+            .cloned()
+            .collect();
+        orphan_tokens
+            .iter()
+            .filter_map(|game_token| lock.remove(game_token))
+            .collect()
+    };
+    for game in orphans {
+        game.stop().await;
+        stats.tasks_stopped += 1;
+    }
+
+    for (player_token, infos) in store.players()? {
+        let expired =
+            infos.last_activity + state.expiry.player_ttl <= now || infos.session_expired(now);
+        if expired && !live_players.contains(&player_token) {
+            store.remove_player(&player_token)?;
+            stats.players_removed += 1;
+        }
+    }
+
+    Ok(stats)
+}
@@ -0,0 +1,33 @@
+use anyhow::{bail, Context};
+use rustls_pemfile::{certs, private_key};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::TlsAcceptor;
+
+// build a tls acceptor from a pem certificate chain and a pem private key (pkcs8, pkcs1 or sec1)
+pub fn load_acceptor(cert_path: &Path, key_path: &Path) -> anyhow::Result<TlsAcceptor> {
+    let mut cert_reader = BufReader::new(
+        File::open(cert_path).with_context(|| format!("can't open {}", cert_path.display()))?,
+    );
+    let cert_chain = certs(&mut cert_reader).collect::<Result<Vec<_>, _>>()?;
+    if cert_chain.is_empty() {
+        bail!("no certificate found in {}", cert_path.display());
+    }
+
+    let mut key_reader = BufReader::new(
+        File::open(key_path).with_context(|| format!("can't open {}", key_path.display()))?,
+    );
+    let key = match private_key(&mut key_reader)? {
+        Some(k) => k,
+        None => bail!("no private key found in {}", key_path.display()),
+    };
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
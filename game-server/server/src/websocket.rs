@@ -0,0 +1,96 @@
+// websocket connections: each text frame carries one json packet, the frames are bridged to
+// the length prefixed packets of the tcp protocol so the same handlers serve both (json only,
+// the frames already carry their length)
+use crate::handler::handle_player;
+use crate::server::State;
+use crate::storage::Storage;
+use crate::stream::{AsyncStream, PacketStream};
+use futures_util::{SinkExt, StreamExt};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, info};
+
+// room for a packet of the maximum size (and its length) in each direction
+const BRIDGE_CAPACITY: usize = u16::MAX as usize + 2;
+
+pub async fn handle_websocket<S: AsyncStream>(
+    state: Arc<State>,
+    stream: S,
+    ip: IpAddr,
+    store: &mut dyn Storage,
+) -> anyhow::Result<()> {
+    let handshake = tokio_tungstenite::accept_async(stream);
+    let websocket = match timeout(state.handshake_timeout, handshake).await {
+        Ok(Ok(ws)) => ws,
+        // not a websocket client, drop the connection
+        Ok(Err(e)) => {
+            info!("websocket handshake failed: {e}");
+            return Ok(());
+        }
+        Err(_) => {
+            info!("websocket handshake timed out");
+            return Ok(());
+        }
+    };
+    let (handler_side, bridge_side) = io::duplex(BRIDGE_CAPACITY);
+
+    let bridge = async move {
+        let (mut sink, mut frames) = websocket.split();
+        let (mut reader, mut writer) = io::split(bridge_side);
+
+        // frames from the client to the handler
+        let incoming = async {
+            while let Some(Ok(message)) = frames.next().await {
+                let packet = match message {
+                    Message::Text(text) => text.into_bytes(),
+                    Message::Close(_) => break,
+                    // pings are answered by tungstenite
+                    _ => continue,
+                };
+                let size = match u16::try_from(packet.len()) {
+                    Ok(s) => s,
+                    Err(_) => {
+                        debug!("websocket frame of {} bytes dropped", packet.len());
+                        break;
+                    }
+                };
+                if writer.write_all(&size.to_be_bytes()).await.is_err()
+                    || writer.write_all(&packet).await.is_err()
+                {
+                    break;
+                }
+            }
+        };
+
+        // packets from the handler to the client
+        let outgoing = async {
+            let mut packet_size = [0; 2];
+            while reader.read_exact(&mut packet_size).await.is_ok() {
+                let mut packet = vec![0; u16::from_be_bytes(packet_size) as usize];
+                if reader.read_exact(&mut packet).await.is_err() {
+                    break;
+                }
+                let text = String::from_utf8_lossy(&packet).into_owned();
+                if sink.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+            }
+            // the handler closed the connection
+            let _ = sink.close().await;
+        };
+
+        tokio::select! {
+            _ = incoming => (),
+            _ = outgoing => (),
+        }
+    };
+
+    let (result, _) = tokio::join!(
+        handle_player(state, PacketStream::json_only(handler_side), ip, store),
+        bridge
+    );
+    result
+}
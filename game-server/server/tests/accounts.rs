@@ -0,0 +1,105 @@
+mod common;
+
+use common::{start_server, test_config, Client};
+use game_server::storage::{unix_time, PlayerInfos};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+
+async fn credentials(
+    client: &mut Client,
+    request_type: u64,
+    pseudo: &str,
+    password: &str,
+) -> Value {
+    client
+        .request(json!({
+            "request_type": request_type,
+            "pseudo": pseudo,
+            "password": password,
+        }))
+        .await
+}
+
+async fn create_game(client: &mut Client, player_token: &Value) -> Value {
+    client
+        .request(json!({ "request_type": GM_CREAT, "player_token": player_token }))
+        .await
+}
+
+#[tokio::test]
+async fn registered_player_logs_in_with_his_password() {
+    let (addr, _) = start_server(test_config()).await;
+
+    let mut client = Client::connect(addr).await;
+    let response = credentials(&mut client, REGISTER, "alice", "correct horse").await;
+    assert_eq!(response["status"], OK_REGISTER);
+    assert!(response["expires"].as_u64().unwrap() > unix_time());
+
+    // another connection, later
+    let mut client = Client::connect(addr).await;
+    let response = credentials(&mut client, LOGIN, "Alice", "wrong password").await;
+    assert_eq!(response["status"], ERR_INV_LOGIN);
+    let response = credentials(&mut client, LOGIN, "nobody", "correct horse").await;
+    assert_eq!(response["status"], ERR_INV_LOGIN);
+
+    let response = credentials(&mut client, LOGIN, "Alice", "correct horse").await;
+    assert_eq!(response["status"], OK_LOGIN);
+    // the session token is a player token
+    let response = create_game(&mut client, &response["player_token"]).await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+}
+
+#[tokio::test]
+async fn registered_pseudos_are_unique() {
+    let (addr, _) = start_server(test_config()).await;
+    let mut client = Client::connect(addr).await;
+
+    let response = credentials(&mut client, REGISTER, "bob", "password1").await;
+    assert_eq!(response["status"], OK_REGISTER);
+    let response = credentials(&mut client, REGISTER, "BOB", "password2").await;
+    assert_eq!(response["status"], ERR_PSEUD_TAKEN);
+
+    // anonymous players can't use it either
+    let response = client
+        .request(json!({ "request_type": PL_CREAT, "pseudo": "Bob" }))
+        .await;
+    assert_eq!(response["status"], ERR_PSEUD_TAKEN);
+}
+
+#[tokio::test]
+async fn invalid_credentials_are_rejected() {
+    let (addr, _) = start_server(test_config()).await;
+    let mut client = Client::connect(addr).await;
+
+    let response = credentials(&mut client, REGISTER, "carol", "short").await;
+    assert_eq!(response["status"], ERR_INV_PASSWD);
+    let response = credentials(&mut client, REGISTER, "not valid", "password").await;
+    assert_eq!(response["status"], ERR_INV_PSEUD);
+    let response = client
+        .request(json!({ "request_type": LOGIN, "pseudo": "carol" }))
+        .await;
+    assert_eq!(response["status"], ERR_MAL_REQ);
+}
+
+#[tokio::test]
+async fn expired_session_is_rejected() {
+    let (addr, state) = start_server(test_config()).await;
+    let mut store = state.storage.get_connection().unwrap();
+    let session_token = "00000000-0000-0000-0000-000000000000";
+    store
+        .insert_player(
+            session_token,
+            &PlayerInfos {
+                pseudo: "dave".into(),
+                hosting: 0,
+                last_activity: unix_time(),
+                session_expiry: Some(unix_time() - 1),
+            },
+        )
+        .unwrap();
+
+    let mut client = Client::connect(addr).await;
+    let response = create_game(&mut client, &json!(session_token)).await;
+    assert_eq!(response["status"], ERR_INV_PL_TOK);
+}
@@ -0,0 +1,314 @@
+// admin console: authentication, inspection of the games, kick, end and notices
+mod common;
+
+use common::{duel_config, start_server, Client, StartedGame};
+use game_server::admin::serve_admin;
+use game_server::server::State;
+use net_utils::encoding::Encoding;
+use net_utils::packet::game_data_code::GM_DATA_SKIP;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout};
+
+const TOKEN: &str = "0123456789abcdef";
+
+async fn start_admin(state: &Arc<State>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve_admin(listener, Arc::clone(state), TOKEN.into()));
+    addr
+}
+
+struct Admin {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Admin {
+    async fn connect(addr: SocketAddr, token: &str) -> (Self, String) {
+        let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        let mut admin = Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        };
+        let (_, status) = admin.command(&format!("auth {token}")).await;
+        (admin, status)
+    }
+
+    // output lines and the final ok or error line
+    async fn command(&mut self, command: &str) -> (Vec<String>, String) {
+        self.writer
+            .write_all(format!("{command}\n").as_bytes())
+            .await
+            .unwrap();
+        let mut output = vec![];
+        while let Some(line) = self.lines.next_line().await.unwrap() {
+            if line == "ok" || line.starts_with("error: ") {
+                return (output, line);
+            }
+            output.push(line);
+        }
+        panic!("admin connection closed after {output:?}");
+    }
+
+    async fn ok(&mut self, command: &str) -> Vec<String> {
+        let (output, status) = self.command(command).await;
+        assert_eq!(status, "ok", "{command}");
+        output
+    }
+
+    async fn closed(&mut self) -> bool {
+        self.lines.next_line().await.unwrap().is_none()
+    }
+}
+
+#[tokio::test]
+async fn the_console_needs_the_token() {
+    let (_, state) = start_server(duel_config()).await;
+    let addr = start_admin(&state).await;
+
+    let (mut admin, status) = Admin::connect(addr, "0123456789abcdeX").await;
+    assert_eq!(status, "error: invalid token");
+    assert!(admin.closed().await);
+
+    let (mut admin, status) = Admin::connect(addr, TOKEN).await;
+    assert_eq!(status, "ok");
+    assert!(admin.ok("games").await.is_empty());
+    let (_, status) = admin.command("shutdown").await;
+    assert!(status.starts_with("error: unknown command"));
+    let (_, status) = admin.command("game unknown").await;
+    assert_eq!(status, "error: no game unknown");
+    admin.ok("quit").await;
+    assert!(admin.closed().await);
+}
+
+#[tokio::test]
+async fn long_lines_are_refused_before_the_authentication() {
+    let (_, state) = start_server(duel_config()).await;
+    let (reader, mut writer) = TcpStream::connect(start_admin(&state).await)
+        .await
+        .unwrap()
+        .into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    // no newline, the console doesn't buffer it all waiting for one
+    writer.write_all(&[b'a'; 10_000]).await.unwrap();
+    let line = lines.next_line().await.unwrap();
+    assert_eq!(line.as_deref(), Some("error: line too long"));
+    assert!(lines.next_line().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn games_and_players_are_listed() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let game = StartedGame::new(addr).await;
+
+    let games = admin.ok("games").await;
+    assert_eq!(games.len(), 1);
+    let expected = format!(
+        "{} started players=2/2 turn={} turns=0 spectators=0",
+        game.game_token, game.player_turn
+    );
+    assert_eq!(games[0], expected);
+
+    let players = admin.ok("players").await;
+    let token = &game.game_token;
+    assert_eq!(
+        players,
+        [
+            format!("{token} 1 host mag hp=80 connected"),
+            format!("{token} 2 guest bar hp=100 connected"),
+        ]
+    );
+
+    let output = admin.ok(&format!("game {token}")).await;
+    assert_eq!(
+        output[0],
+        format!(
+            "started=true fog_of_war=false turn={} turns=0 spectators=0",
+            game.player_turn
+        )
+    );
+    let without_token: Vec<&str> = players.iter().map(|p| &p[token.len() + 1..]).collect();
+    assert_eq!(output[1..3], without_token[..]);
+    assert_eq!(output[3..], ["10", "02"]);
+}
+
+#[tokio::test]
+async fn kicked_players_lose_and_go_back_to_the_lobby() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut game = StartedGame::new(addr).await;
+    let token = game.game_token.clone();
+    let (_, status) = admin.command(&format!("kick {token} nobody")).await;
+    assert_eq!(status, "error: no such player in the game");
+    admin.ok(&format!("kick {token} guest")).await;
+
+    assert_eq!(game.guest.client.recv().await["status"], GM_KICKED);
+    // the host sees the guest die, then wins
+    let response = game.host.client.recv().await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(response["data_type"], GM_DATA_SKIP);
+    assert_eq!(response["enemy"], json!(["2", 0]));
+    assert_eq!(response["changes"], json!([[1, 1, "0"]]));
+    let response = game.host.client.recv().await;
+    assert_eq!(response["status"], OK_GM_OVER);
+    assert_eq!(response["winner"], "1");
+    assert!(!state.state.lock().await.contains_key(&token));
+
+    // the kicked player can play another game
+    let create = json!({ "request_type": GM_CREAT, "player_token": game.guest.token });
+    assert_eq!(
+        game.guest.client.request(create).await["status"],
+        OK_GM_CREAT
+    );
+}
+
+#[tokio::test]
+async fn kicked_hosts_can_host_another_game() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut game = StartedGame::new(addr).await;
+    let token = game.game_token.clone();
+    admin.ok(&format!("kick {token} host")).await;
+
+    assert_eq!(game.host.client.recv().await["status"], GM_KICKED);
+    assert_eq!(game.guest.client.recv().await["status"], OK_GM_DATA);
+    let response = game.guest.client.recv().await;
+    assert_eq!(response["status"], OK_GM_OVER);
+    assert_eq!(response["winner"], "2");
+    assert!(!state.state.lock().await.contains_key(&token));
+
+    // the end of the first game doesn't free the host of the second one
+    let create = json!({ "request_type": GM_CREAT, "player_token": game.host.token });
+    let response = game.host.client.request(create.clone()).await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+    let mut other = Client::connect(addr).await;
+    assert_eq!(other.request(create).await["status"], ERR_WRONG_STATE);
+}
+
+#[tokio::test]
+async fn ended_games_have_no_winner() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut game = StartedGame::new(addr).await;
+    let token = game.game_token.clone();
+
+    admin.ok(&format!("end {token}")).await;
+    for player in [&mut game.host, &mut game.guest] {
+        let response = player.client.recv().await;
+        assert_eq!(response, json!({ "status": OK_GM_OVER, "winner": "" }));
+        // back in the lobby
+        let create = json!({ "request_type": GM_CREAT, "player_token": player.token });
+        assert_eq!(player.client.request(create).await["status"], OK_GM_CREAT);
+    }
+    let (_, status) = admin.command(&format!("game {token}")).await;
+    assert_eq!(status, format!("error: no game {token}"));
+}
+
+#[tokio::test]
+async fn notices_reach_every_connection() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut lobby = Client::connect(addr).await;
+    lobby.create_player("lobby").await;
+    let mut game = StartedGame::new(addr).await;
+
+    let output = admin.ok("notice restart in 5 minutes").await;
+    assert_eq!(output, ["sent to 3 connections"]);
+    let notice = json!({ "status": SERV_NOTICE, "message": "restart in 5 minutes" });
+    for client in [&mut lobby, &mut game.host.client, &mut game.guest.client] {
+        assert_eq!(client.recv().await, notice);
+    }
+    // the connections keep working
+    let leaderboard = json!({ "request_type": LEADERBOARD });
+    assert_eq!(lobby.request(leaderboard).await["status"], OK_LEADERBOARD);
+}
+
+// the bytes of a packet received before a notice are kept
+#[tokio::test]
+async fn notices_dont_cut_packets() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let payload = Encoding::Json.encode(&json!({ "request_type": LEADERBOARD }));
+    let packet = Encoding::Json.frame(&payload).unwrap();
+
+    let (start, end) = packet.split_at(5);
+    stream.write_all(start).await.unwrap();
+    sleep(Duration::from_millis(50)).await;
+    admin.ok("notice hello").await;
+    assert_eq!(read_json(&mut stream).await["status"], SERV_NOTICE);
+    stream.write_all(end).await.unwrap();
+    let response = timeout(Duration::from_secs(5), read_json(&mut stream)).await;
+    assert_eq!(response.unwrap()["status"], OK_LEADERBOARD);
+}
+
+async fn read_json(stream: &mut TcpStream) -> Value {
+    let size = stream.read_u16().await.unwrap();
+    let mut packet = vec![0; size as usize];
+    stream.read_exact(&mut packet).await.unwrap();
+    Encoding::Json.decode(&packet).unwrap()
+}
+
+#[tokio::test]
+async fn players_kicked_before_the_start_leave_the_game() {
+    let (addr, state) = start_server(duel_config()).await;
+    let (mut admin, _) = Admin::connect(start_admin(&state).await, TOKEN).await;
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    let token = response["game_token"].as_str().unwrap().to_string();
+    let join = |player_token: &str| {
+        json!({
+            "request_type": GM_JOIN,
+            "player_token": player_token,
+            "game_token": token,
+        })
+    };
+    let mut guest = Client::connect(addr).await;
+    let guest_token = guest.create_player("guest").await;
+    assert_eq!(
+        guest.request(join(&guest_token)).await["status"],
+        OK_GM_JOIN
+    );
+    assert_eq!(host.recv().await["status"], OK_GM_JOIN);
+
+    admin.ok(&format!("kick {token} guest")).await;
+    assert_eq!(guest.recv().await["status"], GM_KICKED);
+    let response = host.recv().await;
+    assert_eq!(response["status"], GM_PL_LEFT);
+    assert_eq!(response["pseudo"], "guest");
+    assert_eq!(response["player_vec"], json!([["1", "host", "", "1", ""]]));
+
+    // the seat is free again, the kicked player is back in the lobby
+    let mut other = Client::connect(addr).await;
+    let other_token = other.create_player("other").await;
+    let response = other.request(join(&other_token)).await;
+    assert_eq!(response["status"], OK_GM_JOIN);
+    assert_eq!(response["player_vec"][1][0], "2");
+    assert_eq!(host.recv().await["status"], OK_GM_JOIN);
+    let create = json!({ "request_type": GM_CREAT, "player_token": guest_token });
+    assert_eq!(guest.request(create).await["status"], OK_GM_CREAT);
+
+    // the game ends with its host
+    admin.ok(&format!("kick {token} host")).await;
+    assert_eq!(host.recv().await["status"], GM_KICKED);
+    assert_eq!(other.recv().await["status"], GM_PL_LEFT);
+    let response = other.recv().await;
+    assert_eq!(response["status"], OK_GM_OVER);
+    assert_eq!(response["winner"], "");
+    assert!(!state.state.lock().await.contains_key(&token));
+    let create = json!({ "request_type": GM_CREAT, "player_token": host_token });
+    assert_eq!(host.request(create).await["status"], OK_GM_CREAT);
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{duel_config, start_server, Client};
+use game_server::bot::{choose_action, Difficulty, Enemy};
+use net_utils::map::{GameDataType, GameMap, Point};
+use net_utils::packet::game_data_code::GM_DATA_ATK;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use net_utils::rules::chebyshev_distance;
+use serde_json::{json, Value};
+
+const BARBARIAN: (u8, u8, u8, u8) = (10, 100, 4, 1);
+const BOWMAN: (u8, u8, u8, u8) = (6, 70, 2, 6);
+
+fn enemy(x: i16, y: i16, hp: u8) -> Enemy {
+    Enemy {
+        position: Point(x, y),
+        hp,
+    }
+}
+
+#[test]
+fn bot_approaches_then_attacks() {
+    let map: GameMap = "1000\nRR00\n0002".parse().unwrap();
+    let enemies = [enemy(0, 0, 80)];
+    match choose_action(&map, '2', BARBARIAN, &enemies, Difficulty::Normal) {
+        // around the rocks, next to the enemy
+        GameDataType::Movement(dest) => assert_eq!(dest, Point(1, 0)),
+        _ => panic!("the bot should move"),
+    }
+
+    let map: GameMap = "1200\n0000".parse().unwrap();
+    match choose_action(&map, '2', BARBARIAN, &enemies, Difficulty::Normal) {
+        GameDataType::Attack(target) => assert_eq!(target, Point(0, 0)),
+        _ => panic!("the bot should attack"),
+    }
+}
+
+#[test]
+fn hard_bot_attacks_the_weakest_enemy() {
+    let map: GameMap = "102\n000\n003".parse().unwrap();
+    let enemies = [enemy(0, 0, 50), enemy(2, 2, 10)];
+    match choose_action(&map, '2', BOWMAN, &enemies, Difficulty::Hard) {
+        GameDataType::Attack(target) => assert_eq!(target, Point(2, 2)),
+        _ => panic!("the bot should attack"),
+    }
+}
+
+#[test]
+fn easy_bot_moves_one_tile() {
+    let map: GameMap = "10000\n00000\n00002".parse().unwrap();
+    let enemies = [enemy(0, 0, 80)];
+    for _ in 0..10 {
+        match choose_action(&map, '2', BARBARIAN, &enemies, Difficulty::Easy) {
+            GameDataType::Movement(dest) => {
+                assert_eq!(chebyshev_distance(dest, Point(4, 2)), 1)
+            }
+            _ => panic!("the bot should move"),
+        }
+    }
+}
+
+fn add_bot(player_token: &str, game_token: &str) -> Value {
+    json!({
+        "request_type": ADD_BOT,
+        "player_token": player_token,
+        "game_token": game_token,
+        "difficulty": "hard",
+    })
+}
+
+// the host plays against a bot until one of them wins
+#[tokio::test]
+async fn game_against_a_bot() {
+    let mut config = duel_config();
+    config.game.bot_delay = 0;
+    let (addr, _) = start_server(config).await;
+
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    let game_token = response["game_token"].as_str().unwrap().to_string();
+
+    // bots are added from the game
+    let mut other = Client::connect(addr).await;
+    let other_token = other.create_player("other").await;
+    let response = other.request(add_bot(&other_token, &game_token)).await;
+    assert_eq!(response["status"], ERR_WRONG_STATE);
+
+    let response = host.request(add_bot(&host_token, &game_token)).await;
+    assert_eq!(response["status"], OK_GM_JOIN);
+    assert_eq!(response["pseudo"], "bot2");
+    assert_eq!(response["player_vec"][1][1], "bot2");
+    let response = host.recv().await;
+    assert_eq!(response["status"], OK_CHAR_CHOOSING);
+    assert_eq!(response["pseudo"], "bot2");
+    let response = host.request(add_bot(&host_token, &game_token)).await;
+    assert_eq!(response["status"], ERR_GM_FULL);
+
+    let choose = json!({
+        "request_type": CHAR_CHOOSING,
+        "player_token": host_token,
+        "game_token": game_token,
+        "character": "bar",
+    });
+    assert_eq!(host.request(choose).await["status"], OK_CHAR_CHOOSING);
+    let start = json!({
+        "request_type": GM_START,
+        "player_token": host_token,
+        "game_token": game_token,
+    });
+    let response = host.request(start).await;
+    assert_eq!(response["status"], OK_GM_START);
+
+    let mut player_turn = response["player_turn"].as_str().unwrap().to_string();
+    let mut bot_turns = 0;
+    loop {
+        let response = if player_turn == "1" {
+            let attack = json!({
+                "request_type": GM_DATA,
+                "gm_code": GM_DATA_ATK,
+                "target": [1, 1],
+                "player_token": host_token,
+                "game_token": game_token,
+            });
+            host.request(attack).await
+        } else {
+            bot_turns += 1;
+            let response = host.recv().await;
+            if response["status"] == OK_GM_DATA {
+                // the bot is next to the host, it attacks
+                assert_eq!(response["player_num"], "2");
+                assert_eq!(response["data_type"], GM_DATA_ATK);
+            }
+            response
+        };
+
+        match response["status"].as_u64() {
+            Some(OK_GM_DATA) => {
+                player_turn = response["player_turn"].as_str().unwrap().into();
+                if response["enemy"][1] == 0 {
+                    assert_eq!(host.recv().await["status"], OK_GM_OVER);
+                    break;
+                }
+            }
+            Some(OK_GM_OVER) => break,
+            status => panic!("unexpected status {status:?}"),
+        }
+    }
+    assert!(bot_turns > 0);
+}
@@ -0,0 +1,194 @@
+// full game played with the client library, responses and events included
+mod common;
+
+use common::{duel_config, start_server};
+use game_client_lib::{Error, Event, Events, GameClient};
+use net_utils::encoding::{Encoding, PROTOCOL_VERSION};
+use net_utils::map::{GameDataType, Point};
+use net_utils::packet::capabilities::SPECTATE;
+use net_utils::packet::game_data_code::GM_DATA_ATK;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// connection of a client library to a fake server sending packets a real one can't be made
+// to send when wanted
+async fn fake_server() -> (GameClient, Events, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (client, events) = GameClient::connect(addr).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    (client, events, server)
+}
+
+// request of the client, in json
+async fn read_request(server: &mut TcpStream) -> Value {
+    let mut header = [0; 2];
+    server.read_exact(&mut header).await.unwrap();
+    let mut packet = vec![0; Encoding::Json.packet_size(&header)];
+    server.read_exact(&mut packet).await.unwrap();
+    serde_json::from_slice(&packet).unwrap()
+}
+
+async fn write_json(server: &mut TcpStream, json: Value) {
+    let packet = Encoding::Json.frame(json.to_string().as_bytes()).unwrap();
+    server.write_all(&packet).await.unwrap();
+}
+
+// next event, skipping nothing: the test expects every event in order
+async fn next(events: &mut Events) -> Event {
+    events.next().await.expect("connection closed")
+}
+
+#[tokio::test]
+async fn game_with_the_client_library() {
+    let (addr, _) = start_server(duel_config()).await;
+    let (mut host, mut host_events) = GameClient::connect(addr).await.unwrap();
+    let (mut guest, mut guest_events) = GameClient::connect(addr).await.unwrap();
+    let (mut spectator, mut spectator_events) = GameClient::connect(addr).await.unwrap();
+    // the guest plays in msgpack, the others in json
+    guest.hello("guest", Encoding::MessagePack).await.unwrap();
+    let hello = spectator.hello("spectator", Encoding::Json).await.unwrap();
+    assert_eq!(hello.version, PROTOCOL_VERSION);
+    assert!(hello.supports(SPECTATE));
+
+    let status = host.create_player("bad pseudo").await.unwrap_err().status();
+    assert_eq!(status, Some(ERR_INV_PSEUD));
+    host.create_player("host").await.unwrap();
+    guest.create_player("guest").await.unwrap();
+    spectator.create_player("spectator").await.unwrap();
+
+    let game_token = host.create_game().await.unwrap();
+    assert_eq!(host.player_num(), "1");
+    let unknown_token = "00000000-0000-0000-0000-000000000000";
+    match guest.join_game(unknown_token).await {
+        Err(Error::Status(ERR_INV_GM_TOK)) => (),
+        r => panic!("unexpected join result {:?}", r.map(|_| ())),
+    }
+
+    let joined = guest.join_game(&game_token).await.unwrap();
+    assert_eq!(guest.player_num(), "2");
+    assert_eq!(joined.players.len(), 2);
+    assert!(joined.players[0].host);
+    match next(&mut host_events).await {
+        Event::PlayerJoined(joined) => assert_eq!(joined.pseudo, "guest"),
+        e => panic!("unexpected event {e:?}"),
+    }
+    let spectating = spectator.spectate(&game_token).await.unwrap();
+    assert!(!spectating.started);
+
+    host.choose_character("bar").await.unwrap();
+    guest.choose_character("bar").await.unwrap();
+    // the spectator gets both choices
+    for (events, count) in [
+        (&mut guest_events, 1),
+        (&mut host_events, 1),
+        (&mut spectator_events, 2),
+    ] {
+        for _ in 0..count {
+            match next(events).await {
+                Event::CharacterChosen(choice) => assert_eq!(choice.character, "bar"),
+                e => panic!("unexpected event {e:?}"),
+            }
+        }
+    }
+
+    let start = host.start_game().await.unwrap();
+    for events in [&mut guest_events, &mut spectator_events] {
+        match next(events).await {
+            Event::GameStarted(started) => assert_eq!(started.map, start.map),
+            e => panic!("unexpected event {e:?}"),
+        }
+    }
+
+    // both players attack each other until one of them dies
+    let mut turn = start.player_turn;
+    let winner = loop {
+        let (player, other_events, target) = match &*turn {
+            "1" => (&mut host, &mut guest_events, Point(1, 1)),
+            _ => (&mut guest, &mut host_events, Point(0, 0)),
+        };
+        let gm_data = player
+            .send_game_data(GameDataType::Attack(target))
+            .await
+            .unwrap();
+        assert_eq!(gm_data.data_type, GM_DATA_ATK);
+        match next(other_events).await {
+            Event::GameData(data) => assert_eq!(data.changes, gm_data.changes),
+            e => panic!("unexpected event {e:?}"),
+        }
+        if gm_data.enemy.1 == 0 {
+            break gm_data.player_num;
+        }
+        turn = gm_data.player_turn;
+    };
+
+    for events in [&mut host_events, &mut guest_events, &mut spectator_events] {
+        loop {
+            match next(events).await {
+                Event::GameOver(game_over) => {
+                    assert_eq!(game_over.winner, winner);
+                    break;
+                }
+                // the spectator also gets every action
+                Event::GameData(_) => continue,
+                e => panic!("unexpected event {e:?}"),
+            }
+        }
+    }
+
+    host.terminate().await.unwrap();
+    assert!(host_events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn unsolicited_statuses_are_events() {
+    let (mut client, mut events, mut server) = fake_server().await;
+    let fake = tokio::spawn(async move {
+        assert_eq!(read_request(&mut server).await["pseudo"], "player");
+        // sent before the response, e.g. by an operator
+        write_json(&mut server, json!({ "status": GM_KICKED })).await;
+        write_json(&mut server, json!({ "status": SERV_SHUTDOWN })).await;
+        let token = "00000000-0000-0000-0000-000000000000";
+        let response = json!({ "status": OK_PL_CREAT, "player_token": token });
+        write_json(&mut server, response).await;
+        server
+    });
+
+    client.create_player("player").await.unwrap();
+    assert!(matches!(next(&mut events).await, Event::Kicked));
+    assert!(matches!(next(&mut events).await, Event::ServerShutdown));
+    fake.await.unwrap();
+}
+
+#[tokio::test]
+async fn oversized_packets_close_the_connection() {
+    let (mut client, mut events, mut server) = fake_server().await;
+    let fake = tokio::spawn(async move {
+        read_request(&mut server).await;
+        let hello = json!({
+            "status": OK_HELLO,
+            "server": "fake",
+            "version": PROTOCOL_VERSION,
+            "encoding": "msgpack",
+            "versions": [PROTOCOL_VERSION],
+            "encodings": ["json", "msgpack"],
+            "capabilities": [],
+        });
+        write_json(&mut server, hello).await;
+        // length of a 4 GiB msgpack packet, never followed by its bytes
+        let mut request = [0; 64];
+        let _ = server.read(&mut request).await.unwrap();
+        server.write_all(&[0xff; 4]).await.unwrap();
+        server
+    });
+
+    client.hello("client", Encoding::MessagePack).await.unwrap();
+    match client.create_player("player").await {
+        Err(Error::Disconnected) => (),
+        r => panic!("unexpected result {r:?}"),
+    }
+    assert!(events.next().await.is_none());
+    drop(fake.await.unwrap());
+}
@@ -0,0 +1,356 @@
+// helpers shared by the integration tests: in memory server on an ephemeral port and a
+// minimal client speaking the json protocol (over tcp or websocket)
+#![allow(dead_code)]
+
+use futures_util::{SinkExt, StreamExt};
+use game_server::audit::ResultSigner;
+use game_server::config::{Config, StorageBackend};
+use game_server::server::{serve, State};
+use game_server::storage::StorageClient;
+use game_server::stream::{read_packet, write_packet_from_json, PacketStream};
+use net_utils::encoding::{Encoding, PROTOCOL_VERSION};
+use net_utils::packet::game_data_code::GM_DATA_ATK;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::future::pending;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+use tokio::time::timeout;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+// memory storage, no turn timer, no items and limits high enough for tests hammering the
+// server
+pub fn test_config() -> Config {
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::Memory;
+    config.game.turn_timeout = 0;
+    config.game.item_interval = 0;
+    config.limits.requests_per_second = 1000;
+    config.limits.request_burst = 1000;
+    config.limits.ip_requests_per_second = 10000;
+    config.limits.ip_request_burst = 10000;
+    config
+}
+
+// no obstacles on the first and last rows: the players spawn next to each other, the 2 rows
+// map is below the minimum of the config validation which the test servers skip
+pub fn duel_config() -> Config {
+    let mut config = test_config();
+    config.game.map_height = 2;
+    config.game.map_width = 2;
+    config
+}
+
+pub async fn start_server(config: Config) -> (SocketAddr, Arc<State>) {
+    let (addr, _, state) = start_servers(config, false).await;
+    (addr, state)
+}
+
+// (tcp address, websocket address, state)
+pub async fn start_websocket_server(config: Config) -> (SocketAddr, SocketAddr, Arc<State>) {
+    let (addr, ws_addr, state) = start_servers(config, true).await;
+    (addr, ws_addr.unwrap(), state)
+}
+
+async fn start_servers(
+    config: Config,
+    websocket: bool,
+) -> (SocketAddr, Option<SocketAddr>, Arc<State>) {
+    let state = Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        config
+            .results
+            .as_ref()
+            .map(|r| ResultSigner::load(r).unwrap()),
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let ws_listener = match websocket {
+        true => Some(TcpListener::bind("127.0.0.1:0").await.unwrap()),
+        false => None,
+    };
+    let ws_addr = ws_listener.as_ref().map(|l| l.local_addr().unwrap());
+    tokio::spawn(serve(
+        listener,
+        ws_listener,
+        Arc::clone(&state),
+        None,
+        pending(),
+    ));
+    (addr, ws_addr, state)
+}
+
+pub enum Client {
+    Tcp(PacketStream<TcpStream>),
+    // one json packet per text frame
+    WebSocket(Box<WebSocketStream<MaybeTlsStream<TcpStream>>>),
+}
+
+impl Client {
+    pub async fn connect(addr: SocketAddr) -> Self {
+        Self::Tcp(PacketStream::new(TcpStream::connect(addr).await.unwrap()))
+    }
+
+    // from another loopback address than the other clients (127.0.0.1), as if from another
+    // machine
+    pub async fn connect_from(addr: SocketAddr, ip: IpAddr) -> Self {
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.bind(SocketAddr::new(ip, 0)).unwrap();
+        Self::Tcp(PacketStream::new(socket.connect(addr).await.unwrap()))
+    }
+
+    pub async fn connect_websocket(addr: SocketAddr) -> Self {
+        let (stream, _) = connect_async(format!("ws://{addr}")).await.unwrap();
+        Self::WebSocket(Box::new(stream))
+    }
+
+    pub async fn send(&mut self, json: Value) {
+        match self {
+            Self::Tcp(stream) => write_packet_from_json(stream, &json.to_string())
+                .await
+                .unwrap(),
+            Self::WebSocket(stream) => stream.send(Message::Text(json.to_string())).await.unwrap(),
+        }
+    }
+
+    pub async fn recv(&mut self) -> Value {
+        match self {
+            Self::Tcp(stream) => read_packet(stream).await.unwrap(),
+            Self::WebSocket(stream) => loop {
+                match stream.next().await.unwrap().unwrap() {
+                    Message::Text(text) => break serde_json::from_str(&text).unwrap(),
+                    Message::Ping(_) | Message::Pong(_) => continue,
+                    other => panic!("unexpected websocket message {other:?}"),
+                }
+            },
+        }
+    }
+
+    // true once the server closed the connection
+    pub async fn closed(&mut self) -> bool {
+        match self {
+            Self::Tcp(stream) if stream.has_buffered() => false,
+            Self::Tcp(stream) => {
+                let mut buf = [0; 1];
+                matches!(
+                    timeout(Duration::from_secs(5), stream.get_mut().read(&mut buf)).await,
+                    Ok(Ok(0) | Err(_))
+                )
+            }
+            Self::WebSocket(stream) => matches!(
+                timeout(Duration::from_secs(5), stream.next()).await,
+                Ok(None | Some(Err(_)) | Some(Ok(Message::Close(_))))
+            ),
+        }
+    }
+
+    // true if nothing is received for a while
+    pub async fn silent(&mut self) -> bool {
+        match self {
+            Self::Tcp(stream) if stream.has_buffered() => false,
+            Self::Tcp(stream) => {
+                let mut buf = [0; 1];
+                timeout(Duration::from_millis(200), stream.get_mut().peek(&mut buf))
+                    .await
+                    .is_err()
+            }
+            // a message received is consumed
+            Self::WebSocket(stream) => timeout(Duration::from_millis(200), stream.next())
+                .await
+                .is_err(),
+        }
+    }
+
+    pub async fn request(&mut self, json: Value) -> Value {
+        self.send(json).await;
+        self.recv().await
+    }
+
+    // handshake, the following packets use the encoding if the server accepted it
+    pub async fn hello(&mut self, encoding: Encoding) -> Value {
+        let hello = json!({
+            "request_type": HELLO,
+            "client": "tests",
+            "version": PROTOCOL_VERSION,
+            "encoding": encoding.name(),
+        });
+        let response = self.request(hello).await;
+        if let (Self::Tcp(stream), OK_HELLO) = (&mut *self, response["status"].as_u64().unwrap()) {
+            stream.set_encoding(encoding);
+        }
+        response
+    }
+
+    pub async fn create_player(&mut self, pseudo: &str) -> String {
+        let response = self
+            .request(json!({ "request_type": PL_CREAT, "pseudo": pseudo }))
+            .await;
+        assert_eq!(response["status"], OK_PL_CREAT);
+        response["player_token"].as_str().unwrap().into()
+    }
+}
+
+// the other player is told about the character choice
+pub async fn choose_character(
+    client: &mut Client,
+    other: &mut Client,
+    player_token: &str,
+    game_token: &str,
+    character: &str,
+) {
+    let choose = json!({
+        "request_type": CHAR_CHOOSING,
+        "player_token": player_token,
+        "game_token": game_token,
+        "character": character,
+    });
+    assert_eq!(client.request(choose).await["status"], OK_CHAR_CHOOSING);
+    assert_eq!(other.recv().await["status"], OK_CHAR_CHOOSING);
+}
+
+pub struct Player {
+    pub client: Client,
+    pub token: String,
+}
+
+// two players in a started game
+pub struct StartedGame {
+    pub host: Player,
+    pub guest: Player,
+    pub game_token: String,
+    // player number ("1" is the host) of the player who plays first
+    pub player_turn: String,
+}
+
+impl StartedGame {
+    // with two anonymous players
+    pub async fn new(addr: SocketAddr) -> Self {
+        Self::new_with(addr, json!({})).await
+    }
+
+    pub async fn new_with(addr: SocketAddr, options: Value) -> Self {
+        let mut host = Client::connect(addr).await;
+        let host_token = host.create_player("host").await;
+        let mut guest = Client::connect(addr).await;
+        let guest_token = guest.create_player("guest").await;
+        Self::start_with(
+            Player {
+                client: host,
+                token: host_token,
+            },
+            Player {
+                client: guest,
+                token: guest_token,
+            },
+            options,
+        )
+        .await
+    }
+
+    // the host plays a magician, the guest a barbarian
+    pub async fn start(host: Player, guest: Player) -> Self {
+        Self::start_with(host, guest, json!({})).await
+    }
+
+    // options: fields added to the game creation request
+    pub async fn start_with(mut host: Player, mut guest: Player, options: Value) -> Self {
+        let mut create = json!({ "request_type": GM_CREAT, "player_token": host.token });
+        create
+            .as_object_mut()
+            .unwrap()
+            .extend(options.as_object().unwrap().clone());
+        let response = host.client.request(create).await;
+        assert_eq!(response["status"], OK_GM_CREAT);
+        let game_token = response["game_token"].as_str().unwrap().to_string();
+
+        let join = json!({
+            "request_type": GM_JOIN,
+            "player_token": guest.token,
+            "game_token": game_token,
+        });
+        assert_eq!(guest.client.request(join).await["status"], OK_GM_JOIN);
+        assert_eq!(host.client.recv().await["status"], OK_GM_JOIN);
+
+        choose_character(
+            &mut host.client,
+            &mut guest.client,
+            &host.token,
+            &game_token,
+            "mag",
+        )
+        .await;
+        choose_character(
+            &mut guest.client,
+            &mut host.client,
+            &guest.token,
+            &game_token,
+            "bar",
+        )
+        .await;
+
+        let start = json!({
+            "request_type": GM_START,
+            "player_token": host.token,
+            "game_token": game_token,
+        });
+        let response = host.client.request(start).await;
+        assert_eq!(response["status"], OK_GM_START);
+        assert_eq!(guest.client.recv().await["status"], OK_GM_START);
+
+        Self {
+            host,
+            guest,
+            game_token,
+            player_turn: response["player_turn"].as_str().unwrap().into(),
+        }
+    }
+
+    // (current player, other player)
+    pub fn players_by_turn(&mut self) -> (&mut Player, &mut Player) {
+        if self.player_turn == "1" {
+            (&mut self.host, &mut self.guest)
+        } else {
+            (&mut self.guest, &mut self.host)
+        }
+    }
+
+    // on a 2x2 map (players next to each other): the players attack in turn until one
+    // of them dies, returns the number of the winner once both players got the game over
+    pub async fn fight(&mut self) -> String {
+        let game_token = self.game_token.clone();
+        let winner = loop {
+            let player_turn = self.player_turn.clone();
+            let (player, other) = self.players_by_turn();
+            // player 1 spawns in the top left corner, player 2 in the bottom right one
+            let target = if player_turn == "1" { [1, 1] } else { [0, 0] };
+            let attack = json!({
+                "request_type": GM_DATA,
+                "gm_code": GM_DATA_ATK,
+                "target": target,
+                "player_token": player.token,
+                "game_token": game_token,
+            });
+            let response = player.client.request(attack).await;
+            assert_eq!(response["status"], OK_GM_DATA);
+            assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+
+            if response["enemy"][1] == 0 {
+                break player_turn;
+            }
+            self.player_turn = response["player_turn"].as_str().unwrap().into();
+        };
+
+        for client in [&mut self.host.client, &mut self.guest.client] {
+            let response = client.recv().await;
+            assert_eq!(response["status"], OK_GM_OVER);
+            assert_eq!(response["winner"], winner);
+        }
+        winner
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{start_server, test_config, Client, StartedGame};
+use net_utils::packet::game_data_code::GM_DATA_SKIP;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+use std::sync::Arc;
+use tokio::sync::Barrier;
+
+const CONNECTIONS: usize = 16;
+
+// the player whose turn it is plays from many connections at the same time,
+// only one of the actions can be applied
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn simultaneous_actions_play_one_turn() {
+    let (addr, _) = start_server(test_config()).await;
+
+    for _ in 0..5 {
+        let mut game = StartedGame::new(addr).await;
+        let game_token = game.game_token.clone();
+        let player_token = game.players_by_turn().0.token.clone();
+
+        let mut clients = vec![];
+        for _ in 0..CONNECTIONS {
+            let mut client = Client::connect(addr).await;
+            let rejoin = json!({
+                "request_type": GM_JOIN,
+                "player_token": player_token,
+                "game_token": game_token,
+            });
+            assert_eq!(client.request(rejoin).await["status"], OK_GM_JOIN);
+            // state of the started game
+            assert_eq!(client.recv().await["status"], OK_GM_START);
+            clients.push(client);
+        }
+
+        let barrier = Arc::new(Barrier::new(CONNECTIONS));
+        let mut tasks = vec![];
+        for mut client in clients {
+            let barrier = Arc::clone(&barrier);
+            let skip = json!({
+                "request_type": GM_DATA,
+                "gm_code": GM_DATA_SKIP,
+                "player_token": player_token,
+                "game_token": game_token,
+            });
+            tasks.push(tokio::spawn(async move {
+                barrier.wait().await;
+                client.request(skip).await["status"].as_u64().unwrap()
+            }));
+        }
+
+        let mut statuses = vec![];
+        for task in tasks {
+            statuses.push(task.await.unwrap());
+        }
+        let played = statuses.iter().filter(|s| **s == OK_GM_DATA).count();
+        let refused = statuses.iter().filter(|s| **s == ERR_NOT_TURN).count();
+        assert_eq!((played, refused), (1, CONNECTIONS - 1), "{statuses:?}");
+
+        // the other player sees a single turn
+        let other = game.players_by_turn().1;
+        let mut turns = 0;
+        loop {
+            let packet = other.client.recv().await;
+            if packet["status"] == OK_GM_DATA {
+                turns += 1;
+                break;
+            }
+            // rejoin notifications
+            assert_eq!(packet["status"], OK_GM_JOIN);
+        }
+        assert_eq!(turns, 1);
+    }
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use common::{duel_config, test_config};
+
+#[test]
+fn map_sizes_are_checked() {
+    test_config().validate().unwrap();
+    // the 2x2 arena the tests play on is only accepted by the test servers
+    let error = duel_config().validate().unwrap_err().to_string();
+    assert!(error.starts_with("invalid config: map_height"), "{error}");
+
+    for (height, width) in [(2, 10), (51, 10), (5, 1), (5, 51)] {
+        let mut config = test_config();
+        config.game.map_height = height;
+        config.game.map_width = width;
+        let error = config.validate().unwrap_err().to_string();
+        assert!(error.starts_with("invalid config: map_"), "{error}");
+    }
+}
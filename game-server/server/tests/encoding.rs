@@ -0,0 +1,208 @@
+mod common;
+
+use common::{duel_config, start_server, test_config, Client, Player, StartedGame};
+use game_server::handler::handle_player;
+use game_server::server::State;
+use game_server::storage::StorageClient;
+use game_server::stream::{write_packet_from_json, PacketStream};
+use net_utils::encoding::{Encoding, PROTOCOL_VERSION};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::io::ErrorKind;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+async fn msgpack_player(client: &mut Client, pseudo: &str) -> String {
+    let response = client.hello(Encoding::MessagePack).await;
+    assert_eq!(response["status"], OK_HELLO);
+    assert_eq!(response["encoding"], "msgpack");
+    client.create_player(pseudo).await
+}
+
+#[tokio::test]
+async fn full_game_in_msgpack() {
+    let (addr, _) = start_server(duel_config()).await;
+
+    let mut host = Client::connect(addr).await;
+    let host_token = msgpack_player(&mut host, "host").await;
+    // the encoding is per connection
+    let mut guest = Client::connect(addr).await;
+    let guest_token = guest.create_player("guest").await;
+
+    let host = Player {
+        client: host,
+        token: host_token,
+    };
+    let guest = Player {
+        client: guest,
+        token: guest_token,
+    };
+    let mut game = StartedGame::start(host, guest).await;
+    game.fight().await;
+
+    let response = game
+        .host
+        .client
+        .request(json!({ "request_type": TERM_CON }))
+        .await;
+    assert_eq!(response["status"], OK_TERM_CON);
+}
+
+#[tokio::test]
+async fn msgpack_packets_have_a_u32_length() {
+    let (addr, _) = start_server(duel_config()).await;
+    // the handshake itself is json
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let hello = json!({
+        "request_type": HELLO,
+        "client": "tests",
+        "version": PROTOCOL_VERSION,
+        "encoding": "msgpack",
+    });
+    let payload = Encoding::Json.encode(&hello);
+    stream
+        .write_all(&Encoding::Json.frame(&payload).unwrap())
+        .await
+        .unwrap();
+    let size = stream.read_u16().await.unwrap();
+    let mut reply = vec![0; size as usize];
+    stream.read_exact(&mut reply).await.unwrap();
+    let reply = Encoding::Json.decode(&reply).unwrap();
+    assert_eq!(reply["status"], OK_HELLO);
+
+    let request = json!({ "request_type": PL_CREAT, "pseudo": "binary" });
+    let payload = Encoding::MessagePack.encode(&request);
+    stream.write_u32(payload.len() as u32).await.unwrap();
+    stream.write_all(&payload).await.unwrap();
+    let size = stream.read_u32().await.unwrap();
+    let mut response = vec![0; size as usize];
+    stream.read_exact(&mut response).await.unwrap();
+    let response = Encoding::MessagePack.decode(&response).unwrap();
+    assert_eq!(response["status"], OK_PL_CREAT);
+    assert_eq!(response["player_token"].as_str().unwrap().len(), 36);
+}
+
+// PROTO_NEG was replaced by HELLO
+#[tokio::test]
+async fn protocol_negotiation_is_refused() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut client = Client::connect(addr).await;
+
+    for negotiation in [
+        json!({ "request_type": PROTO_NEG, "version": PROTOCOL_VERSION, "encoding": "msgpack" }),
+        json!({ "request_type": PROTO_NEG, "version": PROTOCOL_VERSION }),
+    ] {
+        assert_eq!(client.request(negotiation).await["status"], ERR_UNSUP_PROTO);
+    }
+    // still json
+    let token = client.create_player("player").await;
+    let create = json!({ "request_type": GM_CREAT, "player_token": token });
+    assert_eq!(client.request(create).await["status"], OK_GM_CREAT);
+    let negotiation = json!({ "request_type": PROTO_NEG, "version": PROTOCOL_VERSION });
+    assert_eq!(client.request(negotiation).await["status"], ERR_UNSUP_PROTO);
+    assert_eq!(
+        client.hello(Encoding::MessagePack).await["status"],
+        OK_HELLO
+    );
+}
+
+// status of the reply and whether the server closed the connection after it
+async fn reply_then_closed(stream: &mut TcpStream, encoding: Encoding) -> (Value, bool) {
+    let size = match encoding {
+        Encoding::Json => stream.read_u16().await.unwrap() as usize,
+        Encoding::MessagePack => stream.read_u32().await.unwrap() as usize,
+    };
+    let mut reply = vec![0; size];
+    stream.read_exact(&mut reply).await.unwrap();
+    let reply = encoding.decode(&reply).unwrap();
+    let mut buf = [0; 1];
+    let closed = matches!(
+        timeout(Duration::from_secs(5), stream.read(&mut buf)).await,
+        Ok(Ok(0) | Err(_))
+    );
+    (reply["status"].clone(), closed)
+}
+
+#[tokio::test]
+async fn unreadable_packets_close_the_connection() {
+    let (addr, _) = start_server(duel_config()).await;
+
+    // not json
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let garbage = Encoding::Json.frame(b"\x00{\"request_type\":").unwrap();
+    stream.write_all(&garbage).await.unwrap();
+    let (status, closed) = reply_then_closed(&mut stream, Encoding::Json).await;
+    assert_eq!(status, ERR_MAL_REQ);
+    assert!(closed);
+
+    // larger than a msgpack packet can be, the server doesn't wait for its bytes
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let hello = json!({
+        "request_type": HELLO,
+        "client": "tests",
+        "version": PROTOCOL_VERSION,
+        "encoding": "msgpack",
+    });
+    let payload = Encoding::Json.encode(&hello);
+    stream
+        .write_all(&Encoding::Json.frame(&payload).unwrap())
+        .await
+        .unwrap();
+    let size = stream.read_u16().await.unwrap();
+    let mut reply = vec![0; size as usize];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(Encoding::Json.decode(&reply).unwrap()["status"], OK_HELLO);
+    stream.write_u32(u32::MAX).await.unwrap();
+    let (status, closed) = reply_then_closed(&mut stream, Encoding::MessagePack).await;
+    assert_eq!(status, ERR_MAL_REQ);
+    assert!(closed);
+
+    // the other connections are served
+    let mut client = Client::connect(addr).await;
+    client.create_player("player").await;
+}
+
+#[tokio::test]
+async fn clients_gone_before_the_response_end_the_connection() {
+    let config = test_config();
+    let state = Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ));
+    let mut store = state.storage.get_connection().unwrap();
+
+    // the request is received, the client is gone when the response is written
+    let (mut client, server) = io::duplex(1024);
+    let request = json!({ "request_type": PL_CREAT, "pseudo": "gone" });
+    let payload = Encoding::Json.encode(&request);
+    client
+        .write_all(&Encoding::Json.frame(&payload).unwrap())
+        .await
+        .unwrap();
+    drop(client);
+    let handled = timeout(
+        Duration::from_secs(5),
+        handle_player(
+            state,
+            PacketStream::new(server),
+            "127.0.0.1".parse().unwrap(),
+            &mut *store,
+        ),
+    )
+    .await;
+    handled.unwrap().unwrap();
+
+    // a packet too large for its frame isn't sent
+    let (_client, server) = io::duplex(1024);
+    let mut stream = PacketStream::new(server);
+    let too_large = json!({ "notice": "a".repeat(u16::MAX as usize) }).to_string();
+    let error = write_packet_from_json(&mut stream, &too_large)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
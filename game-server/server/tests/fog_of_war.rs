@@ -0,0 +1,234 @@
+mod common;
+
+use common::{start_server, test_config, Client, StartedGame};
+use net_utils::map::{GameMap, Point, TileChange, HIDDEN_TILE};
+use net_utils::packet::game_data_code::{GM_DATA_ATK, GM_DATA_MOV, GM_DATA_SKIP};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr};
+
+// the players connect from 127.0.0.1
+const SPECTATOR_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+fn map(packet: &Value) -> GameMap {
+    packet["map"].as_str().unwrap().parse().unwrap()
+}
+
+// map seen by the player or spectator, the game data packets only carry the changes
+async fn snapshot(client: &mut Client, player_token: &str, game_token: &str) -> GameMap {
+    let snapshot = json!({
+        "request_type": GM_SNAPSHOT,
+        "player_token": player_token,
+        "game_token": game_token,
+    });
+    let response = client.request(snapshot).await;
+    assert_eq!(response["status"], OK_GM_SNAPSHOT);
+    map(&response)
+}
+
+// every tile further than the vision radius from center is hidden, the others are not
+fn assert_view(map: &GameMap, center: Point, radius: i16) {
+    for y in 0..map.height() as i16 {
+        for x in 0..map.width() as i16 {
+            let hidden = map.tile(Point(x, y)) == Some(HIDDEN_TILE);
+            let visible = (x - center.0).abs() <= radius && (y - center.1).abs() <= radius;
+            assert_eq!(hidden, !visible, "tile ({x}, {y})");
+        }
+    }
+}
+
+#[tokio::test]
+async fn players_only_see_around_them() {
+    let mut config = test_config();
+    config.game.vision_radius = 2;
+    let (addr, _) = start_server(config).await;
+
+    let mut spectator = Client::connect_from(addr, SPECTATOR_IP).await;
+    let spectator_token = spectator.create_player("spectator").await;
+    let mut game = StartedGame::new_with(addr, json!({ "fog_of_war": true })).await;
+    let game_token = game.game_token.clone();
+
+    // the spectator gets the full map
+    let spectate = json!({
+        "request_type": GM_SPECTATE,
+        "player_token": spectator_token,
+        "game_token": game_token,
+    });
+    let response = spectator.request(spectate).await;
+    assert_eq!(response["status"], OK_GM_SPECTATE);
+    assert_eq!(response["started"], true);
+    let response = spectator.recv().await;
+    assert_eq!(response["status"], OK_GM_START);
+    assert!(!response["map"].as_str().unwrap().contains(HIDDEN_TILE));
+
+    // the players can't spectate their own game to see the whole map
+    let mut second_connection = Client::connect(addr).await;
+    let spectate = json!({
+        "request_type": GM_SPECTATE,
+        "player_token": game.host.token,
+        "game_token": game_token,
+    });
+    let response = second_connection.request(spectate).await;
+    assert_eq!(response["status"], ERR_WRONG_STATE);
+    assert!(second_connection.silent().await);
+
+    // host in the top left corner, guest in the bottom right one of the 10x5 map
+    let (host_position, guest_position) = (Point(0, 0), Point(9, 4));
+    let (player, other) = game.players_by_turn();
+    let skip = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_SKIP,
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    let response = player.client.request(skip).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+    let host_map = snapshot(&mut game.host.client, &game.host.token, &game_token).await;
+    let guest_map = snapshot(&mut game.guest.client, &game.guest.token, &game_token).await;
+    assert_view(&host_map, host_position, 2);
+    assert_eq!(host_map.tile(host_position), Some('1'));
+    assert_view(&guest_map, guest_position, 2);
+    assert_eq!(guest_map.tile(guest_position), Some('2'));
+
+    assert_eq!(spectator.recv().await["status"], OK_GM_DATA);
+    let full_map = snapshot(&mut spectator, &spectator_token, &game_token).await;
+    assert_eq!(full_map.tile(host_position), Some('1'));
+    assert_eq!(full_map.tile(guest_position), Some('2'));
+    assert_eq!(full_map.find(HIDDEN_TILE), None);
+}
+
+#[tokio::test]
+async fn whole_map_without_fog_of_war() {
+    let (addr, _) = start_server(test_config()).await;
+    let mut game = StartedGame::new(addr).await;
+    let game_token = game.game_token.clone();
+
+    let (player, other) = game.players_by_turn();
+    let skip = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_SKIP,
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    assert_eq!(player.client.request(skip).await["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+    for player in [&mut game.host, &mut game.guest] {
+        let map = snapshot(&mut player.client, &player.token, &game_token).await;
+        assert_eq!(map.find(HIDDEN_TILE), None);
+    }
+}
+
+#[tokio::test]
+async fn moves_only_send_the_visible_changes() {
+    let mut config = test_config();
+    config.game.vision_radius = 2;
+    let (addr, _) = start_server(config).await;
+    let mut game = StartedGame::new_with(addr, json!({ "fog_of_war": true })).await;
+    let game_token = game.game_token.clone();
+
+    // the host moves one tile right, the guest in the other corner doesn't see it
+    if game.player_turn == "2" {
+        let skip = json!({
+            "request_type": GM_DATA,
+            "gm_code": GM_DATA_SKIP,
+            "player_token": game.guest.token,
+            "game_token": game_token,
+        });
+        assert_eq!(game.guest.client.request(skip).await["status"], OK_GM_DATA);
+        assert_eq!(game.host.client.recv().await["status"], OK_GM_DATA);
+    }
+    let mut host_map = snapshot(&mut game.host.client, &game.host.token, &game_token).await;
+    let movement = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_MOV,
+        "target": [1, 0],
+        "player_token": game.host.token,
+        "game_token": game_token,
+    });
+    let response = game.host.client.request(movement).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(game.guest.client.recv().await["changes"], json!([]));
+
+    // the left tile, the reached one and the new column in sight
+    let changes: Vec<TileChange> = serde_json::from_value(response["changes"].clone()).unwrap();
+    assert!(changes.contains(&(0, 0, '0')));
+    assert!(changes.contains(&(1, 0, '1')));
+    assert!(changes.iter().any(|&(x, _, _)| x == 3));
+    host_map.apply(&changes);
+    assert_view(&host_map, Point(1, 0), 2);
+    assert_eq!(
+        host_map,
+        snapshot(&mut game.host.client, &game.host.token, &game_token).await
+    );
+}
+
+#[tokio::test]
+async fn hidden_tiles_can_not_be_probed() {
+    let mut config = test_config();
+    config.game.map_height = 3;
+    config.game.map_width = 3;
+    config.game.vision_radius = 1;
+    let (addr, _) = start_server(config).await;
+    let mut game = StartedGame::new_with(addr, json!({ "fog_of_war": true })).await;
+    let game_token = game.game_token.clone();
+    if game.player_turn == "2" {
+        let skip = json!({
+            "request_type": GM_DATA,
+            "gm_code": GM_DATA_SKIP,
+            "player_token": game.guest.token,
+            "game_token": game_token,
+        });
+        assert_eq!(game.guest.client.request(skip).await["status"], OK_GM_DATA);
+        assert_eq!(game.host.client.recv().await["status"], OK_GM_DATA);
+    }
+
+    // the guest in the other corner is in the range of the magician but out of his sight,
+    // the free tile at the end of the first row is within his movement speed
+    for (gm_code, target) in [(GM_DATA_ATK, [2, 2]), (GM_DATA_MOV, [2, 0])] {
+        let action = json!({
+            "request_type": GM_DATA,
+            "gm_code": gm_code,
+            "target": target,
+            "player_token": game.host.token,
+            "game_token": game_token,
+        });
+        assert_eq!(
+            game.host.client.request(action).await["status"],
+            ERR_MAL_REQ
+        );
+    }
+    assert!(game.guest.client.silent().await);
+}
+
+#[tokio::test]
+async fn players_can_not_spectate_their_fog_game_with_another_player() {
+    let (addr, _) = start_server(test_config()).await;
+    let mut game = StartedGame::new_with(addr, json!({ "fog_of_war": true })).await;
+    let spectate = |player_token: &str| {
+        json!({
+            "request_type": GM_SPECTATE,
+            "player_token": player_token,
+            "game_token": game.game_token,
+        })
+    };
+
+    // a player created for it from the address of the players
+    let mut same_address = Client::connect(addr).await;
+    let player_token = same_address.create_player("watcher").await;
+    let response = same_address.request(spectate(&player_token)).await;
+    assert_eq!(response["status"], ERR_WRONG_STATE);
+
+    // the pseudo of a player from another address
+    let mut same_pseudo = Client::connect_from(addr, SPECTATOR_IP).await;
+    let player_token = same_pseudo.create_player("host").await;
+    let response = same_pseudo.request(spectate(&player_token)).await;
+    assert_eq!(response["status"], ERR_WRONG_STATE);
+
+    let mut spectator = Client::connect_from(addr, SPECTATOR_IP).await;
+    let player_token = spectator.create_player("spectator").await;
+    let response = spectator.request(spectate(&player_token)).await;
+    assert_eq!(response["status"], OK_GM_SPECTATE);
+    assert!(game.host.client.silent().await);
+}
@@ -0,0 +1,23 @@
+mod common;
+
+use common::{duel_config, start_server, StartedGame};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+
+#[tokio::test]
+async fn last_player_alive_wins() {
+    let (addr, state) = start_server(duel_config()).await;
+
+    let mut game = StartedGame::new(addr).await;
+    game.fight().await;
+
+    // the game is gone and the host can host a new one
+    assert!(!state.state.lock().await.contains_key(&game.game_token));
+    let response = game
+        .host
+        .client
+        .request(json!({ "request_type": GM_CREAT, "player_token": game.host.token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+}
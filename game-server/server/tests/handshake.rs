@@ -0,0 +1,129 @@
+// HELLO handshake and the order of the requests on a connection
+mod common;
+
+use common::{duel_config, start_server, start_websocket_server, Client, StartedGame};
+use net_utils::encoding::{Encoding, PROTOCOL_VERSION};
+use net_utils::packet::capabilities::*;
+use net_utils::packet::game_data_code::GM_DATA_SKIP;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+
+#[tokio::test]
+async fn hello_describes_the_server() {
+    let (addr, ws_addr, _) = start_websocket_server(duel_config()).await;
+
+    let mut client = Client::connect(addr).await;
+    let response = client.hello(Encoding::MessagePack).await;
+    assert_eq!(response["status"], OK_HELLO);
+    assert!(response["server"]
+        .as_str()
+        .unwrap()
+        .starts_with("game-server/"));
+    assert_eq!(response["version"], PROTOCOL_VERSION);
+    assert_eq!(response["encoding"], "msgpack");
+    assert_eq!(response["versions"], json!([PROTOCOL_VERSION]));
+    assert_eq!(response["encodings"], json!(["json", "msgpack"]));
+    for capability in [
+        ACCOUNTS, BOTS, SPECTATE, FOG_OF_WAR, MAP_DELTAS, TEAMS, ITEMS,
+    ] {
+        assert!(response["capabilities"]
+            .as_array()
+            .unwrap()
+            .contains(&json!(capability)));
+    }
+    // the next packets are in msgpack
+    client.create_player("player").await;
+
+    // websocket frames carry json only
+    let mut client = Client::connect_websocket(ws_addr).await;
+    let response = client.hello(Encoding::Json).await;
+    assert_eq!(response["status"], OK_HELLO);
+    assert_eq!(response["encodings"], json!(["json"]));
+}
+
+#[tokio::test]
+async fn incompatible_clients_are_refused() {
+    let (addr, ws_addr, _) = start_websocket_server(duel_config()).await;
+
+    // the client is told what it could have used before being disconnected
+    let mut client = Client::connect(addr).await;
+    let hello = json!({ "request_type": HELLO, "client": "future", "version": 99 });
+    let response = client.request(hello).await;
+    assert_eq!(response["status"], ERR_UNSUP_PROTO);
+    assert_eq!(response["versions"], json!([PROTOCOL_VERSION]));
+    assert_eq!(response["encodings"], json!(["json", "msgpack"]));
+    assert!(client.closed().await);
+
+    let mut client = Client::connect_websocket(ws_addr).await;
+    let response = client.hello(Encoding::MessagePack).await;
+    assert_eq!(response["status"], ERR_UNSUP_PROTO);
+    assert_eq!(response["encodings"], json!(["json"]));
+    assert!(client.closed().await);
+
+    // malformed hello, the connection stays open
+    let mut client = Client::connect(addr).await;
+    for hello in [
+        json!({ "request_type": HELLO, "version": PROTOCOL_VERSION }),
+        json!({ "request_type": HELLO, "client": "", "version": PROTOCOL_VERSION }),
+        json!({ "request_type": HELLO, "client": "x".repeat(65), "version": PROTOCOL_VERSION }),
+        json!({ "request_type": HELLO, "client": "no version" }),
+    ] {
+        assert_eq!(client.request(hello).await["status"], ERR_MAL_REQ);
+    }
+    assert_eq!(client.hello(Encoding::Json).await["status"], OK_HELLO);
+}
+
+#[tokio::test]
+async fn requests_in_any_sensible_order() {
+    let (addr, _) = start_server(duel_config()).await;
+
+    // hello after the player creation, game requests before joining are refused
+    let mut client = Client::connect(addr).await;
+    let player_token = client.create_player("early").await;
+    assert_eq!(client.hello(Encoding::Json).await["status"], OK_HELLO);
+    let start = json!({
+        "request_type": GM_START,
+        "player_token": player_token,
+        "game_token": "00000000-0000-0000-0000-000000000000",
+    });
+    assert_eq!(client.request(start).await["status"], ERR_WRONG_STATE);
+    let leaderboard = json!({ "request_type": LEADERBOARD });
+    assert_eq!(client.request(leaderboard).await["status"], OK_LEADERBOARD);
+
+    // during a game, lobby requests are refused and the game goes on
+    let mut game = StartedGame::new(addr).await;
+    let game_token = game.game_token.clone();
+    let (player, other) = game.players_by_turn();
+    let create = json!({ "request_type": GM_CREAT, "player_token": player.token });
+    assert_eq!(
+        player.client.request(create).await["status"],
+        ERR_WRONG_STATE
+    );
+    assert_eq!(
+        player
+            .client
+            .request(json!({ "request_type": PL_CREAT, "pseudo": "again" }))
+            .await["status"],
+        ERR_WRONG_STATE
+    );
+    assert_eq!(
+        player.client.hello(Encoding::MessagePack).await["status"],
+        OK_HELLO
+    );
+    let skip = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_SKIP,
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    assert_eq!(player.client.request(skip).await["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+
+    // unknown requests still close the connection
+    assert_eq!(
+        player.client.request(json!({ "request_type": 99 })).await["status"],
+        ERR_MAL_REQ
+    );
+    assert!(player.client.closed().await);
+}
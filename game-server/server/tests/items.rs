@@ -0,0 +1,121 @@
+// items spawned on the map: picked up by moving onto them, bonuses lasting a few turns
+mod common;
+
+use common::{duel_config, start_server, StartedGame};
+use net_utils::item::*;
+use net_utils::map::{GameMap, Point};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+
+// stats of the host (magician) and of the guest (barbarian)
+const HOST_STATS: (u8, u8, u8, u8) = (4, 80, 3, 2);
+const GUEST_STATS: (u8, u8, u8, u8) = (10, 100, 4, 1);
+
+fn stats(response: &Value) -> (u8, u8, u8, u8) {
+    serde_json::from_value(response["stats"].clone()).unwrap()
+}
+
+async fn snapshot(game: &mut StartedGame) -> GameMap {
+    let snapshot = json!({
+        "request_type": GM_SNAPSHOT,
+        "player_token": game.host.token,
+        "game_token": game.game_token,
+    });
+    let response = game.host.client.request(snapshot).await;
+    response["map"].as_str().unwrap().parse().unwrap()
+}
+
+// action of the current player, the other one gets it too
+async fn play(game: &mut StartedGame, gm_code: u64, target: [i16; 2]) -> Value {
+    let game_token = game.game_token.clone();
+    let (player, other) = game.players_by_turn();
+    let action = json!({
+        "request_type": GM_DATA,
+        "gm_code": gm_code,
+        "target": target,
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    let response = player.client.request(action).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await, response);
+    game.player_turn = response["player_turn"].as_str().unwrap().into();
+    response
+}
+
+#[tokio::test]
+async fn picked_up_items_give_a_bonus_for_a_few_turns() {
+    let mut config = duel_config();
+    // only the items of the start
+    config.game.item_interval = 1000;
+    let (addr, _) = start_server(config).await;
+    let mut game = StartedGame::new(addr).await;
+
+    // one item per player on the free tiles of the 2x2 map
+    let map = snapshot(&mut game).await;
+    let items = map.find_all(|tile| Item::from_tile(tile).is_some());
+    assert_eq!(items, [Point(1, 0), Point(0, 1)]);
+
+    let (player_num, base) = match &*game.player_turn {
+        "1" => ("1", HOST_STATS),
+        _ => ("2", GUEST_STATS),
+    };
+    let item = Item::from_tile(map.tile(Point(1, 0)).unwrap()).unwrap();
+    let response = play(&mut game, GM_DATA_MOV, [1, 0]).await;
+    assert_eq!(response["item"], json!(item));
+    assert_eq!(response["player_num"], player_num);
+    // player 1 comes from the top left corner, player 2 from the bottom right one
+    let changes = match player_num {
+        "1" => json!([[0, 0, "0"], [1, 0, "1"]]),
+        _ => json!([[1, 0, "2"], [1, 1, "0"]]),
+    };
+    assert_eq!(response["changes"], changes);
+
+    // full hp: the potion does nothing
+    let (atk, hp, ms, rng) = base;
+    let bonus = match item {
+        Item::HealthPotion => base,
+        Item::AttackBuff => (atk + ATTACK_BONUS, hp, ms, rng),
+        Item::Boots => (atk, hp, ms + BOOTS_BONUS, rng),
+    };
+    assert_eq!(stats(&response), bonus);
+
+    // the bonus lasts the next turns of the player, the stats are back after the last one
+    for turn in 1..=EFFECT_TURNS {
+        let other = play(&mut game, GM_DATA_SKIP, [0, 0]).await;
+        assert_eq!(other["item"], Value::Null);
+        let response = play(&mut game, GM_DATA_SKIP, [0, 0]).await;
+        let expected = if turn < EFFECT_TURNS { bonus } else { base };
+        assert_eq!(stats(&response), expected, "turn {turn}");
+    }
+}
+
+#[tokio::test]
+async fn items_are_spawned_every_interval() {
+    let mut config = duel_config();
+    config.game.map_width = 3;
+    config.game.item_interval = 2;
+    let (addr, _) = start_server(config).await;
+    let mut game = StartedGame::new(addr).await;
+
+    // 2 players and 2 items on 6 tiles
+    let map = snapshot(&mut game).await;
+    assert_eq!(map.find_all(|tile| tile == '0').len(), 2);
+
+    let response = play(&mut game, GM_DATA_SKIP, [0, 0]).await;
+    assert_eq!(response["changes"], json!([]));
+    let response = play(&mut game, GM_DATA_SKIP, [0, 0]).await;
+    let changes = response["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 1);
+    let tile = changes[0][2].as_str().unwrap().chars().next().unwrap();
+    assert!(Item::from_tile(tile).is_some());
+    assert_eq!(
+        snapshot(&mut game)
+            .await
+            .find_all(|tile| Item::from_tile(tile).is_some())
+            .len(),
+        3
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{start_server, test_config, Client};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr};
+
+// the clients connect from 127.0.0.1
+const OTHER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+
+async fn player_creation(client: &mut Client, pseudo: &str) -> Value {
+    let response = client
+        .request(json!({ "request_type": PL_CREAT, "pseudo": pseudo }))
+        .await;
+    response["status"].clone()
+}
+
+#[tokio::test]
+async fn connections_flooding_requests_are_throttled() {
+    let mut config = test_config();
+    config.limits.requests_per_second = 1;
+    config.limits.request_burst = 3;
+    config.limits.max_players_per_connection = 10;
+    let (addr, _) = start_server(config).await;
+
+    let mut client = Client::connect(addr).await;
+    for _ in 0..3 {
+        assert_eq!(player_creation(&mut client, "player").await, OK_PL_CREAT);
+    }
+    assert_eq!(player_creation(&mut client, "player").await, ERR_THROTTLED);
+    assert_eq!(player_creation(&mut client, "player").await, ERR_THROTTLED);
+
+    // the other connections of the address have their own bucket
+    let mut other = Client::connect(addr).await;
+    assert_eq!(player_creation(&mut other, "player").await, OK_PL_CREAT);
+}
+
+#[tokio::test]
+async fn addresses_flooding_requests_are_throttled() {
+    let mut config = test_config();
+    config.limits.ip_requests_per_second = 1;
+    config.limits.ip_request_burst = 3;
+    let (addr, _) = start_server(config).await;
+
+    // one request per connection, the address bucket is shared by all of them
+    for _ in 0..3 {
+        let mut client = Client::connect(addr).await;
+        assert_eq!(player_creation(&mut client, "player").await, OK_PL_CREAT);
+    }
+    let mut client = Client::connect(addr).await;
+    assert_eq!(player_creation(&mut client, "player").await, ERR_THROTTLED);
+
+    let mut other = Client::connect_from(addr, OTHER_IP).await;
+    assert_eq!(player_creation(&mut other, "player").await, OK_PL_CREAT);
+}
+
+#[tokio::test]
+async fn players_per_connection_are_limited() {
+    let mut config = test_config();
+    config.limits.max_players_per_connection = 2;
+    let (addr, _) = start_server(config).await;
+
+    let mut client = Client::connect(addr).await;
+    for _ in 0..2 {
+        assert_eq!(player_creation(&mut client, "player").await, OK_PL_CREAT);
+    }
+    assert_eq!(player_creation(&mut client, "player").await, ERR_PL_LIMIT);
+    // accounts are players too
+    let register = json!({
+        "request_type": REGISTER,
+        "pseudo": "account",
+        "password": "correct horse battery",
+    });
+    assert_eq!(client.request(register).await["status"], ERR_PL_LIMIT);
+
+    let mut other = Client::connect(addr).await;
+    assert_eq!(player_creation(&mut other, "player").await, OK_PL_CREAT);
+}
@@ -0,0 +1,70 @@
+// the counters are process wide: this file holds a single test so that no other request
+// is counted
+mod common;
+
+use common::{start_server, test_config, Client};
+use game_server::metrics::serve_metrics;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+async fn scrape(addr: SocketAddr) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream
+        .write_all(b"GET /metrics HTTP/1.1\r\nhost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+    response
+}
+
+#[tokio::test]
+async fn counters_follow_the_requests() {
+    let (addr, state) = start_server(test_config()).await;
+    let metrics_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let metrics_addr = metrics_listener.local_addr().unwrap();
+    tokio::spawn(serve_metrics(metrics_listener, state));
+
+    let mut client = Client::connect(addr).await;
+    let player_token = client.create_player("player").await;
+    let response = client
+        .request(json!({ "request_type": PL_CREAT, "pseudo": "not valid" }))
+        .await;
+    assert_eq!(response["status"], ERR_INV_PSEUD);
+    let response = client
+        .request(json!({ "request_type": GM_CREAT, "player_token": player_token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+
+    let metrics = scrape(metrics_addr).await;
+    let lines: Vec<&str> = metrics.lines().collect();
+    for expected in [
+        "game_server_connections_total 1".to_string(),
+        "game_server_connections_active 1".to_string(),
+        "game_server_games_active 1".to_string(),
+        format!("game_server_requests_total{{code=\"{PL_CREAT}\"}} 2"),
+        format!("game_server_requests_total{{code=\"{GM_CREAT}\"}} 1"),
+        format!("game_server_errors_total{{status=\"{ERR_INV_PSEUD}\"}} 1"),
+        format!("game_server_handler_seconds_count{{code=\"{PL_CREAT}\"}} 2"),
+        format!("game_server_handler_seconds_bucket{{code=\"{GM_CREAT}\",le=\"+Inf\"}} 1"),
+    ] {
+        assert!(
+            lines.contains(&expected.as_str()),
+            "{expected} in {metrics}"
+        );
+    }
+    // no other request or error
+    let requests = lines
+        .iter()
+        .filter(|line| line.starts_with("game_server_requests_total{"));
+    assert_eq!(requests.count(), 2);
+    let errors = lines
+        .iter()
+        .filter(|line| line.starts_with("game_server_errors_total{"));
+    assert_eq!(errors.count(), 1);
+}
@@ -0,0 +1,286 @@
+// protocol conformance: every request of a game from player creation to connection
+// termination, with the response and broadcast expected for each of them
+mod common;
+
+use common::{duel_config, start_server, Client};
+use net_utils::map::{GameMap, TileChange};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+
+// request of a player in a game
+fn game_request(request_type: u64, player_token: &str, game_token: &str, fields: Value) -> Value {
+    let mut request = json!({
+        "request_type": request_type,
+        "player_token": player_token,
+        "game_token": game_token,
+    });
+    request
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    request
+}
+
+fn game_data(gm_code: u64, player_token: &str, game_token: &str, target: [i16; 2]) -> Value {
+    game_request(
+        GM_DATA,
+        player_token,
+        game_token,
+        json!({ "gm_code": gm_code, "target": target }),
+    )
+}
+
+// map after the tile changes of a game data packet
+fn apply(map: &str, packet: &Value) -> String {
+    let mut map: GameMap = map.parse().unwrap();
+    let changes: Vec<TileChange> = serde_json::from_value(packet["changes"].clone()).unwrap();
+    map.apply(&changes);
+    map.to_string()
+}
+
+// (x, y) of the player on the map sent by the server
+fn position(map: &str, player_num: &str) -> [i16; 2] {
+    for (y, line) in map.lines().enumerate() {
+        if let Some(x) = line.find(player_num) {
+            return [x as i16, y as i16];
+        }
+    }
+    panic!("player {player_num} not on the map");
+}
+
+#[tokio::test]
+async fn player_creation() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut client = Client::connect(addr).await;
+
+    let response = client
+        .request(json!({ "request_type": PL_CREAT, "pseudo": "player1" }))
+        .await;
+    assert_eq!(response["status"], OK_PL_CREAT);
+    assert_eq!(response["player_token"].as_str().unwrap().len(), 36);
+
+    for pseudo in ["with space", "", &"a".repeat(33)] {
+        let response = client
+            .request(json!({ "request_type": PL_CREAT, "pseudo": pseudo }))
+            .await;
+        assert_eq!(response["status"], ERR_INV_PSEUD, "pseudo {pseudo:?}");
+    }
+    let response = client.request(json!({ "request_type": PL_CREAT })).await;
+    assert_eq!(response["status"], ERR_MAL_REQ);
+
+    // unknown requests close the connection
+    let response = client.request(json!({ "request_type": 99 })).await;
+    assert_eq!(response["status"], ERR_MAL_REQ);
+    assert!(client.closed().await);
+}
+
+#[tokio::test]
+async fn invalid_tokens() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut client = Client::connect(addr).await;
+    let player_token = client.create_player("player").await;
+    let unknown_token = "00000000-0000-0000-0000-000000000000";
+
+    for player_token in [unknown_token, "not a token"] {
+        let response = client
+            .request(json!({ "request_type": GM_CREAT, "player_token": player_token }))
+            .await;
+        assert_eq!(response["status"], ERR_INV_PL_TOK);
+    }
+    let response = client.request(json!({ "request_type": GM_CREAT })).await;
+    assert_eq!(response["status"], ERR_MAL_REQ);
+
+    let join = game_request(GM_JOIN, &player_token, unknown_token, json!({}));
+    assert_eq!(client.request(join).await["status"], ERR_INV_GM_TOK);
+    let join = game_request(GM_JOIN, unknown_token, unknown_token, json!({}));
+    assert_eq!(client.request(join).await["status"], ERR_INV_PL_TOK);
+}
+
+#[tokio::test]
+async fn start_needs_every_player() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    let game_token = response["game_token"].as_str().unwrap().to_string();
+
+    let start = game_request(GM_START, &host_token, &game_token, json!({}));
+    assert_eq!(host.request(start).await["status"], ERR_GM_NOT_FULL);
+}
+
+#[tokio::test]
+async fn full_game() {
+    let (addr, _) = start_server(duel_config()).await;
+
+    // lobby
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+    let mut guest = Client::connect(addr).await;
+    let guest_token = guest.create_player("guest").await;
+    let mut late = Client::connect(addr).await;
+    let late_token = late.create_player("late").await;
+
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+    let game_token = response["game_token"].as_str().unwrap().to_string();
+    assert_eq!(game_token.len(), 36);
+
+    let join = game_request(GM_JOIN, &guest_token, &game_token, json!({}));
+    let response = guest.request(join).await;
+    let expected = json!({
+        "status": OK_GM_JOIN,
+        "pseudo": "guest",
+        "player_vec": [["1", "host", "", "1", ""], ["2", "guest", "", "0", ""]],
+    });
+    assert_eq!(response, expected);
+    assert_eq!(host.recv().await, expected);
+
+    let join = game_request(GM_JOIN, &late_token, &game_token, json!({}));
+    assert_eq!(late.request(join).await["status"], ERR_GM_FULL);
+
+    // character choice
+    let skip = game_data(GM_DATA_SKIP, &guest_token, &game_token, [0, 0]);
+    assert_eq!(guest.request(skip).await["status"], ERR_GM_NOT_START);
+
+    let choose = game_request(
+        CHAR_CHOOSING,
+        &host_token,
+        &game_token,
+        json!({ "character": "knight" }),
+    );
+    assert_eq!(host.request(choose).await["status"], ERR_MAL_REQ);
+    let choose = game_request(
+        CHAR_CHOOSING,
+        &host_token,
+        &game_token,
+        json!({ "character": "mag" }),
+    );
+    let expected = json!({ "status": OK_CHAR_CHOOSING, "pseudo": "host", "character": "mag" });
+    assert_eq!(host.request(choose).await, expected);
+    assert_eq!(guest.recv().await, expected);
+    let choose = game_request(
+        CHAR_CHOOSING,
+        &guest_token,
+        &game_token,
+        json!({ "character": "bar" }),
+    );
+    let expected = json!({ "status": OK_CHAR_CHOOSING, "pseudo": "guest", "character": "bar" });
+    assert_eq!(guest.request(choose).await, expected);
+    assert_eq!(host.recv().await, expected);
+
+    // start, only the host can start
+    let start = game_request(GM_START, &guest_token, &game_token, json!({}));
+    assert_eq!(guest.request(start).await["status"], ERR_MAL_REQ);
+    assert!(host.silent().await);
+
+    let start = game_request(GM_START, &host_token, &game_token, json!({}));
+    let started = host.request(start).await;
+    assert_eq!(started["status"], OK_GM_START);
+    assert_eq!(started["map"], "10\n02");
+    assert_eq!(started["version"], 0);
+    assert_eq!(guest.recv().await, started);
+    let start = game_request(GM_START, &host_token, &game_token, json!({}));
+    assert_eq!(host.request(start).await["status"], ERR_GM_AL_START);
+
+    let join = game_request(GM_JOIN, &late_token, &game_token, json!({}));
+    assert_eq!(late.request(join).await["status"], ERR_GM_AL_START);
+    // a player of another game can't play in this one
+    let response = late
+        .request(json!({ "request_type": GM_CREAT, "player_token": late_token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+    let skip = game_data(GM_DATA_SKIP, &late_token, &game_token, [0, 0]);
+    assert_eq!(late.request(skip).await["status"], ERR_GM_NOT_JOIN);
+
+    // (client, token, player number, atk) in playing order
+    let mut players = [
+        (&mut host, host_token.as_str(), "1", 4),
+        (&mut guest, guest_token.as_str(), "2", 10),
+    ];
+    if started["player_turn"] == "2" {
+        players.reverse();
+    }
+    let [(first, first_token, first_num, _), (second, second_token, second_num, second_atk)] =
+        &mut players;
+    let mut map = started["map"].as_str().unwrap().to_string();
+
+    // wrong turn
+    let skip = game_data(GM_DATA_SKIP, second_token, &game_token, [0, 0]);
+    assert_eq!(second.request(skip).await["status"], ERR_NOT_TURN);
+
+    // movement: the first player goes to the free tile of his row
+    let [x, y] = position(&map, first_num);
+    let occupied = position(&map, second_num);
+    let movement = game_data(GM_DATA_MOV, first_token, &game_token, occupied);
+    assert_eq!(first.request(movement).await["status"], ERR_MAL_REQ);
+    let outside = game_data(GM_DATA_MOV, first_token, &game_token, [5, 5]);
+    assert_eq!(first.request(outside).await["status"], ERR_MAL_REQ);
+
+    let dest = [1 - x, y];
+    let movement = game_data(GM_DATA_MOV, first_token, &game_token, dest);
+    let response = first.request(movement).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(response["data_type"], GM_DATA_MOV);
+    assert_eq!(response["player_num"], *first_num);
+    assert_eq!(response["player_turn"], *second_num);
+    assert_eq!(response["enemy"], json!(["", 0]));
+    // only the tile left and the tile reached are sent
+    assert_eq!(response["version"], 1);
+    assert_eq!(response["changes"].as_array().unwrap().len(), 2);
+    map = apply(&map, &response);
+    assert_eq!(position(&map, first_num), dest);
+    assert_eq!(second.recv().await, response);
+
+    // attack: the second player hits the first one, now right above or below him
+    let empty = [x, y];
+    let attack = game_data(GM_DATA_ATK, second_token, &game_token, empty);
+    assert_eq!(second.request(attack).await["status"], ERR_MAL_REQ);
+
+    let attack = game_data(GM_DATA_ATK, second_token, &game_token, dest);
+    let response = second.request(attack).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(response["data_type"], GM_DATA_ATK);
+    assert_eq!(response["player_num"], *second_num);
+    assert_eq!(response["player_turn"], *first_num);
+    let first_hp = if *first_num == "1" { 80 } else { 100 };
+    assert_eq!(
+        response["enemy"],
+        json!([first_num, first_hp - *second_atk])
+    );
+    assert_eq!(response["version"], 2);
+    assert_eq!(response["changes"], json!([]));
+    assert_eq!(first.recv().await, response);
+
+    // skip
+    let skip = game_data(GM_DATA_SKIP, first_token, &game_token, [0, 0]);
+    let response = first.request(skip).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(response["data_type"], GM_DATA_SKIP);
+    assert_eq!(response["player_turn"], *second_num);
+    assert_eq!(response["version"], 3);
+    assert_eq!(second.recv().await, response);
+
+    // the whole map, for a client that missed a packet
+    let snapshot = game_request(GM_SNAPSHOT, second_token, &game_token, json!({}));
+    let response = second.request(snapshot).await;
+    assert_eq!(response["status"], OK_GM_SNAPSHOT);
+    assert_eq!(response["map"], map);
+    assert_eq!(response["player_turn"], *second_num);
+    assert_eq!(response["version"], 3);
+    assert!(first.silent().await);
+
+    // termination
+    let response = first.request(json!({ "request_type": TERM_CON })).await;
+    assert_eq!(response["status"], OK_TERM_CON);
+    assert!(first.closed().await);
+
+    let response = late.request(json!({ "request_type": TERM_CON })).await;
+    assert_eq!(response["status"], OK_TERM_CON);
+    assert!(late.closed().await);
+}
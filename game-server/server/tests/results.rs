@@ -0,0 +1,277 @@
+// signed result records of the finished games, checked offline
+mod common;
+
+use common::{duel_config, start_server, StartedGame};
+use game_server::audit::{
+    public_key_path, verify, ActionKind, ResultRecord, ResultSigner, SignedRecord,
+};
+use game_server::config::{Config, ResultsConfig};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+use serde_json::value::RawValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn results_config(test_name: &str) -> Config {
+    let dir = std::env::temp_dir().join(format!("game_server_{test_name}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let mut config = duel_config();
+    config.results = Some(ResultsConfig {
+        dir: dir.join("results"),
+        key: dir.join("results.key"),
+    });
+    config
+}
+
+// the record is written once the players got the game over
+async fn read_record(path: &Path) -> SignedRecord {
+    for _ in 0..50 {
+        if let Ok(content) = fs::read_to_string(path) {
+            return serde_json::from_str(&content).unwrap();
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    panic!("no record written to {}", path.display());
+}
+
+fn parse(signed: &SignedRecord) -> ResultRecord {
+    serde_json::from_str(signed.record.get()).unwrap()
+}
+
+// the signature of the original record with other record bytes
+fn with_record(signed: &SignedRecord, record: String) -> SignedRecord {
+    SignedRecord {
+        record: RawValue::from_string(record).unwrap(),
+        ..signed.clone()
+    }
+}
+
+// a skipped turn then a fight to the death
+async fn finished_game(config: &Config) -> (PathBuf, SignedRecord, String) {
+    let (addr, _) = start_server(config.clone()).await;
+    let mut game = StartedGame::new(addr).await;
+    let game_token = game.game_token.clone();
+    let (player, other) = game.players_by_turn();
+    let skip = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_SKIP,
+        "target": [0, 0],
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    let response = player.client.request(skip).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+    game.player_turn = response["player_turn"].as_str().unwrap().into();
+    let winner = game.fight().await;
+
+    let results = config.results.as_ref().unwrap();
+    let path = results.dir.join(format!("{game_token}.json"));
+    let signed = read_record(&path).await;
+    assert_eq!(parse(&signed).game_token, game_token);
+    (path, signed, winner)
+}
+
+fn public_key(config: &Config) -> String {
+    fs::read_to_string(public_key_path(&config.results.as_ref().unwrap().key)).unwrap()
+}
+
+fn verify_command(config: &Config, record: &Path) -> bool {
+    let key = public_key_path(&config.results.as_ref().unwrap().key);
+    Command::new(env!("CARGO_BIN_EXE_game-verify"))
+        .arg("--public-key")
+        .arg(key)
+        .arg(record)
+        .output()
+        .unwrap()
+        .status
+        .success()
+}
+
+#[tokio::test]
+async fn finished_games_get_a_signed_record() {
+    let config = results_config("signed_record");
+    let (path, signed, winner) = finished_game(&config).await;
+
+    let record = verify(&signed, &public_key(&config)).unwrap();
+    assert_eq!(
+        record.players,
+        [
+            ["1".to_string(), "host".into(), "mag".into()],
+            ["2".to_string(), "guest".into(), "bar".into()],
+        ]
+    );
+    assert_eq!(record.winner, winner);
+    // the map the game started on, then the skipped turn and the attacks
+    let start = &record.actions[0];
+    assert_eq!(start.kind, ActionKind::Start);
+    assert_eq!(start.version, 0);
+    assert_eq!(start.map.as_deref(), Some("10\n02"));
+    assert_eq!(record.actions[1].kind, ActionKind::Skip);
+    for (version, action) in record.actions[1..].iter().enumerate() {
+        assert_eq!(action.version, version as u32);
+        if version > 0 {
+            assert_eq!(action.kind, ActionKind::Attack);
+        }
+    }
+    let last = record.actions.last().unwrap();
+    assert_eq!(last.player_num, winner);
+    assert_eq!(record.chain, last.hash);
+
+    // the record is saved as it was signed
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains(signed.record.get()));
+    assert!(verify_command(&config, &path));
+}
+
+#[tokio::test]
+async fn tampered_records_are_refused() {
+    let config = results_config("tampered_record");
+    let (path, signed, _) = finished_game(&config).await;
+    let public_key = public_key(&config);
+
+    // another winner
+    let mut record = parse(&signed);
+    record.winner = "12".into();
+    let tampered = with_record(&signed, serde_json::to_string(&record).unwrap());
+    let error = verify(&tampered, &public_key).unwrap_err();
+    assert_eq!(error.to_string(), "bad signature");
+    fs::write(&path, serde_json::to_string(&tampered).unwrap()).unwrap();
+    assert!(!verify_command(&config, &path));
+
+    // the signed bytes are checked, not the record they hold
+    let reformatted = serde_json::to_string_pretty(&parse(&signed)).unwrap();
+    let error = verify(&with_record(&signed, reformatted), &public_key).unwrap_err();
+    assert_eq!(error.to_string(), "bad signature");
+
+    // signed again by another server
+    let other_config = results_config("tampered_record_other_key");
+    let other_signer = ResultSigner::load(other_config.results.as_ref().unwrap()).unwrap();
+    let resigned = other_signer.sign(&record);
+    let error = verify(&resigned, &public_key).unwrap_err();
+    assert!(error.to_string().starts_with("signed by another key"));
+
+    // log changed before the server signed it
+    let signer = ResultSigner::load(config.results.as_ref().unwrap()).unwrap();
+    let changed = |change: &dyn Fn(&mut ResultRecord)| {
+        let mut record = parse(&signed);
+        change(&mut record);
+        verify(&signer.sign(&record), &public_key)
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        changed(&|r| r.actions[2].target = Some([0, 1])),
+        "hash chain broken at action 2"
+    );
+    assert_eq!(
+        changed(&|r| r.actions[0].map = Some("12\n00".into())),
+        "hash chain broken at action 0"
+    );
+    assert_eq!(
+        changed(&|r| {
+            r.actions.remove(1);
+        }),
+        "hash chain broken at action 1"
+    );
+    assert_eq!(
+        changed(&|r| {
+            r.actions.remove(0);
+        }),
+        "the log doesn't start with the map of the game"
+    );
+}
+
+#[tokio::test]
+async fn item_spawns_are_chained() {
+    let mut config = results_config("item_spawns");
+    // the items placed at the start fill the map, the next one spawns on the tile left by
+    // the first move
+    config.game.item_interval = 1;
+    let (addr, _) = start_server(config.clone()).await;
+    let mut game = StartedGame::new(addr).await;
+    let game_token = game.game_token.clone();
+    let mover = game.player_turn.clone();
+    let corner = |num: &str| if num == "1" { [0, 0] } else { [1, 1] };
+
+    let (player, other) = game.players_by_turn();
+    let movement = json!({
+        "request_type": GM_DATA,
+        "gm_code": GM_DATA_MOV,
+        "target": [1, 0],
+        "player_token": player.token,
+        "game_token": game_token,
+    });
+    let response = player.client.request(movement).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+    game.player_turn = response["player_turn"].as_str().unwrap().into();
+
+    // fight to the death, the mover stays on the first row
+    loop {
+        let player_turn = game.player_turn.clone();
+        let enemy = if player_turn == "1" { "2" } else { "1" };
+        let target = if enemy == mover {
+            [1, 0]
+        } else {
+            corner(enemy)
+        };
+        let (player, other) = game.players_by_turn();
+        let attack = json!({
+            "request_type": GM_DATA,
+            "gm_code": GM_DATA_ATK,
+            "target": target,
+            "player_token": player.token,
+            "game_token": game_token,
+        });
+        let response = player.client.request(attack).await;
+        assert_eq!(response["status"], OK_GM_DATA);
+        assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+        if response["enemy"][1] == 0 {
+            break;
+        }
+        game.player_turn = response["player_turn"].as_str().unwrap().into();
+    }
+
+    let path = config
+        .results
+        .as_ref()
+        .unwrap()
+        .dir
+        .join(format!("{game_token}.json"));
+    let record = verify(&read_record(&path).await, &public_key(&config)).unwrap();
+    let start = record.actions[0].map.as_deref().unwrap();
+    assert!(!start.contains('0'), "items on every free tile of {start}");
+    let spawn = &record.actions[2];
+    assert_eq!(spawn.kind, ActionKind::Spawn);
+    assert_eq!(spawn.version, 1);
+    assert_eq!(spawn.target, Some(corner(&mover)));
+    assert!(spawn.item.is_some());
+}
+
+#[cfg(unix)]
+#[test]
+fn the_signing_key_is_private() {
+    use std::os::unix::fs::PermissionsExt;
+    let config = results_config("private_key");
+    let results = config.results.as_ref().unwrap();
+    ResultSigner::load(results).unwrap();
+    let mode = fs::metadata(&results.key).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // the public key can be handed out
+    assert!(public_key_path(&results.key).exists());
+
+    // a key other users can read is refused, even though it would work
+    fs::set_permissions(&results.key, fs::Permissions::from_mode(0o644)).unwrap();
+    let error = ResultSigner::load(results).err().unwrap();
+    assert!(error.to_string().contains("can be read by other users"));
+    fs::set_permissions(&results.key, fs::Permissions::from_mode(0o600)).unwrap();
+    ResultSigner::load(results).unwrap();
+}
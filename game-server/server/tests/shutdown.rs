@@ -0,0 +1,163 @@
+mod common;
+
+use common::{duel_config, Client, StartedGame};
+use game_server::config::{Config, StorageBackend};
+use game_server::handler::resume_games;
+use game_server::server::{serve, State};
+use game_server::storage::StorageClient;
+use game_server::stream::{read_packet, write_packet_from_json, PacketStream};
+use net_utils::packet::game_data_code::{GM_DATA_ATK, GM_DATA_MOV};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+// server started like the binary does it: the games saved in the storage are resumed,
+// stopped (and its storage persisted) once the sender is used
+async fn start_server(
+    config: &Config,
+) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<Arc<State>>) {
+    let state = Arc::new(State::new(
+        config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ));
+    resume_games(&state).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(async move {
+        let shutdown = async {
+            stopped.await.unwrap();
+        };
+        serve(listener, None, Arc::clone(&state), None, shutdown)
+            .await
+            .unwrap();
+        state.storage.persist().unwrap();
+        state
+    });
+    (addr, stop, server)
+}
+
+fn game_request(request_type: u64, player_token: &str, game_token: &str, fields: Value) -> Value {
+    let mut json = json!({
+        "request_type": request_type,
+        "player_token": player_token,
+        "game_token": game_token,
+    });
+    json.as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    json
+}
+
+#[tokio::test]
+async fn connections_are_told_about_shutdown() {
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::Memory;
+    let state = Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(serve(listener, None, state, None, async {
+        stopped.await.unwrap();
+    }));
+
+    let mut client = PacketStream::new(TcpStream::connect(addr).await.unwrap());
+    let json = json!({ "request_type": PL_CREAT, "pseudo": "player" }).to_string();
+    write_packet_from_json(&mut client, &json).await.unwrap();
+    assert_eq!(
+        read_packet(&mut client).await.unwrap()["status"],
+        OK_PL_CREAT
+    );
+
+    stop.send(()).unwrap();
+    assert_eq!(
+        read_packet(&mut client).await.unwrap()["status"],
+        SERV_SHUTDOWN
+    );
+    server.await.unwrap().unwrap();
+
+    // the listener is closed once serve returned
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn started_games_are_resumed_after_a_restart() {
+    let snapshot =
+        std::env::temp_dir().join(format!("game_server_resume_{}.json", std::process::id()));
+    let _ = fs::remove_file(&snapshot);
+    let mut config = duel_config();
+    config.storage.snapshot = Some(snapshot.clone());
+    let (addr, stop, server) = start_server(&config).await;
+
+    // the player whose turn it is moves to the free tile of the first row
+    let mut game = StartedGame::new(addr).await;
+    let game_token = game.game_token.clone();
+    let (player, other) = game.players_by_turn();
+    let movement = game_request(
+        GM_DATA,
+        &player.token,
+        &game_token,
+        json!({ "gm_code": GM_DATA_MOV, "target": [1, 0] }),
+    );
+    let moved = player.client.request(movement).await;
+    assert_eq!(moved["status"], OK_GM_DATA);
+    assert_eq!(other.client.recv().await["status"], OK_GM_DATA);
+    let snapshot_request = game_request(GM_SNAPSHOT, &player.token, &game_token, json!({}));
+    let before = player.client.request(snapshot_request).await;
+    assert_eq!(before["status"], OK_GM_SNAPSHOT);
+
+    stop.send(()).unwrap();
+    for client in [&mut game.host.client, &mut game.guest.client] {
+        assert_eq!(client.recv().await["status"], SERV_SHUTDOWN);
+    }
+    server.await.unwrap();
+
+    // same storage, the players rejoin the game where it was
+    let (addr, stop, server) = start_server(&config).await;
+    let mut host = Client::connect(addr).await;
+    let join = game_request(GM_JOIN, &game.host.token, &game_token, json!({}));
+    assert_eq!(host.request(join).await["status"], OK_GM_JOIN);
+    let resumed = host.recv().await;
+    assert_eq!(resumed["status"], OK_GM_START);
+    assert_eq!(resumed["map"], before["map"]);
+    assert_eq!(resumed["player_turn"], before["player_turn"]);
+    assert_eq!(resumed["player_turn"], moved["player_turn"]);
+    assert_eq!(resumed["version"], moved["version"]);
+
+    let mut guest = Client::connect(addr).await;
+    let join = game_request(GM_JOIN, &game.guest.token, &game_token, json!({}));
+    assert_eq!(guest.request(join).await["status"], OK_GM_JOIN);
+    assert_eq!(guest.recv().await["map"], before["map"]);
+    assert_eq!(host.recv().await["status"], OK_GM_JOIN);
+
+    // the game goes on: the mover is attacked by the other player
+    let (attacker, attacker_token, target) = match moved["player_turn"].as_str().unwrap() {
+        "1" => (&mut host, &game.host.token, &mut guest),
+        _ => (&mut guest, &game.guest.token, &mut host),
+    };
+    let attack = game_request(
+        GM_DATA,
+        attacker_token,
+        &game_token,
+        json!({ "gm_code": GM_DATA_ATK, "target": [1, 0] }),
+    );
+    let response = attacker.request(attack).await;
+    assert_eq!(response["status"], OK_GM_DATA);
+    assert_eq!(response["version"], moved["version"].as_u64().unwrap() + 1);
+    assert_eq!(target.recv().await["status"], OK_GM_DATA);
+
+    stop.send(()).unwrap();
+    server.await.unwrap();
+    let _ = fs::remove_file(&snapshot);
+}
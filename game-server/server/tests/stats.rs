@@ -0,0 +1,121 @@
+mod common;
+
+use common::{duel_config, start_server, Client, Player, StartedGame};
+use game_server::stats::updated_ratings;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+async fn register(addr: SocketAddr, pseudo: &str) -> Player {
+    let mut client = Client::connect(addr).await;
+    let response = client
+        .request(json!({
+            "request_type": REGISTER,
+            "pseudo": pseudo,
+            "password": "password",
+        }))
+        .await;
+    assert_eq!(response["status"], OK_REGISTER);
+    Player {
+        client,
+        token: response["player_token"].as_str().unwrap().into(),
+    }
+}
+
+async fn stats(client: &mut Client, pseudo: &str) -> Value {
+    client
+        .request(json!({ "request_type": PL_STATS, "pseudo": pseudo }))
+        .await
+}
+
+#[test]
+fn rating_changes() {
+    // even players exchange half of the k factor
+    assert_eq!(updated_ratings(1200, &[1200]), (1216, vec![1184]));
+    // beating a much weaker player is worth little
+    assert_eq!(updated_ratings(1600, &[1200]), (1603, vec![1197]));
+    // one pairing per loser
+    assert_eq!(
+        updated_ratings(1200, &[1200, 1200]),
+        (1232, vec![1184, 1184])
+    );
+}
+
+#[tokio::test]
+async fn finished_game_is_recorded() {
+    let (addr, _) = start_server(duel_config()).await;
+    let host = register(addr, "alice").await;
+    let guest = register(addr, "bob").await;
+
+    let mut game = StartedGame::start(host, guest).await;
+    let winner = game.fight().await;
+    let (winner, loser) = if winner == "1" {
+        ("alice", "bob")
+    } else {
+        ("bob", "alice")
+    };
+
+    let mut client = Client::connect(addr).await;
+    let response = stats(&mut client, winner).await;
+    assert_eq!(response["status"], OK_PL_STATS);
+    assert_eq!(response["pseudo"], winner);
+    assert_eq!(response["wins"], 1);
+    assert_eq!(response["losses"], 0);
+    assert_eq!(response["kills"], 1);
+    assert_eq!(response["rating"], 1216);
+    // the loser is dead, all his hp were dealt
+    let loser_hp = if loser == "alice" { 80 } else { 100 };
+    assert_eq!(response["damage_dealt"], loser_hp);
+
+    let response = stats(&mut client, &loser.to_uppercase()).await;
+    assert_eq!(response["losses"], 1);
+    assert_eq!(response["kills"], 0);
+    assert_eq!(response["rating"], 1184);
+    let class = if loser == "alice" { "mag" } else { "bar" };
+    assert_eq!(response["classes"], json!({ class: 1 }));
+
+    let response = client
+        .request(json!({ "request_type": LEADERBOARD, "count": 5 }))
+        .await;
+    assert_eq!(response["status"], OK_LEADERBOARD);
+    let pseudos: Vec<&str> = response["players"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["pseudo"].as_str().unwrap())
+        .collect();
+    assert_eq!(pseudos, [winner, loser]);
+}
+
+#[tokio::test]
+async fn anonymous_players_have_no_stats() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut game = StartedGame::new(addr).await;
+    game.fight().await;
+
+    let mut client = Client::connect(addr).await;
+    assert_eq!(stats(&mut client, "host").await["status"], ERR_NO_STATS);
+    let response = client.request(json!({ "request_type": LEADERBOARD })).await;
+    assert_eq!(response["players"], json!([]));
+}
+
+#[tokio::test]
+async fn games_against_anonymous_players_are_not_rated() {
+    let (addr, _) = start_server(duel_config()).await;
+    let host = register(addr, "alice").await;
+    let mut client = Client::connect(addr).await;
+    let token = client.create_player("anonymous").await;
+    let guest = Player { client, token };
+
+    let mut game = StartedGame::start(host, guest).await;
+    let won = game.fight().await == "1";
+
+    // the game counts in the stats, not in the rating
+    let mut client = Client::connect(addr).await;
+    let response = stats(&mut client, "alice").await;
+    assert_eq!(response["status"], OK_PL_STATS);
+    assert_eq!(response["wins"], won as u32);
+    assert_eq!(response["losses"], !won as u32);
+    assert_eq!(response["rating"], 1200);
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use common::*;
+use game_server::config::{Config, StorageBackend};
+use game_server::game::spawn_game;
+use game_server::server::State;
+use game_server::storage::{unix_time, GameInfo, PlayerInfos, StorageClient};
+use game_server::sweeper::{sweep, SweepStats};
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+use std::sync::Arc;
+
+fn memory_state() -> Arc<State> {
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::Memory;
+    config.expiry.player_ttl = 100;
+    config.expiry.game_ttl = 100;
+    Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ))
+}
+
+fn player(last_activity: u64) -> PlayerInfos {
+    PlayerInfos {
+        pseudo: "player".into(),
+        hosting: 0,
+        last_activity,
+        session_expiry: None,
+    }
+}
+
+fn game(host_player: &str, last_activity: u64) -> GameInfo {
+    GameInfo {
+        started: false,
+        host_player: host_player.into(),
+        player_count: 1,
+        max_players: 2,
+        map: "00\n00\n00".into(),
+        turn: "12".into(),
+        turn_count: 0,
+        last_activity,
+        fog_of_war: false,
+        teams: vec![],
+        chain: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn expired_games_and_players_are_removed() {
+    let state = memory_state();
+    let mut store = state.storage.get_connection().unwrap();
+    let now = unix_time();
+    let stale = now - 1000;
+
+    // stale player alone, stale player hosting a stale game, stale player hosting an active game
+    store.insert_player("idle", &player(stale)).unwrap();
+    store.insert_player("stale_host", &player(stale)).unwrap();
+    store.insert_player("active_host", &player(stale)).unwrap();
+    store.insert_player("fresh", &player(now)).unwrap();
+    store
+        .create_game("stale_game", &game("stale_host", stale), &player(stale))
+        .unwrap();
+    store
+        .create_game("active_game", &game("active_host", now), &player(stale))
+        .unwrap();
+    for game_token in ["stale_game", "active_game"] {
+        let game_info = store.game_info(game_token).unwrap().unwrap();
+        let game = spawn_game(&state, game_token.into(), game_info, vec![]).unwrap();
+        state.state.lock().await.insert(game_token.into(), game);
+    }
+
+    let stats = sweep(&state).await.unwrap();
+    assert_eq!(
+        stats,
+        SweepStats {
+            games_removed: 1,
+            players_removed: 2,
+            tasks_stopped: 1,
+        }
+    );
+
+    assert!(!store.game_exists("stale_game").unwrap());
+    assert!(store.game_exists("active_game").unwrap());
+    assert!(store.player_infos("idle").unwrap().is_none());
+    assert!(store.player_infos("stale_host").unwrap().is_none());
+    assert!(store.player_infos("active_host").unwrap().is_some());
+    assert!(store.player_infos("fresh").unwrap().is_some());
+    let lock = state.state.lock().await;
+    assert!(lock.contains_key("active_game"));
+    assert!(!lock.contains_key("stale_game"));
+}
+
+#[tokio::test]
+async fn touched_player_is_kept() {
+    let state = memory_state();
+    let mut store = state.storage.get_connection().unwrap();
+    store
+        .insert_player("player", &player(unix_time() - 1000))
+        .unwrap();
+    store.touch_player("player", unix_time()).unwrap();
+
+    assert_eq!(sweep(&state).await.unwrap(), SweepStats::default());
+    assert!(store.player_infos("player").unwrap().is_some());
+}
+
+#[tokio::test]
+async fn removed_game_frees_its_host() {
+    let state = memory_state();
+    let mut store = state.storage.get_connection().unwrap();
+    let now = unix_time();
+    store.insert_player("host", &player(now)).unwrap();
+    store
+        .create_game("game", &game("host", now - 1000), &player(now))
+        .unwrap();
+    assert_eq!(store.player_infos("host").unwrap().unwrap().hosting, 1);
+
+    sweep(&state).await.unwrap();
+    assert_eq!(store.player_infos("host").unwrap().unwrap().hosting, 0);
+}
+
+#[tokio::test]
+async fn expired_session_is_removed() {
+    let state = memory_state();
+    let mut store = state.storage.get_connection().unwrap();
+    let now = unix_time();
+    let session = |session_expiry| PlayerInfos {
+        session_expiry: Some(session_expiry),
+        ..player(now)
+    };
+    store.insert_player("expired", &session(now - 1)).unwrap();
+    store.insert_player("valid", &session(now + 100)).unwrap();
+
+    let stats = sweep(&state).await.unwrap();
+    assert_eq!(stats.players_removed, 1);
+    assert!(store.player_infos("expired").unwrap().is_none());
+    assert!(store.player_infos("valid").unwrap().is_some());
+}
+
+#[tokio::test]
+async fn players_of_an_expired_lobby_are_told_and_go_back_to_the_lobby() {
+    let (addr, state) = start_server(test_config()).await;
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+    let mut guest = Client::connect(addr).await;
+    let guest_token = guest.create_player("guest").await;
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    let game_token = response["game_token"].as_str().unwrap().to_string();
+    let join = json!({
+        "request_type": GM_JOIN,
+        "player_token": guest_token,
+        "game_token": game_token,
+    });
+    assert_eq!(guest.request(join).await["status"], OK_GM_JOIN);
+    assert_eq!(host.recv().await["status"], OK_GM_JOIN);
+
+    let mut store = state.storage.get_connection().unwrap();
+    let mut game_info = store.game_info(&game_token).unwrap().unwrap();
+    game_info.last_activity -= state.expiry.game_ttl;
+    store.set_game_info(&game_token, &game_info).unwrap();
+    assert_eq!(sweep(&state).await.unwrap().tasks_stopped, 1);
+
+    for client in [&mut host, &mut guest] {
+        let game_over = client.recv().await;
+        assert_eq!(game_over["status"], OK_GM_OVER);
+        assert_eq!(game_over["winner"], "");
+    }
+    // both connections are back in the lobby
+    let response = host
+        .request(json!({ "request_type": GM_CREAT, "player_token": host_token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+    let response = guest
+        .request(json!({ "request_type": GM_CREAT, "player_token": guest_token }))
+        .await;
+    assert_eq!(response["status"], OK_GM_CREAT);
+}
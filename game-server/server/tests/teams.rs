@@ -0,0 +1,228 @@
+// 2v2 games: teams chosen by the host, turns alternating between the teams, no friendly fire
+// and the game over once a team is eliminated
+mod common;
+
+use common::{duel_config, start_server, Client};
+use net_utils::packet::game_data_code::*;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+// spawn of each player on the 2x2 map, everyone is in range of everyone
+const SPAWNS: [[u8; 2]; 4] = [[0, 0], [1, 1], [1, 0], [0, 1]];
+
+fn team(player_num: &str) -> &'static str {
+    match player_num {
+        "1" | "3" => "13",
+        _ => "24",
+    }
+}
+
+struct TeamGame {
+    // ordered by player number
+    players: Vec<(Client, String)>,
+    game_token: String,
+    player_turn: String,
+}
+
+impl TeamGame {
+    // four barbarians, players 1 and 3 against players 2 and 4
+    async fn start(addr: SocketAddr) -> Self {
+        let mut players = vec![];
+        for pseudo in ["p1", "p2", "p3", "p4"] {
+            let mut client = Client::connect(addr).await;
+            let token = client.create_player(pseudo).await;
+            players.push((client, token));
+        }
+
+        let create = json!({
+            "request_type": GM_CREAT,
+            "player_token": players[0].1,
+            "teams": ["31", "24"],
+        });
+        let response = players[0].0.request(create).await;
+        assert_eq!(response["status"], OK_GM_CREAT);
+        let game_token = response["game_token"].as_str().unwrap().to_string();
+
+        for i in 1..4 {
+            let join = json!({
+                "request_type": GM_JOIN,
+                "player_token": players[i].1,
+                "game_token": game_token,
+            });
+            let response = players[i].0.request(join).await;
+            assert_eq!(response["status"], OK_GM_JOIN);
+            let teams: Vec<&Value> = response["player_vec"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|p| &p[4])
+                .collect();
+            assert_eq!(teams, ["1", "2", "1", "2"][..=i]);
+            for (client, _) in &mut players[..i] {
+                assert_eq!(client.recv().await, response);
+            }
+        }
+
+        for i in 0..4 {
+            let choose = json!({
+                "request_type": CHAR_CHOOSING,
+                "player_token": players[i].1,
+                "game_token": game_token,
+                "character": "bar",
+            });
+            let response = players[i].0.request(choose).await;
+            assert_eq!(response["status"], OK_CHAR_CHOOSING);
+            for (j, (client, _)) in players.iter_mut().enumerate() {
+                if j != i {
+                    assert_eq!(client.recv().await["status"], OK_CHAR_CHOOSING);
+                }
+            }
+        }
+
+        let start = json!({
+            "request_type": GM_START,
+            "player_token": players[0].1,
+            "game_token": game_token,
+        });
+        let response = players[0].0.request(start).await;
+        assert_eq!(response["status"], OK_GM_START);
+        for (client, _) in &mut players[1..] {
+            assert_eq!(client.recv().await["status"], OK_GM_START);
+        }
+
+        Self {
+            players,
+            game_token,
+            player_turn: response["player_turn"].as_str().unwrap().into(),
+        }
+    }
+
+    fn index(player_num: &str) -> usize {
+        player_num.parse::<usize>().unwrap() - 1
+    }
+
+    // action of the current player, the others get the game data
+    async fn play(&mut self, gm_code: u64, target: [u8; 2]) -> Value {
+        let current = Self::index(&self.player_turn);
+        let (client, token) = &mut self.players[current];
+        let action = json!({
+            "request_type": GM_DATA,
+            "gm_code": gm_code,
+            "target": target,
+            "player_token": token,
+            "game_token": self.game_token,
+        });
+        let response = client.request(action).await;
+        if response["status"] == OK_GM_DATA {
+            for (i, (client, _)) in self.players.iter_mut().enumerate() {
+                if i != current {
+                    assert_eq!(client.recv().await["status"], OK_GM_DATA);
+                }
+            }
+            self.player_turn = response["player_turn"].as_str().unwrap().into();
+        }
+        response
+    }
+}
+
+#[tokio::test]
+async fn teams_are_checked_at_creation() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut host = Client::connect(addr).await;
+    let host_token = host.create_player("host").await;
+
+    for teams in [
+        json!("1324"),
+        json!(["12"]),
+        json!(["12", "13"]),
+        json!(["123", "4"]),
+        json!(["12", "34", ""]),
+        json!([12, 34]),
+    ] {
+        let create = json!({
+            "request_type": GM_CREAT,
+            "player_token": host_token,
+            "teams": teams,
+        });
+        assert_eq!(host.request(create).await["status"], ERR_MAL_REQ, "{teams}");
+    }
+}
+
+#[tokio::test]
+async fn the_last_team_standing_wins() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut game = TeamGame::start(addr).await;
+
+    // no friendly fire
+    let teammate = match &*game.player_turn {
+        "1" => "3",
+        "3" => "1",
+        "2" => "4",
+        _ => "2",
+    };
+    let response = game
+        .play(GM_DATA_ATK, SPAWNS[TeamGame::index(teammate)])
+        .await;
+    assert_eq!(response["status"], ERR_MAL_REQ);
+
+    // players 1 and 3 go after player 2 then player 4, the others skip their turns
+    let mut alive = vec!["2", "4"];
+    let winner = loop {
+        let previous_team = team(&game.player_turn);
+        let response = match previous_team {
+            "13" => {
+                let target = SPAWNS[TeamGame::index(alive[0])];
+                game.play(GM_DATA_ATK, target).await
+            }
+            _ => game.play(GM_DATA_SKIP, [0, 0]).await,
+        };
+        assert_eq!(response["status"], OK_GM_DATA);
+        if response["data_type"] == GM_DATA_ATK && response["enemy"][1] == 0 {
+            alive.remove(0);
+            if alive.is_empty() {
+                break previous_team;
+            }
+        } else if alive.len() == 2 {
+            // the teams play in turn while every player is alive
+            assert_ne!(team(&game.player_turn), previous_team);
+        }
+    };
+
+    for (client, _) in &mut game.players {
+        let response = client.recv().await;
+        assert_eq!(response, json!({ "status": OK_GM_OVER, "winner": winner }));
+    }
+}
+
+#[tokio::test]
+async fn teams_keep_alternating_after_a_death() {
+    let (addr, _) = start_server(duel_config()).await;
+    let mut game = TeamGame::start(addr).await;
+
+    // players 1 and 3 go after player 2, the others skip their turns
+    let killer = loop {
+        let player_num = game.player_turn.clone();
+        let response = match team(&player_num) {
+            "13" => game.play(GM_DATA_ATK, SPAWNS[1]).await,
+            _ => game.play(GM_DATA_SKIP, [0, 0]).await,
+        };
+        assert_eq!(response["status"], OK_GM_DATA);
+        if response["data_type"] == GM_DATA_ATK && response["enemy"][1] == 0 {
+            break player_num;
+        }
+    };
+    let teammate = match &*killer {
+        "1" => "3",
+        _ => "1",
+    };
+
+    // the lone player 4 plays every other turn, players 1 and 3 still take turns
+    for expected in ["4", teammate, "4", &killer] {
+        assert_eq!(game.player_turn, expected);
+        let response = game.play(GM_DATA_SKIP, [0, 0]).await;
+        assert_eq!(response["status"], OK_GM_DATA);
+    }
+    assert_eq!(game.player_turn, "4");
+}
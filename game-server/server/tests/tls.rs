@@ -0,0 +1,150 @@
+use game_server::config::{Config, StorageBackend};
+use game_server::server::{serve, State};
+use game_server::storage::StorageClient;
+use game_server::stream::{read_packet, write_packet_from_json, PacketStream};
+use game_server::tls::load_acceptor;
+use net_utils::packet::request_codes::PL_CREAT;
+use net_utils::packet::status_codes::OK_PL_CREAT;
+use rcgen::{generate_simple_self_signed, CertifiedKey};
+use serde_json::json;
+use std::fs;
+use std::future::pending;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{timeout, Instant};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+// self-signed certificate for "localhost" written to a per-test directory
+fn write_self_signed_cert(test_name: &str) -> (PathBuf, PathBuf, CertifiedKey) {
+    let certified_key = generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let dir = std::env::temp_dir().join(format!("game_server_{test_name}_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+    fs::write(&cert_path, certified_key.cert.pem()).unwrap();
+    fs::write(&key_path, certified_key.key_pair.serialize_pem()).unwrap();
+
+    (cert_path, key_path, certified_key)
+}
+
+fn connector(roots: RootCertStore) -> TlsConnector {
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    TlsConnector::from(Arc::new(config))
+}
+
+#[tokio::test]
+async fn packet_exchange_over_tls() {
+    let (cert_path, key_path, certified_key) = write_self_signed_cert("exchange");
+    let acceptor = load_acceptor(&cert_path, &key_path).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // echo server answering one packet
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut stream = PacketStream::new(acceptor.accept(stream).await.unwrap());
+        let json = read_packet(&mut stream).await.unwrap();
+        write_packet_from_json(&mut stream, &json.to_string())
+            .await
+            .unwrap();
+    });
+
+    let mut roots = RootCertStore::empty();
+    roots.add(certified_key.cert.der().clone()).unwrap();
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut stream = PacketStream::new(
+        connector(roots)
+            .connect(ServerName::try_from("localhost").unwrap(), tcp)
+            .await
+            .unwrap(),
+    );
+
+    let request = json!({ "request_type": 11, "pseudo": "coco" });
+    write_packet_from_json(&mut stream, &request.to_string())
+        .await
+        .unwrap();
+    assert_eq!(read_packet(&mut stream).await.unwrap(), request);
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn untrusted_certificate_is_rejected() {
+    let (cert_path, key_path, _) = write_self_signed_cert("untrusted");
+    let acceptor = load_acceptor(&cert_path, &key_path).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        acceptor.accept(stream).await.is_err()
+    });
+
+    // client trusting an unrelated ca
+    let other = generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let mut roots = RootCertStore::empty();
+    roots.add(other.cert.der().clone()).unwrap();
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let ret = connector(roots)
+        .connect(ServerName::try_from("localhost").unwrap(), tcp)
+        .await;
+
+    assert!(ret.is_err());
+    assert!(server.await.unwrap());
+}
+
+#[tokio::test]
+async fn stalled_handshakes_are_dropped() {
+    let (cert_path, key_path, certified_key) = write_self_signed_cert("stalled");
+    let mut config = Config::default();
+    config.storage.backend = StorageBackend::Memory;
+    config.handshake_timeout = 1;
+    let state = Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let acceptor = load_acceptor(&cert_path, &key_path).unwrap();
+    tokio::spawn(serve(listener, None, state, Some(acceptor), pending()));
+
+    // connected, but no client hello
+    let mut stalled = TcpStream::connect(addr).await.unwrap();
+    let start = Instant::now();
+    let mut buf = [0; 1];
+    let read = timeout(Duration::from_secs(5), stalled.read(&mut buf)).await;
+    assert!(matches!(read, Ok(Ok(0) | Err(_))));
+    assert!(start.elapsed() >= Duration::from_secs(1));
+
+    // the handshakes that go through are served
+    let mut roots = RootCertStore::empty();
+    roots.add(certified_key.cert.der().clone()).unwrap();
+    let tcp = TcpStream::connect(addr).await.unwrap();
+    let mut stream = PacketStream::new(
+        connector(roots)
+            .connect(ServerName::try_from("localhost").unwrap(), tcp)
+            .await
+            .unwrap(),
+    );
+    let request = json!({ "request_type": PL_CREAT, "pseudo": "player" });
+    write_packet_from_json(&mut stream, &request.to_string())
+        .await
+        .unwrap();
+    let response = read_packet(&mut stream).await.unwrap();
+    assert_eq!(response["status"], OK_PL_CREAT);
+}
+
+#[test]
+fn missing_key_file_is_an_error() {
+    let (cert_path, _, _) = write_self_signed_cert("missing_key");
+    let key_path = cert_path.with_file_name("does_not_exist.pem");
+    assert!(load_acceptor(&cert_path, &key_path).is_err());
+}
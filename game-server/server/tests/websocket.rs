@@ -0,0 +1,100 @@
+mod common;
+
+use common::{duel_config, start_websocket_server, test_config, Client, Player, StartedGame};
+use futures_util::SinkExt;
+use game_server::server::State;
+use game_server::storage::StorageClient;
+use game_server::websocket::handle_websocket;
+use net_utils::packet::request_codes::*;
+use net_utils::packet::status_codes::*;
+use serde_json::json;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+async fn player(mut client: Client, pseudo: &str) -> Player {
+    let token = client.create_player(pseudo).await;
+    Player { client, token }
+}
+
+#[tokio::test]
+async fn full_game_over_websocket() {
+    let (_, ws_addr, state) = start_websocket_server(duel_config()).await;
+
+    let host = player(Client::connect_websocket(ws_addr).await, "host").await;
+    let guest = player(Client::connect_websocket(ws_addr).await, "guest").await;
+    let mut game = StartedGame::start(host, guest).await;
+    game.fight().await;
+    assert!(!state.state.lock().await.contains_key(&game.game_token));
+
+    // back to the lobby, then the connection is closed
+    for player in [&mut game.host, &mut game.guest] {
+        let response = player
+            .client
+            .request(json!({ "request_type": TERM_CON }))
+            .await;
+        assert_eq!(response["status"], OK_TERM_CON);
+        assert!(player.client.closed().await);
+    }
+}
+
+#[tokio::test]
+async fn websocket_and_tcp_players_share_games() {
+    let (addr, ws_addr, _) = start_websocket_server(duel_config()).await;
+
+    let host = player(Client::connect(addr).await, "host").await;
+    let guest = player(Client::connect_websocket(ws_addr).await, "guest").await;
+    let mut game = StartedGame::start(host, guest).await;
+    game.fight().await;
+
+    // errors are sent over websocket too
+    let join = json!({
+        "request_type": GM_JOIN,
+        "player_token": "00000000-0000-0000-0000-000000000000",
+        "game_token": game.game_token,
+    });
+    let response = game.guest.client.request(join).await;
+    assert_eq!(response["status"], ERR_INV_PL_TOK);
+}
+
+// handler of the next websocket connection, its result once the connection is over
+async fn websocket_handler() -> (SocketAddr, JoinHandle<anyhow::Result<()>>) {
+    let config = test_config();
+    let state = Arc::new(State::new(
+        &config,
+        StorageClient::open(&config.storage).unwrap(),
+        None,
+    ));
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = tokio::spawn(async move {
+        let (stream, addr) = listener.accept().await.unwrap();
+        let mut store = state.storage.get_connection().unwrap();
+        handle_websocket(state, stream, addr.ip(), &mut *store).await
+    });
+    (addr, handler)
+}
+
+#[tokio::test]
+async fn closing_the_websocket_is_a_normal_disconnection() {
+    // close frame
+    let (addr, handler) = websocket_handler().await;
+    let mut client = Client::connect_websocket(addr).await;
+    client.create_player("player").await;
+    if let Client::WebSocket(stream) = &mut client {
+        stream.close().await.unwrap();
+    }
+    let result = timeout(Duration::from_secs(5), handler).await.unwrap();
+    result.unwrap().unwrap();
+
+    // dropped connection
+    let (addr, handler) = websocket_handler().await;
+    let mut client = Client::connect_websocket(addr).await;
+    client.create_player("player").await;
+    drop(client);
+    let result = timeout(Duration::from_secs(5), handler).await.unwrap();
+    result.unwrap().unwrap();
+}